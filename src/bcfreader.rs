@@ -0,0 +1,631 @@
+use crate::error::Error;
+use crate::interval_set::Interval;
+use noodles_bgzf as bgzf;
+use noodles_csi::{self as csi, BinningIndex};
+use noodles_vcf as vcf;
+use noodles_vcf::record::Record;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::string::String;
+use std::vec::Vec;
+
+const BCF_MAGIC: &[u8; 5] = b"BCF\x02\x02"; // BCF major version 2, minor version 2
+
+// BCF2 typed value types
+const BCF_INT8: u8 = 1;
+const BCF_INT16: u8 = 2;
+const BCF_INT32: u8 = 3;
+const BCF_FLOAT: u8 = 5;
+const BCF_CHAR: u8 = 7;
+
+const BCF_FLOAT_MISSING: u32 = 0x7F80_0001;
+const BCF_FLOAT_END_OF_VECTOR: u32 = 0x7F80_0002;
+
+// upper bound of the header text and of a single record, a larger length word means a corrupt file
+const BCF_MAX_BLOCK_LENGTH: usize = 1 << 28;
+// CHROM, POS, rlen, QUAL, n_info/n_allele and n_fmt/n_sample are always in the shared part
+const BCF_MIN_SHARED_LENGTH: usize = 24;
+
+/// a single value of a BCF2 typed vector
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BcfValue {
+    Int(i32),
+    Float(f32),
+    Missing,
+    EndOfVector,
+}
+
+/// a cursor over the bytes of a BCF record
+struct ByteCursor<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + n)
+            .ok_or_else(|| Error::parse("Truncated BCF record"))?;
+        self.position += n;
+        Ok(bytes)
+    }
+
+    fn read_i32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// the type and length of a typed value
+    fn read_type(&mut self) -> Result<(u8, usize), Error> {
+        let descriptor = self.take(1)?[0];
+        let value_type = descriptor & 0x0F;
+        let length = match descriptor >> 4 {
+            15 => match self.read_typed()?.first() {
+                Some(BcfValue::Int(n)) if *n >= 0 => *n as usize,
+                _ => return Err(Error::parse("Invalid BCF typed vector length")),
+            },
+            n => n as usize,
+        };
+        Ok((value_type, length))
+    }
+
+    fn read_value(&mut self, value_type: u8) -> Result<BcfValue, Error> {
+        Ok(match value_type {
+            BCF_INT8 => match self.take(1)?[0] as i8 {
+                i8::MIN => BcfValue::Missing,
+                n if n == i8::MIN + 1 => BcfValue::EndOfVector,
+                n => BcfValue::Int(n as i32),
+            },
+            BCF_INT16 => match i16::from_le_bytes(self.take(2)?.try_into().unwrap()) {
+                i16::MIN => BcfValue::Missing,
+                n if n == i16::MIN + 1 => BcfValue::EndOfVector,
+                n => BcfValue::Int(n as i32),
+            },
+            BCF_INT32 => match self.read_i32()? {
+                i32::MIN => BcfValue::Missing,
+                n if n == i32::MIN + 1 => BcfValue::EndOfVector,
+                n => BcfValue::Int(n),
+            },
+            BCF_FLOAT => match self.read_u32()? {
+                BCF_FLOAT_MISSING => BcfValue::Missing,
+                BCF_FLOAT_END_OF_VECTOR => BcfValue::EndOfVector,
+                n => BcfValue::Float(f32::from_bits(n)),
+            },
+            t => return Err(Error::parse(format!("Unsupported BCF value type {}", t))),
+        })
+    }
+
+    fn read_values(&mut self, value_type: u8, length: usize) -> Result<Vec<BcfValue>, Error> {
+        (0..length).map(|_| self.read_value(value_type)).collect()
+    }
+
+    fn read_string(&mut self, length: usize) -> Result<String, Error> {
+        let bytes = self.take(length)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+        Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    /// a typed vector of numbers
+    fn read_typed(&mut self) -> Result<Vec<BcfValue>, Error> {
+        let (value_type, length) = self.read_type()?;
+        match value_type {
+            0 => Ok(vec![]),
+            BCF_CHAR => Err(Error::parse("Expected a numeric BCF value")),
+            _ => self.read_values(value_type, length),
+        }
+    }
+
+    /// a typed string
+    fn read_typed_string(&mut self) -> Result<String, Error> {
+        match self.read_type()? {
+            (0, _) => Ok(String::new()),
+            (BCF_CHAR, length) => self.read_string(length),
+            _ => Err(Error::parse("Expected a BCF string")),
+        }
+    }
+}
+
+/// render a float with the shortest digits that parse back to the same f32, e.g. 0.1 rather than 0.100000001
+fn format_float(value: f32) -> String {
+    value.to_string()
+}
+
+/// read exactly `length` bytes, growing the buffer only with the data actually present
+fn read_block<R: Read>(inner: &mut R, length: usize, name: &str) -> Result<Vec<u8>, Error> {
+    if length > BCF_MAX_BLOCK_LENGTH {
+        return Err(Error::parse(format!(
+            "Invalid BCF {} length {}",
+            name, length
+        )));
+    }
+    let mut block = Vec::new();
+    inner.take(length as u64).read_to_end(&mut block)?;
+    if block.len() < length {
+        return Err(Error::parse(format!("Truncated BCF {}", name)));
+    }
+    Ok(block)
+}
+
+/// render numeric values as a vcf field, e.g. 1,.,3
+fn format_values(values: &[BcfValue]) -> String {
+    let rendered: Vec<String> = values
+        .iter()
+        .take_while(|&&v| v != BcfValue::EndOfVector)
+        .map(|v| match v {
+            BcfValue::Int(n) => n.to_string(),
+            BcfValue::Float(n) => format_float(*n),
+            _ => ".".to_string(),
+        })
+        .collect();
+    match rendered.is_empty() {
+        true => ".".to_string(),
+        false => rendered.join(","),
+    }
+}
+
+/// render BCF-encoded genotype alleles as a vcf GT field, e.g. 0/1 or 0|1
+fn format_genotype(values: &[BcfValue]) -> String {
+    let mut genotype = String::new();
+    for (i, value) in values
+        .iter()
+        .take_while(|&&v| v != BcfValue::EndOfVector)
+        .enumerate()
+    {
+        let encoded = match value {
+            BcfValue::Int(n) => *n,
+            _ => 0,
+        };
+        if i > 0 {
+            genotype.push(if encoded & 1 == 1 { '|' } else { '/' });
+        }
+        match (encoded >> 1) - 1 {
+            allele if allele < 0 => genotype.push('.'),
+            allele => genotype.push_str(&allele.to_string()),
+        }
+    }
+    match genotype.is_empty() {
+        true => ".".to_string(),
+        false => genotype,
+    }
+}
+
+/// the ID and optional IDX attributes of a structured header line, e.g. ##INFO=<ID=DP,...>
+fn header_line_id(line: &str) -> Option<(String, Option<usize>)> {
+    let attribute = |name: &str| {
+        let start = line
+            .find(&format!("<{}=", name))
+            .map(|i| i + 1)
+            .or_else(|| line.find(&format!(",{}=", name)).map(|i| i + 1))?
+            + name.len()
+            + 1;
+        let value = &line[start..];
+        let end = value.find([',', '>']).unwrap_or(value.len());
+        Some(value[..end].to_string())
+    };
+    let id = attribute("ID")?;
+    let idx = attribute("IDX").and_then(|idx| idx.parse::<usize>().ok());
+    Some((id, idx))
+}
+
+/// insert a dictionary entry at its IDX, or append it if it is new
+fn add_dictionary_entry(dictionary: &mut Vec<String>, id: String, idx: Option<usize>) {
+    match idx {
+        Some(idx) => {
+            if dictionary.len() <= idx {
+                dictionary.resize(idx + 1, String::new());
+            }
+            dictionary[idx] = id;
+        }
+        None => {
+            if !dictionary.contains(&id) {
+                dictionary.push(id);
+            }
+        }
+    }
+}
+
+/// the undecoded bytes of a BCF record and its position
+struct RawRecord {
+    contig_id: usize,
+    /// 1-based start position
+    start: usize,
+    /// 1-based end position (inclusive)
+    end: usize,
+    data: Vec<u8>,
+}
+
+/// A reader for BCF (binary vcf) files, each record is decoded into a noodles vcf record
+pub struct BcfReader<R: Read> {
+    inner: R,
+    header: vcf::Header,
+    /// the dictionary of FILTER, INFO and FORMAT keys
+    strings: Vec<String>,
+    /// the dictionary of contig names
+    contigs: Vec<String>,
+}
+
+impl BcfReader<bgzf::Reader<File>> {
+    /// Open a BCF file
+    ///
+    /// # Arguments
+    /// * `bcf_file`: the file path of a (bgzipped) BCF file
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::bcfreader::BcfReader;
+    /// let mut reader = BcfReader::open("data/test.bcf").unwrap();
+    /// let record = reader.read_record().unwrap().unwrap();
+    /// assert_eq!(record.chromosome().to_string(), "X");
+    /// ```
+    pub fn open(bcf_file: &str) -> Result<Self, Error> {
+        let file = File::open(bcf_file).map_err(|e| Error::io(bcf_file, e))?;
+        BcfReader::new(bgzf::Reader::new(file)).map_err(|e| e.with_file(bcf_file))
+    }
+
+    /// Fetch the records overlapping an interval using a CSI index
+    ///
+    /// # Arguments
+    /// * `index`: the CSI index of the BCF file
+    /// * `interval`: the interval to fetch
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::bcfreader::BcfReader;
+    /// let index = noodles_csi::read("data/test.bcf.csi").unwrap();
+    /// let mut reader = BcfReader::open("data/test.bcf").unwrap();
+    /// let interval = "X:38144665-38144669".parse().unwrap();
+    /// let records = reader.query(&index, &interval).unwrap();
+    /// assert_eq!(records.len(), 1);
+    /// ```
+    pub fn query(&mut self, index: &csi::Index, interval: &Interval) -> Result<Vec<Record>, Error> {
+        let reference_sequence_id = match self.contigs.iter().position(|c| *c == interval.contig) {
+            Some(id) => id,
+            None => return Ok(vec![]),
+        };
+        let chunks = index
+            .query(reference_sequence_id, interval.to_region()?.interval())
+            .map_err(|e| Error::index(format!("Cannot query {}: {}", interval, e)))?;
+        let mut records: Vec<Record> = vec![];
+        for chunk in chunks {
+            self.inner.seek(chunk.start())?;
+            while self.inner.virtual_position() < chunk.end() {
+                let raw_record = match self.read_raw_record()? {
+                    Some(raw_record) => raw_record,
+                    None => break,
+                };
+                if raw_record.contig_id == reference_sequence_id
+                    && interval.overlaps(&interval.contig, raw_record.start, raw_record.end)
+                {
+                    records.push(self.decode_record(&raw_record.data)?);
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+impl<R: Read> BcfReader<R> {
+    /// Read the BCF header from a decompressed BCF stream
+    pub fn new(mut inner: R) -> Result<Self, Error> {
+        let mut magic = [0; 5];
+        inner
+            .read_exact(&mut magic)
+            .map_err(|_| Error::parse("Invalid BCF file"))?;
+        if &magic != BCF_MAGIC {
+            return Err(Error::parse(
+                "Invalid BCF file, only BCF version 2.2 is supported",
+            ));
+        }
+        let mut l_text = [0; 4];
+        inner.read_exact(&mut l_text)?;
+        let text = read_block(&mut inner, u32::from_le_bytes(l_text) as usize, "header")?;
+        let text = String::from_utf8_lossy(&text)
+            .trim_end_matches('\0')
+            .to_string();
+
+        let mut strings: Vec<String> = vec!["PASS".to_string()];
+        let mut contigs: Vec<String> = vec![];
+        for line in text.lines() {
+            if line.starts_with("##FILTER=<")
+                || line.starts_with("##INFO=<")
+                || line.starts_with("##FORMAT=<")
+            {
+                if let Some((id, idx)) = header_line_id(line) {
+                    add_dictionary_entry(&mut strings, id, idx);
+                }
+            } else if line.starts_with("##contig=<") {
+                if let Some((id, idx)) = header_line_id(line) {
+                    add_dictionary_entry(&mut contigs, id, idx);
+                }
+            }
+        }
+        let header = text
+            .parse()
+            .map_err(|_| Error::parse("Cannot parse header properly"))?;
+        Ok(Self {
+            inner,
+            header,
+            strings,
+            contigs,
+        })
+    }
+
+    /// the vcf header of the BCF file
+    pub fn header(&self) -> &vcf::Header {
+        &self.header
+    }
+
+    /// Read the next record, or None at the end of the file
+    pub fn read_record(&mut self) -> Result<Option<Record>, Error> {
+        match self.read_raw_record()? {
+            Some(raw_record) => Ok(Some(self.decode_record(&raw_record.data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// read the bytes of the next record, together with its contig id and 1-based start/end
+    fn read_raw_record(&mut self) -> Result<Option<RawRecord>, Error> {
+        let mut lengths = [0; 8];
+        match self.inner.read_exact(&mut lengths) {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let l_shared = u32::from_le_bytes(lengths[..4].try_into().unwrap()) as usize;
+        let l_indiv = u32::from_le_bytes(lengths[4..].try_into().unwrap()) as usize;
+        if l_shared < BCF_MIN_SHARED_LENGTH || l_shared + l_indiv > BCF_MAX_BLOCK_LENGTH {
+            return Err(Error::parse(format!(
+                "Invalid BCF record length {}+{}",
+                l_shared, l_indiv
+            )));
+        }
+        let mut record = lengths.to_vec();
+        record.extend(read_block(&mut self.inner, l_shared + l_indiv, "record")?);
+        let mut cursor = ByteCursor {
+            data: &record[8..],
+            position: 0,
+        };
+        let contig_id = cursor.read_i32()?;
+        let start = cursor.read_i32()?;
+        let reference_length = cursor.read_i32()?;
+        if contig_id < 0 || start < 0 {
+            return Err(Error::parse("Invalid BCF record position"));
+        }
+        let start = start as usize + 1;
+        let end = start + reference_length.max(1) as usize - 1;
+        Ok(Some(RawRecord {
+            contig_id: contig_id as usize,
+            start,
+            end,
+            data: record,
+        }))
+    }
+
+    fn dictionary_string(&self, value: &BcfValue) -> Result<&str, Error> {
+        match value {
+            BcfValue::Int(i) => self
+                .strings
+                .get(*i as usize)
+                .map(|s| s.as_str())
+                .ok_or_else(|| Error::parse(format!("BCF key {} is not defined in the header", i))),
+            _ => Err(Error::parse("Invalid BCF key")),
+        }
+    }
+
+    /// decode the bytes of a record into a vcf record
+    fn decode_record(&self, record: &[u8]) -> Result<Record, Error> {
+        let l_shared = u32::from_le_bytes(record[..4].try_into().unwrap()) as usize;
+        let mut shared = ByteCursor {
+            data: &record[8..8 + l_shared],
+            position: 0,
+        };
+        let mut indiv = ByteCursor {
+            data: &record[8 + l_shared..],
+            position: 0,
+        };
+
+        let contig_id = shared.read_i32()? as usize;
+        let position = shared.read_i32()? + 1;
+        shared.read_i32()?; // rlen
+        let quality = match shared.read_value(BCF_FLOAT)? {
+            BcfValue::Float(quality) => format_float(quality),
+            _ => ".".to_string(),
+        };
+        let n_allele_info = shared.read_u32()?;
+        let n_fmt_sample = shared.read_u32()?;
+        let (n_allele, n_info) = ((n_allele_info >> 16) as usize, n_allele_info & 0xFFFF);
+        let (n_fmt, n_sample) = ((n_fmt_sample >> 24) as usize, n_fmt_sample & 0xFF_FFFF);
+
+        let id = match shared.read_typed_string()? {
+            id if id.is_empty() => ".".to_string(),
+            id => id,
+        };
+        let alleles: Vec<String> = (0..n_allele)
+            .map(|_| shared.read_typed_string())
+            .collect::<Result<Vec<String>, Error>>()?;
+        let filters = shared
+            .read_typed()?
+            .iter()
+            .map(|value| self.dictionary_string(value))
+            .collect::<Result<Vec<&str>, Error>>()?;
+        let mut infos: Vec<String> = vec![];
+        for _ in 0..n_info {
+            let key =
+                self.dictionary_string(shared.read_typed()?.first().unwrap_or(&BcfValue::Missing))?;
+            let info = match shared.read_type()? {
+                (0, _) | (_, 0) => key.to_string(), // flag
+                (BCF_CHAR, length) => format!("{}={}", key, shared.read_string(length)?),
+                (value_type, length) => format!(
+                    "{}={}",
+                    key,
+                    format_values(&shared.read_values(value_type, length)?)
+                ),
+            };
+            infos.push(info);
+        }
+
+        let mut format_keys: Vec<&str> = vec![];
+        let mut sample_fields: Vec<Vec<String>> = vec![vec![]; n_sample as usize];
+        for _ in 0..n_fmt {
+            let key =
+                self.dictionary_string(indiv.read_typed()?.first().unwrap_or(&BcfValue::Missing))?;
+            format_keys.push(key);
+            let (value_type, length) = indiv.read_type()?;
+            for fields in sample_fields.iter_mut() {
+                let field = match value_type {
+                    BCF_CHAR => match indiv.read_string(length)? {
+                        s if s.is_empty() => ".".to_string(),
+                        s => s,
+                    },
+                    _ => {
+                        let values = indiv.read_values(value_type, length)?;
+                        match key {
+                            "GT" => format_genotype(&values),
+                            _ => format_values(&values),
+                        }
+                    }
+                };
+                fields.push(field);
+            }
+        }
+
+        let contig = self.contigs.get(contig_id).ok_or_else(|| {
+            Error::parse(format!(
+                "BCF contig {} is not defined in the header",
+                contig_id
+            ))
+        })?;
+        let mut line = vec![
+            contig.to_string(),
+            position.to_string(),
+            id,
+            alleles.first().cloned().unwrap_or_else(|| "N".to_string()),
+            match alleles.len() > 1 {
+                true => alleles[1..].join(","),
+                false => ".".to_string(),
+            },
+            quality,
+            match filters.is_empty() {
+                true => ".".to_string(),
+                false => filters.join(";"),
+            },
+            match infos.is_empty() {
+                true => ".".to_string(),
+                false => infos.join(";"),
+            },
+        ];
+        if n_fmt > 0 {
+            line.push(format_keys.join(":"));
+            line.extend(sample_fields.iter().map(|fields| fields.join(":")));
+        }
+        Record::try_from_str(&line.join("\t"), &self.header).map_err(|e| {
+            Error::parse(format!("Cannot decode BCF record: {}", e))
+                .with_record(&format!("{}:{}", contig, position))
+        })
+    }
+}
+
+impl<R: Read> Iterator for BcfReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vcfreader::open_vcf_reader;
+    use rstest::*;
+
+    #[test]
+    fn test_read_record() {
+        // the BCF file is encoded from data/test.vcf
+        let vcf_records: Vec<Record> = open_vcf_reader("data/test.vcf")
+            .unwrap()
+            .map(|record| record.unwrap())
+            .collect();
+        let bcf_records: Vec<Record> = BcfReader::open("data/test.bcf")
+            .unwrap()
+            .map(|record| record.unwrap())
+            .collect();
+        assert_eq!(bcf_records, vcf_records);
+    }
+
+    #[rstest]
+    #[case("X:38144665-38144669", 1)]
+    #[case("X:38145491-38145540", 7)]
+    #[case("X:1-100", 0)]
+    #[case("1:1-100000000", 0)]
+    #[case("chrUn:1-100", 0)]
+    fn test_query(#[case] region: &str, #[case] expected_number_records: usize) {
+        let index = csi::read("data/test.bcf.csi").unwrap();
+        let mut reader = BcfReader::open("data/test.bcf").unwrap();
+        let records = reader.query(&index, &region.parse().unwrap()).unwrap();
+        assert_eq!(records.len(), expected_number_records);
+    }
+
+    #[rstest]
+    #[case(vec![BcfValue::Int(2), BcfValue::Int(4)], "0/1")]
+    #[case(vec![BcfValue::Int(2), BcfValue::Int(5)], "0|1")]
+    #[case(vec![BcfValue::Int(0), BcfValue::Int(0)], "./.")]
+    #[case(vec![BcfValue::Int(4), BcfValue::EndOfVector], "1")]
+    #[case(vec![BcfValue::Missing], ".")]
+    fn test_format_genotype(#[case] values: Vec<BcfValue>, #[case] expected: &str) {
+        assert_eq!(format_genotype(&values), expected);
+    }
+
+    #[rstest]
+    #[case(0.1)]
+    #[case(0.333)]
+    #[case(1e-8)]
+    #[case(f32::MAX)]
+    fn test_format_float(#[case] value: f32) {
+        assert_eq!(format_float(value).parse::<f32>().unwrap(), value);
+    }
+
+    /// an uncompressed BCF stream with a minimal header followed by `records`
+    fn bcf_stream(records: &[u8]) -> Vec<u8> {
+        let text = b"##fileformat=VCFv4.2\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\n\0";
+        let mut stream = BCF_MAGIC.to_vec();
+        stream.extend((text.len() as u32).to_le_bytes());
+        stream.extend(text);
+        stream.extend(records);
+        stream
+    }
+
+    #[rstest]
+    #[case(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff], "Invalid BCF record length")]
+    #[case(&[4, 0, 0, 0, 0, 0, 0, 0], "Invalid BCF record length")]
+    #[case(&[32, 0, 0, 0, 0, 0, 0, 0, 1, 2, 3], "Truncated BCF record")]
+    fn test_read_record_corrupt_length(#[case] record: &[u8], #[case] expected: &str) {
+        let mut reader = BcfReader::new(std::io::Cursor::new(bcf_stream(record))).unwrap();
+        let error = reader.read_record().unwrap_err();
+        assert!(error.to_string().contains(expected), "{}", error);
+    }
+
+    #[test]
+    fn test_new_corrupt_header_length() {
+        let mut stream = BCF_MAGIC.to_vec();
+        stream.extend(u32::MAX.to_le_bytes());
+        let error = BcfReader::new(std::io::Cursor::new(stream)).err().unwrap();
+        assert!(
+            error.to_string().contains("Invalid BCF header length"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid BCF file")]
+    fn test_open_exception() {
+        BcfReader::open("data/test.vcf.gz").unwrap();
+    }
+}
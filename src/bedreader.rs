@@ -1,58 +1,180 @@
+use crate::error::Error;
+use crate::interval_set::{Interval, IntervalSet};
+use flate2::read::MultiGzDecoder;
 use log::info;
-use noodles_bed as bed;
 use std::fs::File;
-use std::io::BufReader;
-use std::string::String;
+use std::io::{BufRead, BufReader};
 use std::vec::Vec;
 
-/// Reading bed file and output a list of regions string (contig:start-end)
+/// The formats of interval files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntervalFormat {
+    /// bed file, 0-based and half-open, `track`/`browser`/`#` header lines are skipped
+    BED,
+    /// Picard interval_list, a sam-style `@` header followed by 1-based contig/start/end/strand/name lines
+    PICARD,
+    /// GATK intervals, one 1-based region string (contig, or contig:start-end) per line
+    GATK,
+}
+
+impl IntervalFormat {
+    /// Detect the format of an interval file from its extension (ignoring `.gz`)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::bedreader::IntervalFormat;
+    /// assert_eq!(IntervalFormat::from_path("loci.bed.gz"), IntervalFormat::BED);
+    /// assert_eq!(IntervalFormat::from_path("loci.interval_list"), IntervalFormat::PICARD);
+    /// assert_eq!(IntervalFormat::from_path("loci.intervals"), IntervalFormat::GATK);
+    /// ```
+    pub fn from_path(interval_file: &str) -> Self {
+        let path = interval_file.trim_end_matches(".gz");
+        if path.ends_with(".interval_list") {
+            IntervalFormat::PICARD
+        } else if path.ends_with(".intervals") || path.ends_with(".list") {
+            IntervalFormat::GATK
+        } else {
+            IntervalFormat::BED
+        }
+    }
+}
+
+/// Open a plain or gzipped (including bgzipped) text file, detected by the gzip magic bytes
+pub fn open_text_file(file_name: &str) -> Result<Box<dyn BufRead>, Error> {
+    let file = File::open(file_name).map_err(|e| Error::io(file_name, e))?;
+    let mut reader = BufReader::new(file);
+    let is_gzip = reader
+        .fill_buf()
+        .map_err(|e| Error::io(file_name, e))?
+        .starts_with(&[0x1f, 0x8b]);
+    match is_gzip {
+        true => Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader)))),
+        false => Ok(Box::new(reader)),
+    }
+}
+
+/// parse a single line of an interval file, None for header and empty lines
+fn parse_interval(line: &str, interval_format: IntervalFormat) -> Result<Option<Interval>, Error> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let parse_position = |position: Option<&str>| -> Result<usize, Error> {
+        position
+            .ok_or_else(|| Error::parse(format!("Missing position in interval line: {}", line)))?
+            .parse::<usize>()
+            .map_err(|_| Error::parse(format!("Invalid position in interval line: {}", line)))
+    };
+    match interval_format {
+        IntervalFormat::BED => {
+            if line.starts_with("track") || line.starts_with("browser") {
+                return Ok(None);
+            }
+            let mut columns = line.split_whitespace();
+            let contig = columns.next().unwrap_or_default();
+            let chrom_start = parse_position(columns.next())?;
+            let chrom_end = parse_position(columns.next())?;
+            Interval::from_bed(contig, chrom_start, chrom_end).map(Some)
+        }
+        IntervalFormat::PICARD => {
+            if line.starts_with('@') {
+                return Ok(None);
+            }
+            let mut columns = line.split('\t');
+            let contig = columns.next().unwrap_or_default();
+            let start = parse_position(columns.next())?;
+            let end = parse_position(columns.next())?;
+            Interval::new(contig, start, end).map(Some)
+        }
+        IntervalFormat::GATK => line.parse::<Interval>().map(Some),
+    }
+}
+
+/// Reading an interval file into a set of intervals, overlapping intervals are merged
 ///
 /// # Arguments:
-/// * `bed_file`: bed file path pointing to the data to be parsed
+/// * `interval_file`: a (gzipped) bed, Picard interval_list or GATK intervals file, the
+///   format is detected by the file extension (see [IntervalFormat::from_path])
 ///
 ///
 /// # Return:
-/// * List of region string
+/// * the sorted and merged intervals (1-based and inclusive)
 ///
 /// # Examples
 ///
 /// ```
-/// use diploid_contam_estimator::bedreader::read_bed;
-/// let region_list = read_bed("data/test.bed").unwrap();
-/// assert_eq!(region_list.len(), 4);
-/// assert_eq!(region_list[0], "X:2-5");
+/// use diploid_contam_estimator::bedreader::read_intervals;
+/// let interval_set = read_intervals("data/test.bed").unwrap();
+/// assert_eq!(interval_set.len(), 3);
+/// assert!(interval_set.overlaps("X", 3, 3));
+/// assert!(!interval_set.overlaps("X", 2, 2));
 /// ```
-pub fn read_bed(bed_file: &str) -> Result<Vec<String>, String> {
-    let mut region_list: Vec<String> = vec![];
-    let mut reader = File::open(bed_file)
-        .map(BufReader::new)
-        .map(bed::Reader::new)
-        .map_err(|e| e.to_string())?;
-
-    for record in reader.records::<3>() {
-        let bed_record = record.map_err(|e| e.to_string())?;
-        let start: usize =
-            usize::try_from(bed_record.start_position()).map_err(|e| e.to_string())? - 1;
-        let stop: usize = usize::try_from(bed_record.end_position()).map_err(|e| e.to_string())?;
-        let contig = bed_record.reference_sequence_name();
-        let region_string: String = format!("{}:{}-{}", contig, start, stop);
-        region_list.push(region_string);
-    }
-    info!("Collected {} loci from {}", region_list.len(), bed_file);
-    Ok(region_list)
+pub fn read_intervals(interval_file: &str) -> Result<IntervalSet, Error> {
+    let interval_format = IntervalFormat::from_path(interval_file);
+    let mut intervals: Vec<Interval> = vec![];
+    for (i, line) in open_text_file(interval_file)?.lines().enumerate() {
+        let line = line.map_err(|e| Error::io(interval_file, e))?;
+        let interval = parse_interval(&line, interval_format)
+            .map_err(|e| e.with_line(i + 1).with_file(interval_file))?;
+        if let Some(interval) = interval {
+            intervals.push(interval);
+        }
+    }
+    let interval_set = IntervalSet::new(intervals);
+    info!(
+        "Collected {} loci from {}",
+        interval_set.len(),
+        interval_file
+    );
+    Ok(interval_set)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("data/test.bed")]
+    #[case("data/test.bed.gz")] // gzipped, with track and browser lines
+    #[case("data/test.interval_list")]
+    #[case("data/test.intervals")]
+    fn test_read_intervals(#[case] interval_file: &str) {
+        let intervals: Vec<String> = read_intervals(interval_file)
+            .unwrap()
+            .intervals()
+            .map(|interval| interval.to_string())
+            .collect();
+        // X:3-5 and X:4-7 are merged
+        assert_eq!(intervals, vec!["1:4-7", "X:3-7", "X:38145487-38145490"]);
+    }
+
+    #[rstest]
+    #[case("track name=loci", IntervalFormat::BED, None)]
+    #[case("browser position X:1-100", IntervalFormat::BED, None)]
+    #[case("X\t0\t1\tname\t0\t+", IntervalFormat::BED, Some("X:1-1"))]
+    #[case("@SQ\tSN:X\tLN:156040895", IntervalFormat::PICARD, None)]
+    #[case("X\t1\t1\t+\tname", IntervalFormat::PICARD, Some("X:1-1"))]
+    #[case("X:1-1", IntervalFormat::GATK, Some("X:1-1"))]
+    fn test_parse_interval(
+        #[case] line: &str,
+        #[case] interval_format: IntervalFormat,
+        #[case] expected: Option<&str>,
+    ) {
+        let interval = parse_interval(line, interval_format).unwrap();
+        assert_eq!(interval.map(|i| i.to_string()).as_deref(), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid position in interval line")]
+    fn test_parse_interval_exception() {
+        parse_interval("X\tstart\t5", IntervalFormat::BED).unwrap();
+    }
 
     #[test]
-    fn test_build_variant_list() {
-        let bed_file = "data/test.bed";
-        let region_list = read_bed(bed_file).unwrap();
-        assert_eq!(region_list.len(), 4);
-        assert_eq!(region_list[0], "X:2-5");
-        assert_eq!(region_list[1], "X:3-7");
-        assert_eq!(region_list[3], "1:3-7");
+    #[should_panic(expected = "data/panel.tsv, line 2: Invalid position in interval line")]
+    fn test_read_intervals_exception() {
+        read_intervals("data/panel.tsv").unwrap();
     }
 }
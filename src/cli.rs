@@ -28,7 +28,22 @@ pub fn parse_args() -> ArgMatches {
                 .long("in-vcf")
                 .takes_value(true)
                 .required(true)
-                .help("A diploid vcf file for estimating contamination"),
+                .help("A diploid vcf file (plain, bgzipped or BCF, detected from the file content, or - for stdin) for estimating contamination, regions of a bgzipped vcf or BCF file are fetched through the --index file or <in_vcf>.csi/<in_vcf>.tbi (csi for vcf.gz or BCF, tbi for vcf.gz only), and filtered while streaming otherwise"),
+        )
+        .arg(
+            Arg::with_name("sample")
+                .short('s')
+                .long("sample")
+                .takes_value(true)
+                .required(false)
+                .help("Sample name (or 0-based index) to evaluate in a multi-sample vcf file [default: the first sample]"),
+        )
+        .arg(
+            Arg::with_name("all_samples")
+                .long("all-samples")
+                .takes_value(false)
+                .conflicts_with("sample")
+                .help("Estimate every sample of a multi-sample vcf file, the json output will be a list of per-sample results"),
         )
         .arg(
             Arg::with_name("out_json")
@@ -38,6 +53,13 @@ pub fn parse_args() -> ArgMatches {
                 .required(false)
                 .help("A json output file for storing the maximum likelihood contam level for the vcf file"),
         )
+        .arg(
+            Arg::with_name("out_tsv")
+                .long("out-tsv")
+                .takes_value(true)
+                .required(false)
+                .help("A tab-delimited output file for storing the maximum likelihood estimate of each sample"),
+        )
         .arg(
             Arg::with_name("debug_json")
                 .short('d')
@@ -59,6 +81,65 @@ pub fn parse_args() -> ArgMatches {
                 .takes_value(false)
                 .help("Only use SNV (ignore indel) for contamination estimations"),
         )
+        .arg(
+            Arg::with_name("include_types")
+                .long("include-types")
+                .takes_value(true)
+                .multiple_values(true)
+                .use_value_delimiter(true)
+                .required(false)
+                .help("Variant types to be used, from snv,indel,mnv,complex,symbolic [default: snv,indel,mnv,complex]"),
+        )
+        .arg(
+            Arg::with_name("exclude_types")
+                .long("exclude-types")
+                .takes_value(true)
+                .multiple_values(true)
+                .use_value_delimiter(true)
+                .required(false)
+                .help("Variant types to be skipped, e.g. mnv,complex"),
+        )
+        .arg(
+            Arg::with_name("max_fisher_strand")
+                .long("max-fs")
+                .takes_value(true)
+                .required(false)
+                .help("Skip variants with a phred-scaled Fisher strand bias (as GATK FS) above this, from FORMAT/ADF,ADR, FORMAT/SB or INFO/DP4 (single-sample vcf only)"),
+        )
+        .arg(
+            Arg::with_name("max_strand_odds_ratio")
+                .long("max-sor")
+                .takes_value(true)
+                .required(false)
+                .help("Skip variants with a strand odds ratio (as GATK SOR) above this, from FORMAT/ADF,ADR, FORMAT/SB or INFO/DP4 (single-sample vcf only)"),
+        )
+        .arg(
+            Arg::with_name("strand_likelihood")
+                .long("strand-likelihood")
+                .takes_value(false)
+                .help("Report estimates from the forward and reverse strand read counts separately, contamination shows up on both strands while artifacts are often strand-specific"),
+        )
+        .arg(
+            Arg::with_name("phase_check")
+                .long("phase-check")
+                .takes_value(false)
+                .help("Test whether the allele fraction deviations of phased hets (GT with '|' and FORMAT/PS) are coherent within phase blocks, which points to allelic imbalance such as LOH rather than contamination"),
+        )
+        .arg(
+            Arg::with_name("dedup")
+                .long("dedup")
+                .takes_value(true)
+                .required(false)
+                .possible_values(["depth", "qual", "snv"])
+                .help("Keep one variant per locus of overlapping variants (e.g. split multi-allelics or overlapping indels), with the highest DP (depth), the highest QUAL (qual) or preferring SNV (snv)"),
+        )
+        .arg(
+            Arg::with_name("indel_gap")
+                .long("indel-gap")
+                .takes_value(true)
+                .required(false)
+                .help("Skip variants within this many bp of an indel"),
+        )
         .arg(
             Arg::with_name("depth_threshold")
                 .short('m')
@@ -67,13 +148,207 @@ pub fn parse_args() -> ArgMatches {
                 .default_value("0")
                 .help("Minimum depth for a variant to be considered (i.e. DP tag)"),
         )
+        .arg(
+            Arg::with_name("allow_filters")
+                .long("allow-filters")
+                .takes_value(true)
+                .multiple_values(true)
+                .use_value_delimiter(true)
+                .required(false)
+                .help("FILTER values (besides PASS) of records to be used, e.g. LowQual,RefCall; '*' accepts any FILTER value"),
+        )
+        .arg(
+            Arg::with_name("deny_filters")
+                .long("deny-filters")
+                .takes_value(true)
+                .multiple_values(true)
+                .use_value_delimiter(true)
+                .required(false)
+                .help("FILTER values of records to be skipped, taking precedence over --allow-filters"),
+        )
+        .arg(
+            Arg::with_name("include")
+                .long("include")
+                .takes_value(true)
+                .required(false)
+                .help("Only use variants satisfying a bcftools-style expression over QUAL, FILTER, INFO/TAG and FMT/TAG, e.g. 'QUAL>30 && FMT/GQ>=20 && INFO/DB'"),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .long("exclude")
+                .takes_value(true)
+                .required(false)
+                .help("Skip variants satisfying a bcftools-style expression, e.g. 'FMT/AD[1]<5 || FMT/DP>500'"),
+        )
+        .arg(
+            Arg::with_name("caller")
+                .long("caller")
+                .takes_value(true)
+                .default_value("gatk")
+                .possible_values(["gatk", "deepvariant", "freebayes", "strelka2", "bcftools"])
+                .help("Variant caller preset for the FORMAT tags of depth and allele counts (gatk/deepvariant: DP/AD, freebayes: DP/RO/AO, strelka2: tier-1 counts, bcftools: DP/ADF/ADR or INFO DP4 of a single-sample vcf)"),
+        )
+        .arg(
+            Arg::with_name("depth_tag")
+                .long("depth-tag")
+                .takes_value(true)
+                .required(false)
+                .help("FORMAT tag of the total read depth, overriding the caller preset"),
+        )
+        .arg(
+            Arg::with_name("allele_depth_tag")
+                .long("allele-depth-tag")
+                .takes_value(true)
+                .required(false)
+                .help("A per-allele (Number=R) FORMAT tag of allele counts, overriding the caller preset"),
+        )
+        .arg(
+            Arg::with_name("sum_allele_depth")
+                .long("sum-allele-depth")
+                .takes_value(false)
+                .help("Use the sum of the allele counts instead of the depth tag as the total read depth"),
+        )
         .arg(
             Arg::with_name("loci_bed")
                 .short('b')
                 .long("bed")
                 .takes_value(true)
                 .required(false)
-                .help("bed file containing loci for extracting variants"),
+                .help("bed (optionally gzipped), Picard .interval_list or GATK .intervals file containing loci for extracting variants, fetched through the vcf index if available, otherwise filtered while streaming the vcf records"),
+        )
+        .arg(
+            Arg::with_name("exclude_bed")
+                .long("exclude-bed")
+                .takes_value(true)
+                .required(false)
+                .help("bed (optionally gzipped), Picard .interval_list or GATK .intervals file containing regions to be excluded (e.g. segmental duplications and blacklists), applied after --bed"),
+        )
+        .arg(
+            Arg::with_name("lenient")
+                .long("lenient")
+                .takes_value(false)
+                .help("Skip malformed or inconsistent records (missing AD/GT, DP lower than the sum of AD, unparsable lines) instead of failing, the skipped records are counted by reason in the output json"),
+        )
+        .arg(
+            Arg::with_name("sites_panel")
+                .long("panel")
+                .takes_value(true)
+                .required(false)
+                .help("Known-sites panel (e.g. common SNPs) to restrict the variants to, as a sites vcf/bcf or a (gzipped) tsv with contig, position, ref and alt columns; alleles must match the panel, and hom-ref calls of an all-sites vcf or gVCF (with per-site AD) at panel sites are used as contamination evidence"),
+        )
+        .arg(
+            Arg::with_name("reference_block_depth")
+                .long("reference-block-depth")
+                .takes_value(false)
+                .requires("sites_panel")
+                .help("Use the --panel sites within gVCF reference blocks (END spanning several sites, e.g. GATK GT:DP:GQ:MIN_DP) as hom-ref calls without alt reads at the MIN_DP (or DP) of the block, these blocks have no per-site allele depths and are skipped otherwise"),
+        )
+        .arg(
+            Arg::with_name("contig_aliases")
+                .long("contig-aliases")
+                .takes_value(true)
+                .required(false)
+                .help("Alias table for matching contig names of the bed/fasta files to the vcf, each line lists the equivalent names of a contig (e.g. chr1 1 NC_000001.11); chr prefixes and MT/chrM are matched automatically"),
+        )
+        .arg(
+            Arg::with_name("index_file")
+                .long("index")
+                .takes_value(true)
+                .required(false)
+                .help("A tabix (.tbi) or csi (.csi) index of the vcf file for fetching the bed loci [default: <in_vcf>.csi or <in_vcf>.tbi]"),
+        )
+        .arg(
+            Arg::with_name("fetal_fraction")
+                .long("fetal-fraction")
+                .takes_value(false)
+                .help("Estimate fetal fraction (2-30%) of a maternal cell-free DNA sample instead of contamination level"),
+        )
+        .arg(
+            Arg::with_name("candidate_vcfs")
+                .short('c')
+                .long("candidates")
+                .takes_value(true)
+                .multiple_values(true)
+                .required(false)
+                .help("Candidate contaminant genotype vcf files (or directories of vcf files) to be ranked by likelihood gain, every sample of a multi-sample vcf file is a candidate; only the sites with a called candidate genotype (including hom-ref) are scored"),
+        )
+        .arg(
+            Arg::with_name("kinship")
+                .long("kinship")
+                .takes_value(true)
+                .required(false)
+                .conflicts_with("profile_kinship")
+                .help("Kinship coefficient between sample and contaminant (0: unrelated, 0.25: parent/sibling) for the relatedness-aware model"),
+        )
+        .arg(
+            Arg::with_name("profile_kinship")
+                .long("profile-kinship")
+                .takes_value(false)
+                .help("Profile the kinship coefficient between sample and contaminant in the relatedness-aware model"),
+        )
+        .arg(
+            Arg::with_name("transversion_only")
+                .long("transversion-only")
+                .takes_value(false)
+                .help("Only use transversion SNVs, which are not affected by deamination damage in ancient DNA or FFPE samples"),
+        )
+        .arg(
+            Arg::with_name("damage_aware")
+                .long("damage-aware")
+                .takes_value(false)
+                .help("Report estimates stratified by substitution class (transition, C>T/G>A, transversion) to spot DNA damage"),
+        )
+        .arg(
+            Arg::with_name("reference")
+                .short('r')
+                .long("reference")
+                .takes_value(true)
+                .required(false)
+                .help("A samtools-faidx indexed reference fasta file for annotating the sequence context of variants, variants on sequences missing from the fasta are removed by the sequence context filters"),
+        )
+        .arg(
+            Arg::with_name("max_homopolymer")
+                .long("max-homopolymer")
+                .takes_value(true)
+                .required(false)
+                .requires("reference")
+                .help("Remove variants in homopolymers longer than this"),
+        )
+        .arg(
+            Arg::with_name("exclude_str")
+                .long("exclude-str")
+                .takes_value(false)
+                .requires("reference")
+                .help("Remove variants in short tandem repeats (2-6bp repeat unit)"),
+        )
+        .arg(
+            Arg::with_name("min_gc")
+                .long("min-gc")
+                .takes_value(true)
+                .required(false)
+                .requires("reference")
+                .help("Remove variants with flanking GC content lower than this"),
+        )
+        .arg(
+            Arg::with_name("max_gc")
+                .long("max-gc")
+                .takes_value(true)
+                .required(false)
+                .requires("reference")
+                .help("Remove variants with flanking GC content higher than this"),
+        )
+        .arg(
+            Arg::with_name("context_strata")
+                .long("context-strata")
+                .takes_value(false)
+                .requires("reference")
+                .help("Report estimates stratified by sequence context (homopolymer/STR/non-repetitive and GC content)"),
+        )
+        .arg(
+            Arg::with_name("type_strata")
+                .long("type-strata")
+                .takes_value(false)
+                .help("Report estimates stratified by variant type (SNV/indel/MNV/complex/symbolic)"),
         )
         .get_matches();
     matches
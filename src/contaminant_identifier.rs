@@ -0,0 +1,460 @@
+use crate::contamination_estimator::{
+    calc_floored_loglik_for_variant_position, calc_loglik_for_hypothetical_contam_level,
+    with_sequencing_error,
+};
+use crate::contig_alias::ContigAliases;
+use crate::error::Error;
+use crate::model::{CandidateResult, ContamProbResult, VariantPosition};
+use crate::vcfreader::open_vcf_reader;
+use log::info;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::read_dir;
+use std::io;
+use std::path::Path;
+use std::string::String;
+use std::vec::Vec;
+
+/// alt allele dosage (0, 1 or 2) of a candidate sample, keyed by (contig, position, alt allele),
+/// only the sites with a called genotype of the candidate are present
+pub type CandidateGenotypes = HashMap<(String, usize, String), usize>;
+
+/// Expand a list of candidate vcf files and/or directories into a list of vcf files
+///
+/// # Arguments:
+/// * `candidate_paths`: vcf files or directories containing `*.vcf`/`*.vcf.gz` files
+///
+/// # Return:
+/// * sorted list of candidate vcf files
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::contaminant_identifier::collect_candidate_vcfs;
+/// let candidates = collect_candidate_vcfs(&["data/candidates".to_string()]).unwrap();
+/// assert_eq!(candidates.len(), 2);
+/// ```
+pub fn collect_candidate_vcfs(candidate_paths: &[String]) -> Result<Vec<String>, Error> {
+    let mut candidate_vcfs: Vec<String> = vec![];
+    for candidate_path in candidate_paths {
+        let path = Path::new(candidate_path);
+        if path.is_dir() {
+            for entry in read_dir(path).map_err(|e| Error::io(candidate_path, e))? {
+                let file_name = entry.map_err(|e| Error::io(candidate_path, e))?.path();
+                let file_name = file_name.to_string_lossy().to_string();
+                if file_name.ends_with(".vcf")
+                    || file_name.ends_with(".vcf.gz")
+                    || file_name.ends_with(".bcf")
+                {
+                    candidate_vcfs.push(file_name);
+                }
+            }
+        } else if path.is_file() {
+            candidate_vcfs.push(candidate_path.to_string());
+        } else {
+            return Err(Error::io(
+                candidate_path,
+                io::Error::new(io::ErrorKind::NotFound, "Candidate vcf not found"),
+            ));
+        }
+    }
+    candidate_vcfs.sort();
+    candidate_vcfs.dedup();
+    Ok(candidate_vcfs)
+}
+
+/// Read the genotypes of every sample of a candidate vcf file
+///
+/// # Arguments:
+/// * `vcf_file`: the candidate genotype vcf file
+///
+/// # Return:
+/// * the sample names and their alt allele dosages for each alt allele of the sites with a
+///   called genotype, sites absent from the candidate vcf file or with a missing genotype
+///   (e.g. `./.`) are not known
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::contaminant_identifier::read_candidate_genotypes;
+/// let candidates = read_candidate_genotypes("data/mixture_candidates.vcf").unwrap();
+/// let samples: Vec<&str> = candidates.iter().map(|(sample, _)| sample.as_str()).collect();
+/// assert_eq!(samples, vec!["sibling", "contaminant", "unrelated"]);
+/// ```
+pub fn read_candidate_genotypes(
+    vcf_file: &str,
+) -> Result<Vec<(String, CandidateGenotypes)>, Error> {
+    let reader = open_vcf_reader(vcf_file)?;
+    let mut candidates: Vec<(String, CandidateGenotypes)> = reader
+        .header()
+        .sample_names()
+        .iter()
+        .map(|sample| (sample.to_string(), HashMap::new()))
+        .collect();
+    for result in reader {
+        let record = result.map_err(|e| e.with_file(vcf_file))?;
+        let contig = record.chromosome().to_string();
+        let position = usize::from(record.position());
+        for (sample_genotype, (_, genotypes)) in
+            record.genotypes().iter().zip(candidates.iter_mut())
+        {
+            let allele_indices: Vec<usize> = match sample_genotype.genotype() {
+                Some(Ok(gt)) => match gt.iter().map(|allele| allele.position()).collect() {
+                    Some(allele_indices) => allele_indices,
+                    None => continue, // a (partially) uncalled genotype, e.g. ./.
+                },
+                _ => continue, // no genotype for this site, treat it as missing
+            };
+            for (alt_index, alt_allele) in record.alternate_bases().iter().enumerate() {
+                let dosage = allele_indices
+                    .iter()
+                    .filter(|&&allele_index| allele_index == alt_index + 1)
+                    .count();
+                genotypes.insert((contig.clone(), position, alt_allele.to_string()), dosage);
+            }
+        }
+    }
+    for (sample, genotypes) in candidates.iter() {
+        info!(
+            "Collected {} called candidate alt alleles of {} from {}",
+            genotypes.len(),
+            sample,
+            vcf_file
+        );
+    }
+    Ok(candidates)
+}
+
+/// the candidate genotype key of a variant
+fn genotype_key(variant_position: &VariantPosition) -> (String, usize, String) {
+    (
+        variant_position.contig.clone(),
+        variant_position.position,
+        variant_position.alt_allele.clone().unwrap_or_default(),
+    )
+}
+
+/// Rename the contigs of candidate genotypes to the contig names of the sample variants
+/// (see [ContigAliases::resolve]), genotypes on contigs without sample variants are dropped
+///
+/// # Arguments
+/// * `genotypes`: the genotypes of the candidate contaminant
+/// * `sample_contigs`: the contig names of the sample variants
+/// * `contig_aliases`: aliases for matching the candidate contigs to the sample contigs
+///
+/// # Returns
+/// * the genotypes keyed by the contig names of the sample variants
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::contaminant_identifier::{rename_candidate_contigs, CandidateGenotypes};
+/// use diploid_contam_estimator::contig_alias::ContigAliases;
+/// let mut genotypes = CandidateGenotypes::new();
+/// genotypes.insert(("chr1".to_string(), 100, "G".to_string()), 1);
+/// let genotypes =
+///     rename_candidate_contigs(genotypes, &["1".to_string()], &ContigAliases::default());
+/// assert_eq!(genotypes[&("1".to_string(), 100, "G".to_string())], 1);
+/// ```
+pub fn rename_candidate_contigs(
+    genotypes: CandidateGenotypes,
+    sample_contigs: &[String],
+    contig_aliases: &ContigAliases,
+) -> CandidateGenotypes {
+    let mut renamed_contigs: HashMap<String, Option<String>> = HashMap::new();
+    genotypes
+        .into_iter()
+        .filter_map(|((contig, position, alt_allele), dosage)| {
+            renamed_contigs
+                .entry(contig.clone())
+                .or_insert_with(|| contig_aliases.resolve(&contig, sample_contigs))
+                .clone()
+                .map(|renamed| ((renamed, position, alt_allele), dosage))
+        })
+        .collect()
+}
+
+/// Calculate the log likelihood of the observed variants at a hypothetical contamination level
+/// given the contaminant genotypes
+///
+/// The expected alt allele fraction of a variant is `(1 - c) * s + c * g / 2`,
+/// where `s` is the alt fraction of the sample genotype (0.5 for heterozygous, 1 for homozygous)
+/// and `g` is the alt allele dosage of the contaminant, with a floor and ceiling from
+/// sequencing errors. Only the sites in the variant list of the sample with a called genotype
+/// of the candidate are scored, as the read counts of the other candidate sites are not in the
+/// sample vcf (use `--panel` with gVCF or all-sites input to score hom-ref sites of the sample)
+///
+/// # Arguments
+/// * `variant_list`: the variants of the sample
+/// * `genotypes`: the genotypes of the candidate contaminant
+/// * `hypothetical_contamination_level`: the contamination level to test
+///
+/// # Returns
+/// * sum of the log likelihood across all variants
+pub fn calculate_candidate_hypothesis(
+    variant_list: &[VariantPosition],
+    genotypes: &CandidateGenotypes,
+    hypothetical_contamination_level: f64,
+) -> Result<f64, Error> {
+    variant_list
+        .iter()
+        .filter_map(|variant_position| {
+            genotypes
+                .get(&genotype_key(variant_position))
+                .map(|&dosage| (variant_position, dosage))
+        })
+        .map(|(variant_position, dosage)| {
+            let sample_fraction = variant_position.zygosity.alt_fraction();
+            let variant_fraction = (1.0 - hypothetical_contamination_level) * sample_fraction
+                + hypothetical_contamination_level * dosage as f64 / 2.0;
+            calc_loglik_for_hypothetical_contam_level(
+                variant_position,
+                with_sequencing_error(variant_fraction),
+            )
+        })
+        .sum()
+}
+
+/// Fit the anonymous contaminant model with the sequencing error floor of the candidate model
+/// (see [calc_floored_loglik_for_variant_position]), so the likelihoods can be compared
+fn fit_anonymous_contaminant(
+    variant_list: &[VariantPosition],
+    contamination_levels: &[f64],
+) -> Result<f64, Error> {
+    contamination_levels
+        .par_iter()
+        .map(|&hypothetical_contamination_level| {
+            variant_list
+                .iter()
+                .map(|variant_position| {
+                    calc_floored_loglik_for_variant_position(
+                        variant_position,
+                        hypothetical_contamination_level,
+                    )
+                })
+                .sum::<Result<f64, Error>>()
+        })
+        .try_reduce(|| f64::NEG_INFINITY, |a, b| Ok(a.max(b)))
+}
+
+/// Fit the contamination level for each of the candidate contaminants, and rank the
+/// candidates by how much they improve the likelihood over the anonymous contaminant model,
+/// both models are fitted over the same contamination levels with a sequencing error floor,
+/// at the sites with a called genotype of the candidate
+///
+/// # Arguments
+/// * `variant_list`: the variants of the sample
+/// * `candidate_vcfs`: list of candidate genotype vcf files, every sample of a file is a candidate
+/// * `contamination_levels`: the contamination levels to evaluate
+/// * `contig_aliases`: aliases for matching the candidate contigs to the sample contigs
+///
+/// # Returns
+/// * the candidates sorted by descending likelihood gain
+pub fn rank_contaminant_candidates(
+    variant_list: &[VariantPosition],
+    candidate_vcfs: &[String],
+    contamination_levels: &[f64],
+    contig_aliases: &ContigAliases,
+) -> Result<Vec<CandidateResult>, Error> {
+    let mut sample_contigs: Vec<String> = variant_list
+        .iter()
+        .map(|variant_position| variant_position.contig.clone())
+        .collect();
+    sample_contigs.sort();
+    sample_contigs.dedup();
+    let candidate_genotypes = candidate_vcfs
+        .par_iter()
+        .map(|candidate_vcf| {
+            Ok(read_candidate_genotypes(candidate_vcf)?
+                .into_iter()
+                .map(|(sample, genotypes)| (candidate_vcf, sample, genotypes))
+                .collect::<Vec<_>>())
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut candidate_results = candidate_genotypes
+        .into_par_iter()
+        .flatten()
+        .map(|(candidate_vcf, sample, genotypes)| {
+            let genotypes = rename_candidate_contigs(genotypes, &sample_contigs, contig_aliases);
+            let called_variants: Vec<VariantPosition> = variant_list
+                .iter()
+                .filter(|variant_position| genotypes.contains_key(&genotype_key(variant_position)))
+                .cloned()
+                .collect();
+            info!(
+                "Scoring {} of {} variants with a called genotype of {} in {}",
+                called_variants.len(),
+                variant_list.len(),
+                sample,
+                candidate_vcf
+            );
+            let anonymous_log_likelihood =
+                fit_anonymous_contaminant(&called_variants, contamination_levels)?;
+            let mut best_guess: Option<ContamProbResult> = None;
+            for &hypothetical_contamination_level in contamination_levels {
+                let log_likelihood = calculate_candidate_hypothesis(
+                    &called_variants,
+                    &genotypes,
+                    hypothetical_contamination_level,
+                )?;
+                let output = ContamProbResult {
+                    contamination_level: hypothetical_contamination_level,
+                    log_likelihood,
+                };
+                match best_guess {
+                    Some(bg) if bg.log_likelihood >= output.log_likelihood => {}
+                    _ => best_guess = Some(output),
+                }
+            }
+            let best_guess =
+                best_guess.ok_or_else(|| Error::model("No best guess contam object"))?;
+            Ok(CandidateResult {
+                candidate: candidate_vcf.to_string(),
+                sample,
+                contamination_level: best_guess.contamination_level,
+                log_likelihood: best_guess.log_likelihood,
+                likelihood_gain: best_guess.log_likelihood - anonymous_log_likelihood,
+            })
+        })
+        .collect::<Result<Vec<CandidateResult>, Error>>()?;
+    candidate_results.sort_by(|a, b| b.likelihood_gain.total_cmp(&a.likelihood_gain));
+    Ok(candidate_results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{VariantType, Zygosity};
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::*;
+
+    #[test]
+    fn test_read_candidate_genotypes() {
+        let candidates = read_candidate_genotypes("data/candidates/candidate_hom_alt.vcf").unwrap();
+        assert_eq!(candidates.len(), 1);
+        let (sample, genotypes) = &candidates[0];
+        assert_eq!(sample, "candidate_hom_alt");
+        assert_eq!(genotypes.len(), 9);
+        assert_eq!(genotypes[&("X".to_string(), 38145492, "C".to_string())], 2);
+    }
+
+    #[test]
+    fn test_read_candidate_genotypes_multi_sample() {
+        let candidates = read_candidate_genotypes("data/mixture_candidates.vcf").unwrap();
+        let key = ("X".to_string(), 400, "C".to_string());
+        let dosages: Vec<(&str, Option<usize>)> = candidates
+            .iter()
+            .map(|(sample, genotypes)| (sample.as_str(), genotypes.get(&key).copied()))
+            .collect();
+        assert_eq!(
+            dosages,
+            vec![
+                ("sibling", Some(1)),
+                ("contaminant", Some(0)),
+                ("unrelated", Some(2))
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case(100, 0, 0.0, -0.1000500334)]
+    #[case(100, 0, 0.2, -22.3893832705)] // contam with the ref allele
+    #[case(100, 2, 0.2, -0.1000500334)] // contam with the same hom alt allele
+    #[case(99, 2, 0.2, -2.4016346260)] // a ref read from sequencing error is not impossible
+    fn test_calculate_candidate_hypothesis(
+        #[case] alt_depth: usize,
+        #[case] dosage: usize,
+        #[case] contam_level: f64,
+        #[case] expected_log_prob: f64,
+    ) {
+        let mut variant = VariantPosition::new(
+            "X",
+            2,
+            100,
+            alt_depth,
+            VariantType::SNV,
+            Zygosity::HOMOZYGOUS,
+        )
+        .unwrap();
+        variant.set_alleles("A", "G");
+        let mut genotypes: CandidateGenotypes = HashMap::new();
+        genotypes.insert(("X".to_string(), 2, "G".to_string()), dosage);
+        let log_prob =
+            calculate_candidate_hypothesis(&[variant], &genotypes, contam_level).unwrap();
+        assert_approx_eq!(log_prob, expected_log_prob);
+    }
+
+    #[test]
+    fn test_calculate_candidate_hypothesis_hom_ref() {
+        // alt reads at a hom-ref site of a hom-ref candidate are sequencing errors
+        let mut variant =
+            VariantPosition::new("X", 2, 100, 1, VariantType::SNV, Zygosity::HOM_REF).unwrap();
+        variant.set_alleles("A", "G");
+        let mut genotypes: CandidateGenotypes = HashMap::new();
+        genotypes.insert(("X".to_string(), 2, "G".to_string()), 0);
+        let log_prob = calculate_candidate_hypothesis(&[variant], &genotypes, 0.1).unwrap();
+        assert!(log_prob.is_finite());
+        assert!(log_prob < 0.0);
+    }
+
+    #[test]
+    fn test_read_candidate_genotypes_uncalled() {
+        // uncalled genotypes are unknown rather than hom-ref, and the chr prefixed contigs of
+        // the candidate are matched to the contigs of the sample
+        let (_, genotypes) = read_candidate_genotypes("data/chr_candidates.vcf")
+            .unwrap()
+            .remove(0);
+        let genotypes =
+            rename_candidate_contigs(genotypes, &["X".to_string()], &ContigAliases::default());
+        let mut dosages: Vec<(usize, usize)> = genotypes
+            .iter()
+            .map(|((contig, position, _), dosage)| {
+                assert_eq!(contig, "X");
+                (*position, *dosage)
+            })
+            .collect();
+        dosages.sort_unstable();
+        assert_eq!(dosages, vec![(100, 2), (300, 0)]);
+
+        // the sample variant at the uncalled site is not scored
+        let mut variant =
+            VariantPosition::new("X", 200, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS)
+                .unwrap();
+        variant.set_alleles("C", "T");
+        let log_prob = calculate_candidate_hypothesis(&[variant], &genotypes, 0.1).unwrap();
+        assert_eq!(log_prob, 0.0);
+    }
+
+    #[test]
+    fn test_rank_contaminant_candidates_uninformative() {
+        // a candidate without alt alleles at the hom sites fits as well as the anonymous model,
+        // as both models are scored with the sequencing error floor
+        let variant_list: Vec<VariantPosition> = (1..=10)
+            .map(|position| {
+                VariantPosition::new(
+                    "X",
+                    position,
+                    1000,
+                    1000,
+                    VariantType::SNV,
+                    Zygosity::HOMOZYGOUS,
+                )
+                .unwrap()
+            })
+            .collect();
+        let candidates = rank_contaminant_candidates(
+            &variant_list,
+            &["data/candidates/candidate_unrelated.vcf".to_string()],
+            &[0.001, 0.01, 0.1],
+            &ContigAliases::default(),
+        )
+        .unwrap();
+        assert_approx_eq!(candidates[0].likelihood_gain, 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Candidate vcf not found")]
+    fn test_collect_candidate_vcfs_exception() {
+        collect_candidate_vcfs(&["data/candidates/missing.vcf".to_string()]).unwrap();
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::Error;
 use crate::model::{Hypothesis, VariantPosition, Zygosity};
 use lazy_static::lazy_static;
 use rayon::prelude::*;
@@ -5,16 +6,107 @@ use statrs::distribution::{Binomial, Discrete};
 use std::vec::Vec;
 
 lazy_static! {
-    static ref HYPOTHESES: [&'static str; 6] = [
+    static ref HYPOTHESES: [&'static str; 9] = [
         "homozygous",
         "contam is not ref nor alt",
         "contam is called as alt",
         "contam looks like het-alt at hom-alt position",
         "contam comes from a ref-allele",
         "contam looks like het-alt at hom-ref position",
+        "contam is hom-ref at hom-ref position",
+        "contam is het at hom-ref position",
+        "contam is hom-alt at hom-ref position",
     ];
 }
 
+/// per-base sequencing error rate, turning ref reads into alt reads and vice versa
+pub const SEQUENCING_ERROR_RATE: f64 = 0.001;
+
+/// The expected alt allele fraction after sequencing errors, so that a few stray reads
+/// at an expected alt allele fraction of 0 or 1 do not make a hypothesis impossible
+///
+/// # Arguments
+/// * `variant_fraction`: the expected alt allele fraction of the mixture of genomes
+///
+/// # Returns
+/// * the expected alt allele fraction of the reads, within
+///   [SEQUENCING_ERROR_RATE, 1 - SEQUENCING_ERROR_RATE]
+///
+/// # Examples
+///
+/// ```
+/// use assert_approx_eq::assert_approx_eq;
+/// use diploid_contam_estimator::contamination_estimator::with_sequencing_error;
+/// assert_approx_eq!(with_sequencing_error(0.0), 0.001);
+/// assert_approx_eq!(with_sequencing_error(0.5), 0.5);
+/// assert_approx_eq!(with_sequencing_error(1.0), 0.999);
+/// ```
+pub fn with_sequencing_error(variant_fraction: f64) -> f64 {
+    SEQUENCING_ERROR_RATE + (1.0 - 2.0 * SEQUENCING_ERROR_RATE) * variant_fraction
+}
+
+/// The alt allele frequency assumed for the contaminant (or the father) at a known site, the
+/// known-sites panels are expected to hold common polymorphic sites
+pub const KNOWN_SITE_ALLELE_FREQUENCY: f64 = 0.5;
+
+/// The log probability of an alt depth under a mixture of hypotheses, each weighted by its prior
+/// probability, with a sequencing error floor on the expected alt allele fractions
+///
+/// # Arguments
+/// * `variant_position`: the positional data of the variant
+/// * `weighted_hypotheses`: the hypotheses and their prior probabilities
+///
+/// # Returns
+/// * the hypothesis with the highest posterior probability, carrying the log likelihood of the
+///   whole mixture
+///
+/// # Examples
+///
+/// ```
+/// use assert_approx_eq::assert_approx_eq;
+/// use diploid_contam_estimator::contamination_estimator::mixture_hypothesis;
+/// use diploid_contam_estimator::model::{Hypothesis, VariantPosition, VariantType, Zygosity};
+/// let variant = VariantPosition::new(
+///     "chr1", 1, 100, 0, VariantType::SNV, Zygosity::HOM_REF
+/// ).unwrap();
+/// let hypothesis = mixture_hypothesis(&variant, vec![
+///     (Hypothesis::new("no alt allele".to_string(), 0.0).unwrap(), 0.5),
+///     (Hypothesis::new("alt allele".to_string(), 0.1).unwrap(), 0.5),
+/// ]).unwrap();
+/// assert_eq!(hypothesis.label, "no alt allele");
+/// let expected_loglik = (0.5 * 0.999_f64.powi(100) + 0.5 * 0.8992_f64.powi(100)).ln();
+/// assert_approx_eq!(hypothesis.loglik.unwrap(), expected_loglik);
+/// ```
+pub fn mixture_hypothesis(
+    variant_position: &VariantPosition,
+    weighted_hypotheses: Vec<(Hypothesis, f64)>,
+) -> Result<Hypothesis, Error> {
+    let mut component_logliks: Vec<f64> = Vec::with_capacity(weighted_hypotheses.len());
+    for (hypothesis, prior) in weighted_hypotheses.iter() {
+        let loglik = calc_loglik_for_hypothetical_contam_level(
+            variant_position,
+            with_sequencing_error(hypothesis.variant_fraction),
+        )?;
+        component_logliks.push(prior.ln() + loglik);
+    }
+    let (best_index, max_loglik) = component_logliks
+        .iter()
+        .copied()
+        .enumerate()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .ok_or_else(|| Error::model("MAX is not found in the loglik calculation"))?;
+    // log-sum-exp of the weighted likelihoods of the hypotheses
+    let loglik = max_loglik
+        + component_logliks
+            .iter()
+            .map(|component_loglik| (component_loglik - max_loglik).exp())
+            .sum::<f64>()
+            .ln();
+    let mut best_hypothesis = weighted_hypotheses[best_index].0.clone();
+    best_hypothesis.set_loglik(loglik);
+    Ok(best_hypothesis)
+}
+
 /// Calculate log probability of seeing a number of alt calls
 /// at some read depth for a given contamination level
 /// # Arguments
@@ -39,12 +131,12 @@ lazy_static! {
 pub fn calc_loglik_for_hypothetical_contam_level(
     variant_position: &VariantPosition,
     hypothetical_contamination_level: f64,
-) -> Result<f64, String> {
+) -> Result<f64, Error> {
     let binom = Binomial::new(
         hypothetical_contamination_level,
         variant_position.total_read_depth as u64,
     )
-    .map_err(|e| e.to_string())?;
+    .map_err(|e| Error::model(e.to_string()))?;
     Ok(binom.ln_pmf(variant_position.alt_depth as u64))
 }
 
@@ -65,8 +157,29 @@ pub fn calc_loglik_for_hypothetical_contam_level(
 pub fn calc_loglik_for_hypothetical_contam_level_heterozygous(
     variant_position: &VariantPosition,
     hypothetical_contamination_level: f64,
-) -> Result<Hypothesis, String> {
-    let mut contamination_hypotheses: Vec<Hypothesis> = vec![
+) -> Result<Hypothesis, Error> {
+    let mut contamination_hypotheses = heterozygous_hypotheses(hypothetical_contamination_level)?;
+    for contam_hypothesis in contamination_hypotheses.iter_mut() {
+        let loglik = calc_loglik_for_hypothetical_contam_level(
+            variant_position,
+            contam_hypothesis.variant_fraction,
+        )?;
+        contam_hypothesis.set_loglik(loglik);
+    }
+    let best_hypothesis = contamination_hypotheses
+        .iter()
+        .max_by(|a, b| a.loglik.partial_cmp(&b.loglik).unwrap())
+        .ok_or_else(|| Error::model("MAX is not found in the loglik calculation"))?;
+
+    Ok(best_hypothesis.clone())
+}
+
+/// The hypotheses of a heterozygous variant for a given contamination level, see
+/// [calc_loglik_for_hypothetical_contam_level_heterozygous]
+fn heterozygous_hypotheses(
+    hypothetical_contamination_level: f64,
+) -> Result<Vec<Hypothesis>, Error> {
+    Ok(vec![
         Hypothesis::new(
             HYPOTHESES[1].to_string(),
             (1.0 - hypothetical_contamination_level) / 2.0,
@@ -84,22 +197,61 @@ pub fn calc_loglik_for_hypothetical_contam_level_heterozygous(
             0.5 - hypothetical_contamination_level,
         )?, // this is when contam looks like REF
         Hypothesis::new(HYPOTHESES[5].to_string(), hypothetical_contamination_level)?, // this is when the contam is being called as low vaf het
-    ];
-    let best_hypothesis = contamination_hypotheses
-        .iter_mut()
-        .map(|contam_hypothesis| {
-            let loglik = calc_loglik_for_hypothetical_contam_level(
-                variant_position,
-                contam_hypothesis.variant_fraction,
-            )
-            .unwrap();
-            contam_hypothesis.set_loglik(loglik);
-            contam_hypothesis
-        })
-        .max_by(|a, b| a.loglik.partial_cmp(&b.loglik).unwrap())
-        .ok_or("MAX is not found in the loglik calculation")?;
+    ])
+}
 
-    Ok(best_hypothesis.clone())
+/// Return log probability of a homozygous reference call at a known site for a given contamination level
+/// the expected alt allele frequency is c * (alt allele fraction of the contaminant), where the contaminant can be:
+/// 1. homozygous reference as well: we expect no alt allele
+/// 2. heterozygous: we expect an alt allele frequency of c / 2
+/// 3. homozygous alt: we expect an alt allele frequency of c
+///
+/// the contaminant genotypes are not known, so the likelihood is a mixture over them, weighted
+/// by their Hardy-Weinberg proportions at [KNOWN_SITE_ALLELE_FREQUENCY], and hom-ref calls
+/// without alt reads are evidence against contamination
+///
+/// # Arguments
+/// * `variant_position`: the positional data of the homozygous reference call
+/// * `hypothetical_contamination_level`: hypothetical contamination level
+///
+/// # Returns
+/// * the most likely contaminant genotype, with the log probability of seeing the given alt
+///   depth (across all the contaminant genotypes)
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::contamination_estimator::calc_loglik_for_hypothetical_contam_level_hom_ref;
+/// use diploid_contam_estimator::model::{VariantPosition, VariantType, Zygosity};
+/// let variant = VariantPosition::new(
+///     "chr1", 1, 100, 5, VariantType::SNV, Zygosity::HOM_REF
+/// ).unwrap();
+/// let hypothesis = calc_loglik_for_hypothetical_contam_level_hom_ref(&variant, 0.1).unwrap();
+/// assert_eq!(hypothesis.label, "contam is het at hom-ref position");
+/// ```
+pub fn calc_loglik_for_hypothetical_contam_level_hom_ref(
+    variant_position: &VariantPosition,
+    hypothetical_contamination_level: f64,
+) -> Result<Hypothesis, Error> {
+    let alt_frequency = KNOWN_SITE_ALLELE_FREQUENCY;
+    let contamination_hypotheses: Vec<(Hypothesis, f64)> = vec![
+        (
+            Hypothesis::new(HYPOTHESES[6].to_string(), 0.0)?,
+            (1.0 - alt_frequency).powi(2),
+        ),
+        (
+            Hypothesis::new(
+                HYPOTHESES[7].to_string(),
+                hypothetical_contamination_level / 2.0,
+            )?,
+            2.0 * alt_frequency * (1.0 - alt_frequency),
+        ),
+        (
+            Hypothesis::new(HYPOTHESES[8].to_string(), hypothetical_contamination_level)?,
+            alt_frequency.powi(2),
+        ),
+    ];
+    mixture_hypothesis(variant_position, contamination_hypotheses)
 }
 
 /// Helper function to calculate the log probability of a given
@@ -116,7 +268,7 @@ pub fn calc_loglik_for_hypothetical_contam_level_heterozygous(
 pub fn calaulate_loglik_for_variant_position(
     variant_position: &VariantPosition,
     hypothetical_contamination_level: f64,
-) -> Result<Hypothesis, String> {
+) -> Result<Hypothesis, Error> {
     match variant_position.zygosity {
         Zygosity::HOMOZYGOUS => {
             let variant_fraction = 1.0 - hypothetical_contamination_level;
@@ -134,9 +286,68 @@ pub fn calaulate_loglik_for_variant_position(
             )?;
             Ok(best_hypothesis)
         }
+        Zygosity::HOM_REF => calc_loglik_for_hypothetical_contam_level_hom_ref(
+            variant_position,
+            hypothetical_contamination_level,
+        ),
     }
 }
 
+/// The log probability of a variant under the best hypothesis for a given contamination level
+/// as [calaulate_loglik_for_variant_position], but with the sequencing error floor of
+/// [with_sequencing_error], so that it is on the same scale as the candidate contaminant model
+/// (see [crate::contaminant_identifier::calculate_candidate_hypothesis])
+///
+/// # Arguments
+///
+/// * `variant_position`: A VariantPosition object to be evaluated
+/// * `hypothetical_contamination_level`: hypothetical contamination level to test
+///
+/// # Returns
+///
+/// the highest log probability of seeing the given variant alt count
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::contamination_estimator::calc_floored_loglik_for_variant_position;
+/// use diploid_contam_estimator::model::{VariantPosition, VariantType, Zygosity};
+/// let variant = VariantPosition::new(
+///     "chr1", 1, 100, 99, VariantType::SNV, Zygosity::HOMOZYGOUS
+/// ).unwrap();
+/// // a ref read at a hom-alt site is a sequencing error without contamination
+/// assert!(calc_floored_loglik_for_variant_position(&variant, 0.0).unwrap().is_finite());
+/// ```
+pub fn calc_floored_loglik_for_variant_position(
+    variant_position: &VariantPosition,
+    hypothetical_contamination_level: f64,
+) -> Result<f64, Error> {
+    let hypotheses = match variant_position.zygosity {
+        Zygosity::HOMOZYGOUS => vec![Hypothesis::new(
+            HYPOTHESES[0].to_string(),
+            1.0 - hypothetical_contamination_level,
+        )?],
+        Zygosity::HETEROZYGOUS => heterozygous_hypotheses(hypothetical_contamination_level)?,
+        Zygosity::HOM_REF => {
+            return calc_loglik_for_hypothetical_contam_level_hom_ref(
+                variant_position,
+                hypothetical_contamination_level,
+            )?
+            .loglik
+            .ok_or_else(|| Error::model("loglik not calculated"))
+        }
+    };
+    hypotheses
+        .iter()
+        .map(|hypothesis| {
+            calc_loglik_for_hypothetical_contam_level(
+                variant_position,
+                with_sequencing_error(hypothesis.variant_fraction),
+            )
+        })
+        .try_fold(f64::NEG_INFINITY, |best, loglik| Ok(best.max(loglik?)))
+}
+
 /// Given a list of variant position and a hypothetical contamination level
 /// we calculate the log probabilty of seeing the given numbers of alt base across all positions
 ///
@@ -167,9 +378,9 @@ pub fn calaulate_loglik_for_variant_position(
 pub fn calculate_contam_hypothesis(
     variant_list: &mut Vec<VariantPosition>,
     hypothetical_contamination_level: f64,
-) -> Result<f64, String> {
+) -> Result<f64, Error> {
     if !(0.0..1.0).contains(&hypothetical_contamination_level) {
-        return Err("Contamination level must be > 0 and <= 1".to_string());
+        return Err(Error::model("Contamination level must be > 0 and <= 1"));
     }
 
     // parallel processing of the variant list
@@ -185,9 +396,9 @@ pub fn calculate_contam_hypothesis(
             // transferring the contamination label to the VariantPosition object
             variant_position.set_contamination_label(hyp.label);
             hyp.loglik
-                .ok_or_else(|| "loglik not calculated".to_string())
+                .ok_or_else(|| Error::model("loglik not calculated"))
         })
-        .sum::<Result<f64, String>>()?;
+        .sum::<Result<f64, Error>>()?;
     Ok(log_prob_sum)
 }
 
@@ -207,6 +418,9 @@ mod tests {
     #[case(50, 30, 0.1, -2.16666920827, Zygosity::HETEROZYGOUS, HYPOTHESES[3].to_string())] // case 3 in HET
     #[case(50, 20, 0.1, -2.16666920827, Zygosity::HETEROZYGOUS, HYPOTHESES[4].to_string())] // case 3 in HET
     #[case(50, 5, 0.1,  -1.68780709970, Zygosity::HETEROZYGOUS, HYPOTHESES[5].to_string())] // case 4 in HET
+    #[case(50, 0, 0.1,  -1.28834915712, Zygosity::HOM_REF, HYPOTHESES[6].to_string())] // no alt read at hom-ref
+    #[case(50, 2, 0.1,  -1.90690880344, Zygosity::HOM_REF, HYPOTHESES[7].to_string())] // het contam at hom-ref
+    #[case(50, 5, 0.1,  -2.51686947144, Zygosity::HOM_REF, HYPOTHESES[8].to_string())] // hom-alt contam at hom-ref
     /// SUT:  calaulate_loglik_for_variant_position
     /// Collaborators:
     ///     - calc_loglik_for_hypothetical_contam_level
@@ -235,6 +449,42 @@ mod tests {
         assert_eq!(p.label, label);
     }
 
+    /// the contamination level (in percent) with the highest log likelihood of a variant list
+    fn best_contam_level(variant_list: &mut Vec<VariantPosition>) -> usize {
+        (0..30)
+            .map(|level| {
+                let loglik = calculate_contam_hypothesis(variant_list, level as f64 / 100.0);
+                (level, loglik.unwrap())
+            })
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn test_calculate_contam_hypothesis_hom_ref_without_alt_reads() {
+        // a hom-ref call without alt reads is less likely at a higher contamination level
+        let variant =
+            VariantPosition::new("X", 1, 100, 0, VariantType::SNV, Zygosity::HOM_REF).unwrap();
+        let loglik_0 = calaulate_loglik_for_variant_position(&variant, 0.0).unwrap();
+        let loglik_10 = calaulate_loglik_for_variant_position(&variant, 0.1).unwrap();
+        assert!(loglik_0.loglik.unwrap() > loglik_10.loglik.unwrap());
+
+        // and pulls down the estimate of hom-ref calls with alt reads
+        let mut variant_list: Vec<VariantPosition> = (1..=10)
+            .map(|position| {
+                VariantPosition::new("X", position, 100, 3, VariantType::SNV, Zygosity::HOM_REF)
+                    .unwrap()
+            })
+            .collect();
+        let best_level = best_contam_level(&mut variant_list);
+        variant_list.extend((11..=30).map(|position| {
+            VariantPosition::new("X", position, 100, 0, VariantType::SNV, Zygosity::HOM_REF)
+                .unwrap()
+        }));
+        assert!(best_contam_level(&mut variant_list) < best_level);
+    }
+
     #[rstest]
     #[case(0.0,  -2.5308764039)]
     #[case(0.1,  -13.569444762)]
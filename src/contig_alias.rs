@@ -0,0 +1,134 @@
+use crate::error::Error;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::string::String;
+use std::vec::Vec;
+
+/// the equivalent names of the mitochondrial genome
+const MITOCHONDRIAL_NAMES: [&str; 4] = ["MT", "chrM", "M", "chrMT"];
+
+/// Contig name aliases for matching contigs between the vcf file and the bed/fasta files
+///
+/// A contig name is resolved to a target name (e.g. a contig in the vcf header) by trying,
+/// in this order: the name itself, the aliases from a user-supplied alias table, the name
+/// with the `chr` prefix added or removed, and the mitochondrial names (MT, chrM, M, chrMT)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContigAliases {
+    /// all equivalent names of each contig name in the alias table
+    aliases: HashMap<String, Vec<String>>,
+}
+
+impl ContigAliases {
+    /// Read an alias table, each line lists the equivalent names of a contig
+    /// (tab or space separated, e.g. `chr1 1 NC_000001.11`), `#` lines are skipped
+    ///
+    /// # Arguments
+    /// * `alias_file`: the file path of the alias table
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::contig_alias::ContigAliases;
+    /// let contig_aliases = ContigAliases::from_file("data/contig_aliases.tsv").unwrap();
+    /// let vcf_contigs = vec!["X".to_string()];
+    /// assert_eq!(
+    ///     contig_aliases.resolve("NC_000023.11", &vcf_contigs),
+    ///     Some("X".to_string())
+    /// );
+    /// ```
+    pub fn from_file(alias_file: &str) -> Result<Self, Error> {
+        let reader = File::open(alias_file)
+            .map(BufReader::new)
+            .map_err(|e| Error::io(alias_file, e))?;
+        let mut contig_aliases = ContigAliases::default();
+        for line in reader.lines() {
+            let line = line.map_err(|e| Error::io(alias_file, e))?;
+            if line.starts_with('#') {
+                continue;
+            }
+            contig_aliases.add(&line.split_whitespace().collect::<Vec<&str>>());
+        }
+        Ok(contig_aliases)
+    }
+
+    /// Add a group of equivalent contig names
+    pub fn add(&mut self, names: &[&str]) {
+        for name in names {
+            let aliases = self.aliases.entry(name.to_string()).or_default();
+            for alias in names {
+                if alias != name && !aliases.iter().any(|a| a == alias) {
+                    aliases.push(alias.to_string());
+                }
+            }
+        }
+    }
+
+    /// the candidate names of a contig, in the order they are tried
+    fn candidates(&self, contig: &str) -> Vec<String> {
+        let mut candidates: Vec<String> = vec![contig.to_string()];
+        if let Some(aliases) = self.aliases.get(contig) {
+            candidates.extend(aliases.iter().cloned());
+        }
+        candidates.push(match contig.strip_prefix("chr") {
+            Some(stripped) => stripped.to_string(),
+            None => format!("chr{}", contig),
+        });
+        if MITOCHONDRIAL_NAMES.contains(&contig) {
+            candidates.extend(MITOCHONDRIAL_NAMES.iter().map(|name| name.to_string()));
+        }
+        candidates
+    }
+
+    /// Resolve a contig name to one of the target contig names
+    ///
+    /// # Arguments
+    /// * `contig`: the contig name to be resolved (e.g. from a bed file)
+    /// * `targets`: the available contig names (e.g. from the vcf header)
+    ///
+    /// # Returns
+    /// * the matching target contig name, or None if the contig cannot be matched
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::contig_alias::ContigAliases;
+    /// let vcf_contigs = vec!["1".to_string(), "MT".to_string()];
+    /// let contig_aliases = ContigAliases::default();
+    /// assert_eq!(contig_aliases.resolve("chr1", &vcf_contigs), Some("1".to_string()));
+    /// assert_eq!(contig_aliases.resolve("chrM", &vcf_contigs), Some("MT".to_string()));
+    /// assert_eq!(contig_aliases.resolve("chr2", &vcf_contigs), None);
+    /// ```
+    pub fn resolve(&self, contig: &str, targets: &[String]) -> Option<String> {
+        self.candidates(contig)
+            .into_iter()
+            .find(|candidate| targets.contains(candidate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("X", Some("X"))]
+    #[case("chrX", Some("X"))]
+    #[case("1", Some("chr1"))]
+    #[case("chrM", Some("MT"))]
+    #[case("M", Some("MT"))]
+    #[case("NC_000001.11", Some("chr1"))] // from the alias table
+    #[case("chr2", None)]
+    fn test_resolve(#[case] contig: &str, #[case] expected: Option<&str>) {
+        let targets: Vec<String> = vec!["X", "chr1", "MT"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let mut contig_aliases = ContigAliases::default();
+        contig_aliases.add(&["chr1", "1", "NC_000001.11"]);
+        assert_eq!(
+            contig_aliases.resolve(contig, &targets).as_deref(),
+            expected
+        );
+    }
+}
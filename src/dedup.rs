@@ -0,0 +1,255 @@
+use crate::error::Error;
+use crate::model::{VariantPosition, VariantType};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+use std::vec::Vec;
+
+/// The rules for choosing the variant to keep among variants at overlapping loci
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupRule {
+    /// keep the variant with the highest total read depth (DP)
+    DEPTH,
+    /// keep the variant with the highest QUAL, variants without QUAL come last
+    QUAL,
+    /// keep a SNV over other variant types, then the highest total read depth
+    SNV,
+}
+
+impl DedupRule {
+    /// Compare two variants, the greater one is preferred
+    fn compare(&self, a: &VariantPosition, b: &VariantPosition) -> Ordering {
+        match self {
+            DedupRule::DEPTH => a.total_read_depth.cmp(&b.total_read_depth),
+            DedupRule::QUAL => match (a.quality, b.quality) {
+                (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                (a, b) => a.is_some().cmp(&b.is_some()),
+            },
+            DedupRule::SNV => (a.variant_type == VariantType::SNV)
+                .cmp(&(b.variant_type == VariantType::SNV))
+                .then(a.total_read_depth.cmp(&b.total_read_depth)),
+        }
+    }
+}
+
+impl FromStr for DedupRule {
+    type Err = Error;
+
+    /// Parse a rule name: `depth`, `qual` or `snv`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "depth" => Ok(DedupRule::DEPTH),
+            "qual" => Ok(DedupRule::QUAL),
+            "snv" => Ok(DedupRule::SNV),
+            _ => Err(Error::option(format!(
+                "Unknown deduplication rule {}, available rules: depth,qual,snv",
+                s
+            ))),
+        }
+    }
+}
+
+/// the indices of the variants grouped by contig, sorted by position
+fn sorted_by_contig(variants: &[VariantPosition]) -> BTreeMap<&str, Vec<usize>> {
+    let mut contig_indices: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, variant) in variants.iter().enumerate() {
+        contig_indices
+            .entry(variant.contig.as_str())
+            .or_default()
+            .push(i);
+    }
+    for indices in contig_indices.values_mut() {
+        indices.sort_by_key(|&i| (variants[i].position, variants[i].end_position()));
+    }
+    contig_indices
+}
+
+/// Keep one variant per locus, where a locus is a cluster of variants with overlapping
+/// ref alleles (e.g. the same position emitted twice from a split multi-allelic record,
+/// or overlapping indels), as they are supported by the same reads
+///
+/// # Arguments
+/// * `variants`: the variants of a sample, the order of the kept variants is preserved
+/// * `rule`: how to choose the variant to keep, ties are resolved by the vcf order
+///
+/// # Returns
+/// * the number of variants removed
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::dedup::{resolve_duplicates, DedupRule};
+/// use diploid_contam_estimator::model::{VariantPosition, VariantType, Zygosity};
+/// let mut variants = vec![
+///     VariantPosition::new("X", 10, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS).unwrap(),
+///     VariantPosition::new("X", 10, 120, 60, VariantType::SNV, Zygosity::HETEROZYGOUS).unwrap(),
+///     VariantPosition::new("X", 11, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS).unwrap(),
+/// ];
+/// assert_eq!(resolve_duplicates(&mut variants, DedupRule::DEPTH), 1);
+/// assert_eq!(variants[0].total_read_depth, 120);
+/// assert_eq!(variants[1].position, 11);
+/// ```
+pub fn resolve_duplicates(variants: &mut Vec<VariantPosition>, rule: DedupRule) -> usize {
+    let mut kept: HashSet<usize> = HashSet::new();
+    for indices in sorted_by_contig(variants).values() {
+        let mut best = indices[0];
+        let mut locus_end = variants[best].end_position();
+        for &i in &indices[1..] {
+            if variants[i].position > locus_end {
+                kept.insert(best);
+                best = i;
+            } else if rule
+                .compare(&variants[i], &variants[best])
+                .then(best.cmp(&i))
+                == Ordering::Greater
+            {
+                best = i;
+            }
+            locus_end = locus_end.max(variants[i].end_position());
+        }
+        kept.insert(best);
+    }
+    let variant_count = variants.len();
+    let mut i = 0;
+    variants.retain(|_| {
+        i += 1;
+        kept.contains(&(i - 1))
+    });
+    variant_count - variants.len()
+}
+
+/// Remove the variants within a distance of an indel, whose allele counts are prone to
+/// misalignment of the reads spanning the indel (the indels are removed too if they
+/// are within the distance of another indel)
+///
+/// # Arguments
+/// * `variants`: the variants of a sample, the order of the kept variants is preserved
+/// * `gap`: the distance (in bp) from the ref allele of an indel
+///
+/// # Returns
+/// * the number of variants removed
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::dedup::remove_near_indels;
+/// use diploid_contam_estimator::model::{VariantPosition, VariantType, Zygosity};
+/// let mut variants = vec![
+///     VariantPosition::new("X", 10, 100, 50, VariantType::INDEL, Zygosity::HETEROZYGOUS).unwrap(),
+///     VariantPosition::new("X", 13, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS).unwrap(),
+///     VariantPosition::new("X", 20, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS).unwrap(),
+/// ];
+/// assert_eq!(remove_near_indels(&mut variants, 5), 1);
+/// assert_eq!(variants.len(), 2);
+/// ```
+pub fn remove_near_indels(variants: &mut Vec<VariantPosition>, gap: usize) -> usize {
+    let mut removed: HashSet<usize> = HashSet::new();
+    for indices in sorted_by_contig(variants).values() {
+        let indels: Vec<usize> = indices
+            .iter()
+            .copied()
+            .filter(|&i| variants[i].variant_type == VariantType::INDEL)
+            .collect();
+        let max_indel_span = indels
+            .iter()
+            .map(|&i| variants[i].end_position() - variants[i].position)
+            .max()
+            .unwrap_or(0);
+        for &i in indices {
+            let start = variants[i].position;
+            let end = variants[i].end_position();
+            // the indels starting at or before end + gap, the closest first
+            let candidates = indels.partition_point(|&j| variants[j].position <= end + gap);
+            let near_indel = indels[..candidates]
+                .iter()
+                .rev()
+                .take_while(|&&j| variants[j].position + max_indel_span + gap >= start)
+                .any(|&j| j != i && variants[j].end_position() + gap >= start);
+            if near_indel {
+                removed.insert(i);
+            }
+        }
+    }
+    let variant_count = variants.len();
+    let mut i = 0;
+    variants.retain(|_| {
+        i += 1;
+        !removed.contains(&(i - 1))
+    });
+    variant_count - variants.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Zygosity;
+    use rstest::*;
+
+    /// a heterozygous variant with the alleles and QUAL set
+    fn variant(
+        position: usize,
+        ref_allele: &str,
+        alt_allele: &str,
+        depth: usize,
+        quality: f64,
+    ) -> VariantPosition {
+        let mut variant = VariantPosition::new(
+            "X",
+            position,
+            depth,
+            depth / 2,
+            VariantType::classify(ref_allele, alt_allele),
+            Zygosity::HETEROZYGOUS,
+        )
+        .unwrap();
+        variant.set_alleles(ref_allele, alt_allele);
+        variant.set_quality(quality);
+        variant
+    }
+
+    /// a split multi-allelic record, a deletion overlapping a SNV and an isolated SNV
+    fn variants() -> Vec<VariantPosition> {
+        vec![
+            variant(100, "A", "G", 50, 30.0),
+            variant(100, "A", "T", 60, 20.0),
+            variant(200, "ACGT", "A", 80, 10.0),
+            variant(202, "G", "C", 70, 40.0),
+            variant(300, "C", "T", 90, 50.0),
+        ]
+    }
+
+    #[rstest]
+    #[case(DedupRule::DEPTH, vec![(100, "T"), (200, "A"), (300, "T")])]
+    #[case(DedupRule::QUAL, vec![(100, "G"), (202, "C"), (300, "T")])]
+    #[case(DedupRule::SNV, vec![(100, "T"), (202, "C"), (300, "T")])]
+    fn test_resolve_duplicates(#[case] rule: DedupRule, #[case] expected: Vec<(usize, &str)>) {
+        let mut variants = variants();
+        assert_eq!(resolve_duplicates(&mut variants, rule), 2);
+        let kept: Vec<(usize, &str)> = variants
+            .iter()
+            .map(|variant| (variant.position, variant.alt_allele.as_deref().unwrap()))
+            .collect();
+        assert_eq!(kept, expected);
+    }
+
+    #[rstest]
+    #[case(0, vec![100, 100, 200, 300])]
+    #[case(97, vec![100, 100, 200])]
+    #[case(100, vec![200])]
+    fn test_remove_near_indels(#[case] gap: usize, #[case] expected: Vec<usize>) {
+        let mut variants = variants();
+        let removed = remove_near_indels(&mut variants, gap);
+        let kept: Vec<usize> = variants.iter().map(|variant| variant.position).collect();
+        assert_eq!(removed, 5 - kept.len());
+        assert_eq!(kept, expected);
+    }
+
+    #[rstest]
+    #[case("depth", Some(DedupRule::DEPTH))]
+    #[case("QUAL", Some(DedupRule::QUAL))]
+    #[case("snv", Some(DedupRule::SNV))]
+    #[case("dp", None)]
+    fn test_dedup_rule_from_str(#[case] value: &str, #[case] expected: Option<DedupRule>) {
+        assert_eq!(value.parse::<DedupRule>().ok(), expected);
+    }
+}
@@ -0,0 +1,280 @@
+use std::fmt;
+use std::io;
+use std::string::String;
+use std::vec::Vec;
+
+/// Where in the input an error occurred, each part is optional
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// the input file
+    pub file: Option<String>,
+    /// 1-based line number in the file
+    pub line: Option<usize>,
+    /// the record being processed, e.g. the locus of a vcf record
+    pub record: Option<String>,
+}
+
+impl ErrorContext {
+    /// true if no part of the context is known
+    pub fn is_empty(&self) -> bool {
+        self.file.is_none() && self.line.is_none() && self.record.is_none()
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = vec![];
+        if let Some(file) = &self.file {
+            parts.push(file.to_string());
+        }
+        if let Some(line) = self.line {
+            parts.push(format!("line {}", line));
+        }
+        if let Some(record) = &self.record {
+            parts.push(format!("record {}", record));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// The errors of the library, each kind of error has its own exit code (see [Error::exit_code])
+pub enum Error {
+    /// a file cannot be opened, read or written
+    IO {
+        context: ErrorContext,
+        source: io::Error,
+    },
+    /// an input (a file, record, region, expression or option value) is malformed: it cannot
+    /// be parsed or its values are inconsistent
+    PARSE {
+        context: ErrorContext,
+        message: String,
+    },
+    /// a required field is missing, e.g. `FORMAT/AD`
+    MISSING {
+        context: ErrorContext,
+        field: String,
+    },
+    /// an index file is missing or invalid, or cannot be queried
+    INDEX {
+        context: ErrorContext,
+        message: String,
+    },
+    /// the model cannot be evaluated, e.g. a parameter out of range
+    MODEL { message: String },
+    /// invalid or conflicting options
+    OPTION { message: String },
+}
+
+impl Error {
+    /// an I/O error of a file (or `-` for stdin)
+    pub fn io(file: &str, source: io::Error) -> Self {
+        Error::IO {
+            context: ErrorContext {
+                file: Some(file.to_string()),
+                ..ErrorContext::default()
+            },
+            source,
+        }
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Error::PARSE {
+            context: ErrorContext::default(),
+            message: message.into(),
+        }
+    }
+
+    pub fn missing(field: impl Into<String>) -> Self {
+        Error::MISSING {
+            context: ErrorContext::default(),
+            field: field.into(),
+        }
+    }
+
+    pub fn index(message: impl Into<String>) -> Self {
+        Error::INDEX {
+            context: ErrorContext::default(),
+            message: message.into(),
+        }
+    }
+
+    pub fn model(message: impl Into<String>) -> Self {
+        Error::MODEL {
+            message: message.into(),
+        }
+    }
+
+    pub fn option(message: impl Into<String>) -> Self {
+        Error::OPTION {
+            message: message.into(),
+        }
+    }
+
+    /// the context of the error, or None for model and option errors
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::IO { context, .. }
+            | Error::PARSE { context, .. }
+            | Error::MISSING { context, .. }
+            | Error::INDEX { context, .. } => Some(context),
+            Error::MODEL { .. } | Error::OPTION { .. } => None,
+        }
+    }
+
+    fn context_mut(&mut self) -> Option<&mut ErrorContext> {
+        match self {
+            Error::IO { context, .. }
+            | Error::PARSE { context, .. }
+            | Error::MISSING { context, .. }
+            | Error::INDEX { context, .. } => Some(context),
+            Error::MODEL { .. } | Error::OPTION { .. } => None,
+        }
+    }
+
+    /// Add the input file to the context, unless it is already known
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::error::Error;
+    /// let error = Error::missing("FORMAT/AD")
+    ///     .with_record("X:38145492")
+    ///     .with_line(12)
+    ///     .with_file("data/test.vcf");
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "data/test.vcf, line 12, record X:38145492: Missing FORMAT/AD"
+    /// );
+    /// ```
+    pub fn with_file(mut self, file: &str) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.file.get_or_insert_with(|| file.to_string());
+        }
+        self
+    }
+
+    /// Add the 1-based line number to the context, unless it is already known
+    pub fn with_line(mut self, line: usize) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.line.get_or_insert(line);
+        }
+        self
+    }
+
+    /// Add the record to the context, unless it is already known
+    pub fn with_record(mut self, record: &str) -> Self {
+        if let Some(context) = self.context_mut() {
+            context.record.get_or_insert_with(|| record.to_string());
+        }
+        self
+    }
+
+    /// The process exit code of the error
+    ///
+    /// | error    | exit code |
+    /// |----------|-----------|
+    /// | OPTION   | 2 (as for command line usage errors) |
+    /// | IO       | 3         |
+    /// | PARSE    | 4         |
+    /// | MISSING  | 5         |
+    /// | INDEX    | 6         |
+    /// | MODEL    | 7         |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::OPTION { .. } => 2,
+            Error::IO { .. } => 3,
+            Error::PARSE { .. } => 4,
+            Error::MISSING { .. } => 5,
+            Error::INDEX { .. } => 6,
+            Error::MODEL { .. } => 7,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(context) = self.context().filter(|context| !context.is_empty()) {
+            write!(f, "{}: ", context)?;
+        }
+        match self {
+            Error::IO { source, .. } => write!(f, "{}", source),
+            Error::MISSING { field, .. } => write!(f, "Missing {}", field),
+            Error::PARSE { message, .. }
+            | Error::INDEX { message, .. }
+            | Error::MODEL { message }
+            | Error::OPTION { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+/// the kind of the error and its message, so that unwrapped errors are readable
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Error::IO { .. } => "IO",
+            Error::PARSE { .. } => "PARSE",
+            Error::MISSING { .. } => "MISSING",
+            Error::INDEX { .. } => "INDEX",
+            Error::MODEL { .. } => "MODEL",
+            Error::OPTION { .. } => "OPTION",
+        };
+        write!(f, "{} error: {}", kind, self)
+    }
+}
+
+/// an I/O error without context, e.g. of a stream, the file can be added with [Error::with_file]
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Self {
+        Error::IO {
+            context: ErrorContext::default(),
+            source,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IO { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(Error::parse("Invalid interval X:5-3"), "Invalid interval X:5-3")]
+    #[case(Error::parse("Invalid position").with_line(3).with_file("a.bed"), "a.bed, line 3: Invalid position")]
+    #[case(Error::missing("FORMAT/AD").with_record("X:1").with_record("X:2"), "record X:1: Missing FORMAT/AD")]
+    #[case(Error::model("Fetal fraction must be >= 0 and < 1").with_file("a.vcf"), "Fetal fraction must be >= 0 and < 1")]
+    #[case(
+        Error::io("a.vcf", io::Error::new(io::ErrorKind::NotFound, "not found")),
+        "a.vcf: not found"
+    )]
+    fn test_display(#[case] error: Error, #[case] expected: &str) {
+        assert_eq!(error.to_string(), expected);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let mut exit_codes: Vec<i32> = [
+            Error::io("-", io::Error::other("")),
+            Error::parse(""),
+            Error::missing(""),
+            Error::index(""),
+            Error::model(""),
+            Error::option(""),
+        ]
+        .iter()
+        .map(|error| error.exit_code())
+        .collect();
+        exit_codes.sort_unstable();
+        exit_codes.dedup();
+        assert_eq!(exit_codes, vec![2, 3, 4, 5, 6, 7]);
+    }
+}
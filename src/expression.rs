@@ -0,0 +1,532 @@
+use crate::error::Error;
+use crate::vcfreader::record_filter_values;
+use noodles_vcf::header::format::Key as FormatKey;
+use noodles_vcf::header::info::Key as InfoKey;
+use noodles_vcf::record::genotypes::genotype::field::Value as FormatValue;
+use noodles_vcf::record::genotypes::Genotype;
+use noodles_vcf::record::info::field::Value as InfoValue;
+use noodles_vcf::record::Record;
+use std::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+
+/// A field of a vcf record that can be used in an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    /// the QUAL column
+    QUAL,
+    /// the FILTER column
+    FILTER,
+    /// an INFO tag, and optionally the 0-based index into its values
+    INFO(String, Option<usize>),
+    /// a FORMAT tag of the sample, and optionally the 0-based index into its values
+    FORMAT(String, Option<usize>),
+}
+
+/// One side of a comparison
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    FIELD(Field),
+    NUMBER(f64),
+    TEXT(String),
+}
+
+/// Comparison operators
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    EQ,
+    NE,
+    GT,
+    GE,
+    LT,
+    LE,
+}
+
+/// A bcftools-style filtering expression over the QUAL, FILTER, INFO and FORMAT fields of a record,
+/// e.g. `QUAL>30 && FMT/GQ>=20 && INFO/DB`
+///
+/// * fields are `QUAL`, `FILTER`, `INFO/TAG`, `FMT/TAG` (or `FORMAT/TAG`), a bare `TAG` refers to INFO
+/// * values of a multi-value field can be selected by a 0-based index, e.g. `FMT/AD[1]`
+/// * a comparison holds if any of the (non-missing) values of a field satisfies it,
+///   `FILTER="LowQual"` holds if `LowQual` is one of the FILTER values
+/// * a field alone tests for its presence, e.g. an INFO flag
+/// * comparisons are combined with `&&`, `||`, `!` and parentheses
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    AND(Box<Expression>, Box<Expression>),
+    OR(Box<Expression>, Box<Expression>),
+    NOT(Box<Expression>),
+    COMPARE(Operand, Comparison, Operand),
+    EXISTS(Field),
+}
+
+/// a value of a record field
+#[derive(Debug, Clone, PartialEq)]
+enum Scalar {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Compare(Comparison),
+    Number(f64),
+    Text(String),
+    Word(String),
+}
+
+/// split an expression string into tokens
+fn tokenize(expression: &str) -> Result<Vec<Token>, Error> {
+    let chars: Vec<char> = expression.chars().collect();
+    let mut tokens: Vec<Token> = vec![];
+    let mut i = 0;
+    let is_word_char = |c: char| c.is_ascii_alphanumeric() || "_./[]".contains(c);
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, width) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('&', _) => (Token::And, 1),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('|', _) => (Token::Or, 1),
+            ('!', Some('=')) => (Token::Compare(Comparison::NE), 2),
+            ('!', _) => (Token::Not, 1),
+            ('=', Some('=')) => (Token::Compare(Comparison::EQ), 2),
+            ('=', _) => (Token::Compare(Comparison::EQ), 1),
+            ('>', Some('=')) => (Token::Compare(Comparison::GE), 2),
+            ('>', _) => (Token::Compare(Comparison::GT), 1),
+            ('<', Some('=')) => (Token::Compare(Comparison::LE), 2),
+            ('<', _) => (Token::Compare(Comparison::LT), 1),
+            ('"', _) | ('\'', _) => {
+                let end = chars[i + 1..].iter().position(|&q| q == c).ok_or_else(|| {
+                    Error::parse(format!("Unterminated string in expression: {}", expression))
+                })?;
+                let text: String = chars[i + 1..i + 1 + end].iter().collect();
+                (Token::Text(text), end + 2)
+            }
+            (c, _) if is_word_char(c) || c == '-' => {
+                let width = 1 + chars[i + 1..]
+                    .iter()
+                    .take_while(|&&w| is_word_char(w) || w == '+' || w == '-')
+                    .count();
+                let word: String = chars[i..i + width].iter().collect();
+                match word.parse::<f64>() {
+                    Ok(number) => (Token::Number(number), width),
+                    Err(_) if c == '-' => {
+                        return Err(Error::parse(format!(
+                            "Invalid number {} in expression",
+                            word
+                        )))
+                    }
+                    Err(_) => (Token::Word(word), width),
+                }
+            }
+            (c, _) => {
+                return Err(Error::parse(format!(
+                    "Unexpected character '{}' in expression: {}",
+                    c, expression
+                )))
+            }
+        };
+        tokens.push(token);
+        i += width;
+    }
+    Ok(tokens)
+}
+
+/// parse a field name, e.g. QUAL, INFO/DP, FMT/AD[1]
+fn parse_field(word: &str) -> Result<Field, Error> {
+    let (name, index) = match word.strip_suffix(']').and_then(|w| w.split_once('[')) {
+        Some((name, index)) => (
+            name,
+            Some(
+                index
+                    .parse::<usize>()
+                    .map_err(|_| Error::parse(format!("Invalid index in field {}", word)))?,
+            ),
+        ),
+        None => (word, None),
+    };
+    if name.is_empty() || name.contains('[') || name.contains(']') {
+        return Err(Error::parse(format!("Invalid field {}", word)));
+    }
+    match name.split_once('/') {
+        None if name == "QUAL" => Ok(Field::QUAL),
+        None if name == "FILTER" => Ok(Field::FILTER),
+        None => Ok(Field::INFO(name.to_string(), index)),
+        Some(("INFO", tag)) if !tag.is_empty() => Ok(Field::INFO(tag.to_string(), index)),
+        Some(("FMT", tag)) | Some(("FORMAT", tag)) if !tag.is_empty() => {
+            Ok(Field::FORMAT(tag.to_string(), index))
+        }
+        _ => Err(Error::parse(format!("Invalid field {}", word))),
+    }
+}
+
+/// recursive descent parser over the expression tokens
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expression = Expression::OR(Box::new(expression), Box::new(self.parse_and()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_not()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expression = Expression::AND(Box::new(expression), Box::new(self.parse_not()?));
+        }
+        Ok(expression)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, Error> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next();
+                Ok(Expression::NOT(Box::new(self.parse_not()?)))
+            }
+            Some(Token::LParen) => {
+                self.next();
+                let expression = self.parse_or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expression),
+                    _ => Err(Error::parse("Missing closing parenthesis in expression")),
+                }
+            }
+            _ => self.parse_comparison(),
+        }
+    }
+
+    fn parse_operand(&mut self) -> Result<Operand, Error> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Operand::NUMBER(number)),
+            Some(Token::Text(text)) => Ok(Operand::TEXT(text)),
+            Some(Token::Word(word)) => Ok(Operand::FIELD(parse_field(&word)?)),
+            Some(token) => Err(Error::parse(format!(
+                "Unexpected token {:?} in expression",
+                token
+            ))),
+            None => Err(Error::parse("Unexpected end of expression")),
+        }
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, Error> {
+        let lhs = self.parse_operand()?;
+        match self.peek() {
+            Some(Token::Compare(comparison)) => {
+                let comparison = *comparison;
+                self.next();
+                let rhs = self.parse_operand()?;
+                Ok(Expression::COMPARE(lhs, comparison, rhs))
+            }
+            _ => match lhs {
+                Operand::FIELD(field) => Ok(Expression::EXISTS(field)),
+                _ => Err(Error::parse(format!(
+                    "Expected a comparison after {:?}",
+                    lhs
+                ))),
+            },
+        }
+    }
+}
+
+impl FromStr for Expression {
+    type Err = Error;
+
+    /// Parse a filtering expression
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::expression::Expression;
+    /// let expression: Expression = "QUAL>30 && FMT/GQ>=20 && INFO/DB".parse().unwrap();
+    /// assert!("QUAL>".parse::<Expression>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        if parser.tokens.is_empty() {
+            return Err(Error::parse("Empty expression"));
+        }
+        let expression = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(Error::parse(format!(
+                "Unexpected token {:?} in expression: {}",
+                token, s
+            ))),
+        }
+    }
+}
+
+fn info_scalars(value: &InfoValue) -> Vec<Option<Scalar>> {
+    match value {
+        InfoValue::Integer(n) => vec![Some(Scalar::Number(*n as f64))],
+        InfoValue::Float(n) => vec![Some(Scalar::Number(*n as f64))],
+        InfoValue::Flag => vec![Some(Scalar::Number(1.0))],
+        InfoValue::Character(c) => vec![Some(Scalar::Text(c.to_string()))],
+        InfoValue::String(s) => s.split(',').map(text_scalar).collect(),
+        InfoValue::IntegerArray(values) => values
+            .iter()
+            .map(|v| v.map(|n| Scalar::Number(n as f64)))
+            .collect(),
+        InfoValue::FloatArray(values) => values
+            .iter()
+            .map(|v| v.map(|n| Scalar::Number(n as f64)))
+            .collect(),
+        InfoValue::CharacterArray(values) => values
+            .iter()
+            .map(|v| v.map(|c| Scalar::Text(c.to_string())))
+            .collect(),
+        InfoValue::StringArray(values) => values
+            .iter()
+            .map(|v| v.as_deref().and_then(text_scalar))
+            .collect(),
+    }
+}
+
+fn format_scalars(value: &FormatValue) -> Vec<Option<Scalar>> {
+    match value {
+        FormatValue::Integer(n) => vec![Some(Scalar::Number(*n as f64))],
+        FormatValue::Float(n) => vec![Some(Scalar::Number(*n as f64))],
+        FormatValue::Character(c) => vec![Some(Scalar::Text(c.to_string()))],
+        // the GT string should not be split
+        FormatValue::String(s) => vec![Some(Scalar::Text(s.to_string()))],
+        FormatValue::IntegerArray(values) => values
+            .iter()
+            .map(|v| v.map(|n| Scalar::Number(n as f64)))
+            .collect(),
+        FormatValue::FloatArray(values) => values
+            .iter()
+            .map(|v| v.map(|n| Scalar::Number(n as f64)))
+            .collect(),
+        FormatValue::CharacterArray(values) => values
+            .iter()
+            .map(|v| v.map(|c| Scalar::Text(c.to_string())))
+            .collect(),
+        FormatValue::StringArray(values) => values
+            .iter()
+            .map(|v| v.as_deref().and_then(text_scalar))
+            .collect(),
+    }
+}
+
+/// a value from an untyped field, "." is missing
+fn text_scalar(s: &str) -> Option<Scalar> {
+    match s {
+        "." => None,
+        s => Some(match s.parse::<f64>() {
+            Ok(number) => Scalar::Number(number),
+            Err(_) => Scalar::Text(s.to_string()),
+        }),
+    }
+}
+
+/// the non-missing values of a field in a record
+fn field_values(field: &Field, record: &Record, sample_genotype: Option<&Genotype>) -> Vec<Scalar> {
+    let (values, index): (Vec<Option<Scalar>>, &Option<usize>) = match field {
+        Field::QUAL => (
+            vec![record
+                .quality_score()
+                .map(|score| Scalar::Number(f32::from(score) as f64))],
+            &None,
+        ),
+        Field::FILTER => (
+            record_filter_values(record)
+                .into_iter()
+                .map(|value| Some(Scalar::Text(value)))
+                .collect(),
+            &None,
+        ),
+        Field::INFO(tag, index) => (
+            InfoKey::from_str(tag)
+                .ok()
+                .and_then(|key| record.info().get(&key))
+                .and_then(|field| field.value())
+                .map(info_scalars)
+                .unwrap_or_default(),
+            index,
+        ),
+        Field::FORMAT(tag, index) => (
+            FormatKey::from_str(tag)
+                .ok()
+                .and_then(|key| sample_genotype?.get(&key))
+                .and_then(|field| field.value())
+                .map(format_scalars)
+                .unwrap_or_default(),
+            index,
+        ),
+    };
+    match index {
+        Some(i) => values.into_iter().nth(*i).flatten().into_iter().collect(),
+        None => values.into_iter().flatten().collect(),
+    }
+}
+
+fn compare_scalars(lhs: &Scalar, comparison: Comparison, rhs: &Scalar) -> bool {
+    let ordering = match (lhs, rhs) {
+        (Scalar::Number(l), Scalar::Number(r)) => l.partial_cmp(r),
+        (Scalar::Text(l), Scalar::Text(r)) => Some(l.cmp(r)),
+        // a text value is never equal to a number
+        _ => None,
+    };
+    match (comparison, ordering) {
+        (Comparison::NE, None) => true,
+        (_, None) => false,
+        (Comparison::EQ, Some(o)) => o.is_eq(),
+        (Comparison::NE, Some(o)) => o.is_ne(),
+        (Comparison::GT, Some(o)) => o.is_gt(),
+        (Comparison::GE, Some(o)) => o.is_ge(),
+        (Comparison::LT, Some(o)) => o.is_lt(),
+        (Comparison::LE, Some(o)) => o.is_le(),
+    }
+}
+
+impl Expression {
+    /// Evaluate the expression on a record
+    ///
+    /// # Arguments
+    /// * `record`: the vcf record
+    /// * `sample_genotype`: the FORMAT fields of the sample being evaluated, FORMAT fields are
+    ///   treated as missing if not given
+    ///
+    /// # Returns
+    /// * whether the record satisfies the expression, comparisons involving missing values are false
+    pub fn evaluate(&self, record: &Record, sample_genotype: Option<&Genotype>) -> bool {
+        let operand_values = |operand: &Operand| match operand {
+            Operand::FIELD(field) => field_values(field, record, sample_genotype),
+            Operand::NUMBER(number) => vec![Scalar::Number(*number)],
+            Operand::TEXT(text) => vec![Scalar::Text(text.to_string())],
+        };
+        match self {
+            Expression::AND(lhs, rhs) => {
+                lhs.evaluate(record, sample_genotype) && rhs.evaluate(record, sample_genotype)
+            }
+            Expression::OR(lhs, rhs) => {
+                lhs.evaluate(record, sample_genotype) || rhs.evaluate(record, sample_genotype)
+            }
+            Expression::NOT(expression) => !expression.evaluate(record, sample_genotype),
+            Expression::EXISTS(field) => !field_values(field, record, sample_genotype).is_empty(),
+            Expression::COMPARE(lhs, comparison, rhs) => {
+                let rhs_values = operand_values(rhs);
+                operand_values(lhs).iter().any(|l| {
+                    rhs_values
+                        .iter()
+                        .any(|r| compare_scalars(l, *comparison, r))
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use noodles_vcf as vcf;
+    use rstest::*;
+
+    const HEADER: &str = "##fileformat=VCFv4.2
+##INFO=<ID=DB,Number=0,Type=Flag,Description=\"dbSNP membership\">
+##INFO=<ID=AF,Number=A,Type=Float,Description=\"Allele frequency\">
+##FILTER=<ID=LowQual,Description=\"Low quality\">
+##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">
+##FORMAT=<ID=GQ,Number=1,Type=Integer,Description=\"Genotype quality\">
+##FORMAT=<ID=DP,Number=1,Type=Integer,Description=\"Read depth\">
+##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Allele depths\">
+##FORMAT=<ID=VAF,Number=A,Type=Float,Description=\"Variant allele fraction\">
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tsample
+";
+    const RECORD: &str =
+        "X\t1\t.\tA\tG\t45\tLowQual\tDB;AF=0.5\tGT:GQ:DP:AD:VAF\t0/1:15:60:32,28:0.466";
+
+    #[rstest]
+    #[case("QUAL>30", true)]
+    #[case("QUAL>30 && FMT/GQ>=20", false)]
+    #[case("QUAL>30 && (FMT/GQ>=20 || INFO/DB)", true)]
+    #[case("INFO/DB", true)]
+    #[case("!DB", false)]
+    #[case("INFO/MQ", false)]
+    #[case("INFO/MQ<10", false)] // missing values never pass a comparison
+    #[case("FMT/AD[1]>=25 & FMT/AD[1]<30", true)]
+    #[case("FMT/AD>30", true)] // any of the values
+    #[case("FMT/AD[1]>30", false)]
+    #[case("FORMAT/VAF>0.4 && FMT/VAF<0.6", true)]
+    #[case("FMT/DP<=60 && AF=0.5", true)]
+    #[case("FILTER=\"LowQual\"", true)]
+    #[case("FILTER!='PASS'", true)]
+    #[case("FMT/GT==\"0/1\"", true)]
+    #[case("QUAL>-1e3", true)]
+    fn test_evaluate(#[case] expression: &str, #[case] expected: bool) {
+        let header: vcf::Header = HEADER.parse().unwrap();
+        let record = Record::try_from_str(RECORD, &header).unwrap();
+        let expression: Expression = expression.parse().unwrap();
+        assert_eq!(
+            expression.evaluate(&record, record.genotypes().first()),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("QUAL>")]
+    #[case("QUAL>30 &&")]
+    #[case("(QUAL>30")]
+    #[case("QUAL>30)")]
+    #[case("30")]
+    #[case("FMT/AD[x]>1")]
+    #[case("FILTER=\"PASS")]
+    #[case("QUAL>30 ; DP>1")]
+    fn test_parse_exception(#[case] expression: &str) {
+        assert!(expression.parse::<Expression>().is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let expression: Expression = "QUAL>30 && !INFO/DB".parse().unwrap();
+        assert_eq!(
+            expression,
+            Expression::AND(
+                Box::new(Expression::COMPARE(
+                    Operand::FIELD(Field::QUAL),
+                    Comparison::GT,
+                    Operand::NUMBER(30.0)
+                )),
+                Box::new(Expression::NOT(Box::new(Expression::EXISTS(Field::INFO(
+                    "DB".to_string(),
+                    None
+                )))))
+            )
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use crate::error::Error;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::string::String;
+use std::vec::Vec;
+
+/// a record of the fasta index (.fai) file
+#[derive(Debug, Clone)]
+struct FaiRecord {
+    /// total length of the sequence
+    length: usize,
+    /// byte offset of the first base of the sequence
+    offset: u64,
+    /// number of bases per line
+    line_bases: usize,
+    /// number of bytes per line (including the line break)
+    line_width: usize,
+}
+
+/// A samtools-faidx indexed fasta file for random access of the reference sequence
+pub struct IndexedFasta {
+    file_name: String,
+    file: File,
+    index: HashMap<String, FaiRecord>,
+}
+
+impl IndexedFasta {
+    /// Open an indexed fasta file, the index is expected to be at `<fasta_file>.fai`
+    ///
+    /// # Arguments
+    /// * `fasta_file`: the fasta file path
+    ///
+    /// Example::
+    ///
+    /// ```
+    /// use diploid_contam_estimator::fastareader::IndexedFasta;
+    /// let fasta = IndexedFasta::open("data/test.fa").unwrap();
+    /// assert_eq!(fasta.contig_length("chr_test"), Some(120));
+    /// ```
+    pub fn open(fasta_file: &str) -> Result<Self, Error> {
+        let fai_file = format!("{}.fai", fasta_file);
+        let fai = File::open(&fai_file)
+            .map(BufReader::new)
+            .map_err(|_| Error::index(format!("Missing fasta index file {}", fai_file)))?;
+        let mut index: HashMap<String, FaiRecord> = HashMap::new();
+        for (i, line) in fai.lines().enumerate() {
+            let line = line.map_err(|e| Error::io(&fai_file, e))?;
+            let malformed = || {
+                Error::index(format!("Malformed fasta index line: {}", line))
+                    .with_line(i + 1)
+                    .with_file(&fai_file)
+            };
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 5 {
+                return Err(malformed());
+            }
+            let parse = |field: &str| field.parse::<usize>().map_err(|_| malformed());
+            let record = FaiRecord {
+                length: parse(fields[1])?,
+                offset: parse(fields[2])? as u64,
+                line_bases: parse(fields[3])?,
+                line_width: parse(fields[4])?,
+            };
+            if record.line_bases == 0 || record.line_width < record.line_bases {
+                return Err(malformed());
+            }
+            index.insert(fields[0].to_string(), record);
+        }
+        let file = File::open(fasta_file).map_err(|e| Error::io(fasta_file, e))?;
+        Ok(Self {
+            file_name: fasta_file.to_string(),
+            file,
+            index,
+        })
+    }
+
+    /// the length of a sequence in the fasta file, or None if the sequence is missing
+    pub fn contig_length(&self, contig: &str) -> Option<usize> {
+        self.index.get(contig).map(|record| record.length)
+    }
+
+    /// the names of the sequences in the fasta file
+    pub fn contig_names(&self) -> Vec<String> {
+        self.index.keys().cloned().collect()
+    }
+
+    /// Fetch a (upper-cased) subsequence from the fasta file
+    ///
+    /// # Arguments
+    /// * `contig`: the sequence name
+    /// * `start`: 1-based start position (inclusive)
+    /// * `end`: 1-based end position (inclusive), clipped to the sequence length
+    ///
+    /// Example::
+    ///
+    /// ```
+    /// use diploid_contam_estimator::fastareader::IndexedFasta;
+    /// let mut fasta = IndexedFasta::open("data/test.fa").unwrap();
+    /// assert_eq!(fasta.fetch("chr_test", 1, 4).unwrap(), b"ACGT".to_vec());
+    /// ```
+    pub fn fetch(&mut self, contig: &str, start: usize, end: usize) -> Result<Vec<u8>, Error> {
+        let record = self.index.get(contig).ok_or_else(|| {
+            Error::index(format!("Sequence {} not found in the fasta file", contig))
+                .with_file(&self.file_name)
+        })?;
+        let end = end.min(record.length);
+        if start < 1 || start > end {
+            return Err(Error::parse(format!(
+                "Invalid fasta region {}:{}-{}",
+                contig, start, end
+            )));
+        }
+        let byte_offset = |i: usize| {
+            record.offset
+                + ((i / record.line_bases) * record.line_width + i % record.line_bases) as u64
+        };
+        let start_offset = byte_offset(start - 1);
+        let end_offset = byte_offset(end - 1) + 1;
+        let mut buf = vec![0; (end_offset - start_offset) as usize];
+        self.file
+            .seek(SeekFrom::Start(start_offset))
+            .and_then(|_| self.file.read_exact(&mut buf))
+            .map_err(|e| Error::io(&self.file_name, e))?;
+        Ok(buf
+            .into_iter()
+            .filter(|base| !base.is_ascii_whitespace())
+            .map(|base| base.to_ascii_uppercase())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(1, 4, "ACGT")]
+    #[case(58, 63, "GTCACG")] // across a line break
+    #[case(118, 200, "AGA")] // clipped at the end
+    fn test_fetch(#[case] start: usize, #[case] end: usize, #[case] expected: &str) {
+        let mut fasta = IndexedFasta::open("data/test.fa").unwrap();
+        let seq = fasta.fetch("chr_test", start, end).unwrap();
+        assert_eq!(String::from_utf8(seq).unwrap(), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "not found in the fasta file")]
+    fn test_fetch_missing_contig() {
+        let mut fasta = IndexedFasta::open("data/test.fa").unwrap();
+        fasta.fetch("X", 1, 10).unwrap();
+    }
+
+    #[rstest]
+    #[case("chr_test\t120\t52\t0\t1")]
+    #[case("chr_test\t120\t52\t60\t59")]
+    #[case("chr_test\t120\t52")]
+    fn test_open_malformed_index(#[case] fai_line: &str) {
+        let fasta_file = std::env::temp_dir().join(format!(
+            "diploid_contam_estimator_malformed_{}.fa",
+            fai_line.len()
+        ));
+        let fasta_file = fasta_file.to_str().unwrap();
+        std::fs::copy("data/test.fa", fasta_file).unwrap();
+        std::fs::write(format!("{}.fai", fasta_file), fai_line).unwrap();
+        let error = IndexedFasta::open(fasta_file).err().unwrap();
+        assert!(
+            error.to_string().contains("Malformed fasta index line"),
+            "{}",
+            error
+        );
+    }
+}
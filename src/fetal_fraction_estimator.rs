@@ -0,0 +1,317 @@
+use crate::contamination_estimator::{
+    calc_loglik_for_hypothetical_contam_level, mixture_hypothesis, KNOWN_SITE_ALLELE_FREQUENCY,
+};
+use crate::error::Error;
+use crate::model::{Hypothesis, VariantPosition, Zygosity};
+use lazy_static::lazy_static;
+use rayon::prelude::*;
+use std::vec::Vec;
+
+lazy_static! {
+    static ref FETAL_HYPOTHESES: [&'static str; 8] = [
+        "fetus is homozygous alt at maternal hom-alt position",
+        "fetus is heterozygous at maternal hom-alt position",
+        "fetus is heterozygous at maternal het position",
+        "fetus is homozygous alt at maternal het position",
+        "fetus is homozygous ref at maternal het position",
+        "fetal allele at maternal hom-ref position",
+        "fetus is homozygous ref at maternal hom-ref position",
+        "fetus is heterozygous at maternal hom-ref position",
+    ];
+}
+
+/// Return the best hypothesis of a maternal homozygous (alt) variant for a given fetal fraction
+///
+/// for maternal homozygous variant, the fetus can either be:
+/// 1. homozygous alt as well: we expect an alt allele frequency of 1
+/// 2. heterozygous with a paternal ref allele: we expect an alt allele frequency of 1 - f/2
+///
+/// as for the hom-ref calls, the likelihood is a mixture over both fetal genotypes weighted by
+/// [KNOWN_SITE_ALLELE_FREQUENCY], so a single ref read from sequencing error doesn't rule out
+/// the homozygous fetus
+///
+/// # Arguments
+/// * `variant_position`: the positional data of the homozygous variant
+/// * `hypothetical_fetal_fraction`: hypothetical fetal fraction
+///
+/// # Returns
+/// * the most likely fetal genotype, with the log probability of seeing the given alt depth
+///   (across both fetal genotypes)
+fn calc_loglik_for_hypothetical_fetal_fraction_homozygous(
+    variant_position: &VariantPosition,
+    hypothetical_fetal_fraction: f64,
+) -> Result<Hypothesis, Error> {
+    let fetal_hypotheses: Vec<(Hypothesis, f64)> = vec![
+        (
+            Hypothesis::new(FETAL_HYPOTHESES[0].to_string(), 1.0)?,
+            KNOWN_SITE_ALLELE_FREQUENCY,
+        ),
+        (
+            Hypothesis::new(
+                FETAL_HYPOTHESES[1].to_string(),
+                1.0 - hypothetical_fetal_fraction / 2.0,
+            )?,
+            1.0 - KNOWN_SITE_ALLELE_FREQUENCY,
+        ),
+    ];
+    mixture_hypothesis(variant_position, fetal_hypotheses)
+}
+
+/// Return the best hypothesis of a maternal heterozygous variant for a given fetal fraction
+///
+/// for heterozygous variant, it could be due to:
+/// 1. the fetus is also heterozygous: we expect an alt allele frequency of 0.5
+/// 2. the fetus is homozygous alt: we expect an alt allele frequency of 0.5 + f/2
+/// 3. the fetus is homozygous ref: we expect an alt allele frequency of 0.5 - f/2
+/// 4. the mother is homozygous ref and the fetal (paternal) alt allele is called as a low vaf het: we expect f/2
+///
+/// # Arguments
+/// * `variant_position`: the positional data of the heterozygous variant
+/// * `hypothetical_fetal_fraction`: hypothetical fetal fraction
+///
+/// # Returns
+/// * the best hypothesis with the maximum log probability of seeing the given alt depth (across all the tested hypotheses)
+fn calc_loglik_for_hypothetical_fetal_fraction_heterozygous(
+    variant_position: &VariantPosition,
+    hypothetical_fetal_fraction: f64,
+) -> Result<Hypothesis, Error> {
+    let fetal_hypotheses: Vec<Hypothesis> = vec![
+        Hypothesis::new(FETAL_HYPOTHESES[2].to_string(), 0.5)?,
+        Hypothesis::new(
+            FETAL_HYPOTHESES[3].to_string(),
+            0.5 + hypothetical_fetal_fraction / 2.0,
+        )?,
+        Hypothesis::new(
+            FETAL_HYPOTHESES[4].to_string(),
+            0.5 - hypothetical_fetal_fraction / 2.0,
+        )?,
+        Hypothesis::new(
+            FETAL_HYPOTHESES[5].to_string(),
+            hypothetical_fetal_fraction / 2.0,
+        )?,
+    ];
+    best_fetal_hypothesis(variant_position, fetal_hypotheses)
+}
+
+/// Return the best hypothesis of a maternal homozygous reference call at a known site for a given fetal fraction
+///
+/// for maternal homozygous reference call, the fetus can either be:
+/// 1. homozygous ref as well: we expect no alt allele
+/// 2. heterozygous with a paternal alt allele: we expect an alt allele frequency of f/2
+///
+/// the paternal allele is not known, so the likelihood is a mixture over both fetal genotypes,
+/// weighted by the chance of a paternal alt allele at [KNOWN_SITE_ALLELE_FREQUENCY], and hom-ref
+/// calls without alt reads are evidence against a high fetal fraction
+///
+/// # Arguments
+/// * `variant_position`: the positional data of the homozygous reference call
+/// * `hypothetical_fetal_fraction`: hypothetical fetal fraction
+///
+/// # Returns
+/// * the most likely fetal genotype, with the log probability of seeing the given alt depth
+///   (across both fetal genotypes)
+fn calc_loglik_for_hypothetical_fetal_fraction_hom_ref(
+    variant_position: &VariantPosition,
+    hypothetical_fetal_fraction: f64,
+) -> Result<Hypothesis, Error> {
+    let fetal_hypotheses: Vec<(Hypothesis, f64)> = vec![
+        (
+            Hypothesis::new(FETAL_HYPOTHESES[6].to_string(), 0.0)?,
+            1.0 - KNOWN_SITE_ALLELE_FREQUENCY,
+        ),
+        (
+            Hypothesis::new(
+                FETAL_HYPOTHESES[7].to_string(),
+                hypothetical_fetal_fraction / 2.0,
+            )?,
+            KNOWN_SITE_ALLELE_FREQUENCY,
+        ),
+    ];
+    mixture_hypothesis(variant_position, fetal_hypotheses)
+}
+
+/// Evaluate a list of hypotheses and return the one with the highest log likelihood
+fn best_fetal_hypothesis(
+    variant_position: &VariantPosition,
+    mut fetal_hypotheses: Vec<Hypothesis>,
+) -> Result<Hypothesis, Error> {
+    for fetal_hypothesis in fetal_hypotheses.iter_mut() {
+        let loglik = calc_loglik_for_hypothetical_contam_level(
+            variant_position,
+            fetal_hypothesis.variant_fraction,
+        )?;
+        fetal_hypothesis.set_loglik(loglik);
+    }
+    let best_hypothesis = fetal_hypotheses
+        .into_iter()
+        .max_by(|a, b| a.loglik.partial_cmp(&b.loglik).unwrap())
+        .ok_or_else(|| Error::model("MAX is not found in the loglik calculation"))?;
+    Ok(best_hypothesis)
+}
+
+/// Helper function to calculate the log probability of a given
+/// variant under a fetal fraction hypothesis
+///
+/// # Arguments
+///
+/// * `variant_position`: A VariantPosition object to be evaluated
+/// * `hypothetical_fetal_fraction`: hypothetical fetal fraction to test
+///
+/// # Returns
+///
+/// the best hypothesis with the highest log probability of seeing the given variant alt count
+pub fn calculate_fetal_loglik_for_variant_position(
+    variant_position: &VariantPosition,
+    hypothetical_fetal_fraction: f64,
+) -> Result<Hypothesis, Error> {
+    match variant_position.zygosity {
+        Zygosity::HOMOZYGOUS => calc_loglik_for_hypothetical_fetal_fraction_homozygous(
+            variant_position,
+            hypothetical_fetal_fraction,
+        ),
+        Zygosity::HETEROZYGOUS => calc_loglik_for_hypothetical_fetal_fraction_heterozygous(
+            variant_position,
+            hypothetical_fetal_fraction,
+        ),
+        Zygosity::HOM_REF => calc_loglik_for_hypothetical_fetal_fraction_hom_ref(
+            variant_position,
+            hypothetical_fetal_fraction,
+        ),
+    }
+}
+
+/// Given a list of variant position from a cell-free DNA sample and a hypothetical fetal fraction
+/// we calculate the log probabilty of seeing the given numbers of alt base across all positions
+///
+/// # Arguments
+///
+/// * `variant_list` - a list of VariantPosition
+/// * `hypothetical_fetal_fraction` - the hypthetical fetal fraction
+///
+/// # Returns
+///
+/// * the sum of log probabilty of seeing the given list of variants at the given fetal fraction
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::fetal_fraction_estimator::calculate_fetal_fraction_hypothesis;
+/// use diploid_contam_estimator::model::{VariantPosition, VariantType, Zygosity};
+/// let mut variant_list: Vec<VariantPosition> = vec![
+///     VariantPosition::new("X", 1, 100, 95, VariantType::SNV, Zygosity::HOMOZYGOUS).unwrap(),
+///     VariantPosition::new("X", 2, 100, 5, VariantType::SNV, Zygosity::HETEROZYGOUS).unwrap(),
+/// ];
+/// let log_prob_10: f64 = calculate_fetal_fraction_hypothesis(&mut variant_list, 0.1).unwrap();
+/// let log_prob_30: f64 = calculate_fetal_fraction_hypothesis(&mut variant_list, 0.3).unwrap();
+/// assert!(log_prob_10 > log_prob_30);
+/// ```
+pub fn calculate_fetal_fraction_hypothesis(
+    variant_list: &mut Vec<VariantPosition>,
+    hypothetical_fetal_fraction: f64,
+) -> Result<f64, Error> {
+    if !(0.0..1.0).contains(&hypothetical_fetal_fraction) {
+        return Err(Error::model("Fetal fraction must be >= 0 and < 1"));
+    }
+
+    let log_prob_sum = variant_list
+        .par_iter_mut()
+        .map(|variant_position| {
+            let hyp = calculate_fetal_loglik_for_variant_position(
+                variant_position,
+                hypothetical_fetal_fraction,
+            )?;
+            variant_position.set_contamination_label(hyp.label);
+            hyp.loglik
+                .ok_or_else(|| Error::model("loglik not calculated"))
+        })
+        .sum::<Result<f64, Error>>()?;
+    Ok(log_prob_sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::VariantType;
+    use rstest::*;
+
+    #[rstest]
+    #[case(100, 100, 0.1, Zygosity::HOMOZYGOUS, FETAL_HYPOTHESES[0].to_string())]
+    #[case(100, 95, 0.1, Zygosity::HOMOZYGOUS, FETAL_HYPOTHESES[1].to_string())]
+    #[case(100, 50, 0.1, Zygosity::HETEROZYGOUS, FETAL_HYPOTHESES[2].to_string())]
+    #[case(100, 56, 0.1, Zygosity::HETEROZYGOUS, FETAL_HYPOTHESES[3].to_string())]
+    #[case(100, 44, 0.1, Zygosity::HETEROZYGOUS, FETAL_HYPOTHESES[4].to_string())]
+    #[case(100, 5, 0.1, Zygosity::HETEROZYGOUS, FETAL_HYPOTHESES[5].to_string())]
+    #[case(100, 0, 0.1, Zygosity::HOM_REF, FETAL_HYPOTHESES[6].to_string())]
+    #[case(100, 5, 0.1, Zygosity::HOM_REF, FETAL_HYPOTHESES[7].to_string())]
+    fn test_calculate_fetal_loglik_for_variant_position(
+        #[case] total_read_depth: usize,
+        #[case] alt_depth: usize,
+        #[case] hypothetical_fetal_fraction: f64,
+        #[case] zygosity: Zygosity,
+        #[case] label: String,
+    ) {
+        let variant = VariantPosition::new(
+            "X",
+            1,
+            total_read_depth,
+            alt_depth,
+            VariantType::SNV,
+            zygosity,
+        )
+        .unwrap();
+        let hyp =
+            calculate_fetal_loglik_for_variant_position(&variant, hypothetical_fetal_fraction)
+                .unwrap();
+        assert_eq!(hyp.label, label);
+    }
+
+    #[test]
+    fn test_calculate_fetal_fraction_hypothesis_hom_ref_without_alt_reads() {
+        // maternal hom-ref calls without alt reads pull the fetal fraction estimate down
+        let best_fraction = |variant_list: &mut Vec<VariantPosition>| {
+            (0..40)
+                .map(|level| {
+                    let fraction = level as f64 / 100.0;
+                    let loglik = calculate_fetal_fraction_hypothesis(variant_list, fraction);
+                    (level, loglik.unwrap())
+                })
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .unwrap()
+                .0
+        };
+        let mut variant_list: Vec<VariantPosition> = (1..=10)
+            .map(|position| {
+                VariantPosition::new("X", position, 100, 3, VariantType::SNV, Zygosity::HOM_REF)
+                    .unwrap()
+            })
+            .collect();
+        let estimate = best_fraction(&mut variant_list);
+        variant_list.extend((11..=30).map(|position| {
+            VariantPosition::new("X", position, 100, 0, VariantType::SNV, Zygosity::HOM_REF)
+                .unwrap()
+        }));
+        assert!(best_fraction(&mut variant_list) < estimate);
+    }
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(0.1)]
+    fn test_calculate_fetal_loglik_homozygous_with_ref_read(#[case] fraction: f64) {
+        // a single ref read from sequencing error at a maternal hom-alt site is not impossible
+        let variant =
+            VariantPosition::new("X", 1, 100, 99, VariantType::SNV, Zygosity::HOMOZYGOUS).unwrap();
+        let hyp = calculate_fetal_loglik_for_variant_position(&variant, fraction).unwrap();
+        assert!(hyp.loglik.unwrap().is_finite());
+    }
+
+    #[test]
+    #[should_panic(expected = "Fetal fraction must be")]
+    fn test_calculate_fetal_fraction_hypothesis_exception() {
+        let mut variant_list =
+            vec![
+                VariantPosition::new("X", 1, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS)
+                    .unwrap(),
+            ];
+        calculate_fetal_fraction_hypothesis(&mut variant_list, 1.0).unwrap();
+    }
+}
@@ -0,0 +1,501 @@
+use crate::error::Error;
+use crate::model::StrandCounts;
+use noodles_vcf::header::format::Key;
+use noodles_vcf::header::info::Key as InfoKey;
+use noodles_vcf::record::genotypes::genotype::field::Value;
+use noodles_vcf::record::genotypes::Genotype;
+use noodles_vcf::record::info::field::Value as InfoValue;
+use noodles_vcf::record::Record;
+use std::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+
+/// Where to find the per-allele read counts of a sample in a vcf record
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlleleCountSource {
+    /// a per-allele (Number=R) FORMAT tag, e.g. AD
+    ALLELE(String),
+    /// a reference count FORMAT tag and a per-alt-allele (Number=A) FORMAT tag, e.g. freebayes RO/AO
+    REFALT(String, String),
+    /// per-allele forward and reverse strand FORMAT tags (e.g. bcftools ADF/ADR), falling back
+    /// to the INFO DP4 tag for bi-allelic sites without the FORMAT tags in a single-sample vcf
+    STRAND(String, String),
+    /// Strelka2 tier-1 counts: `{BASE}U` for SNVs and `TAR`/`TIR` for indels
+    TIER1,
+}
+
+/// Variant callers with known FORMAT tags for depth and allele counts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallerPreset {
+    GATK,
+    DEEPVARIANT,
+    FREEBAYES,
+    STRELKA2,
+    BCFTOOLS,
+}
+
+impl FromStr for CallerPreset {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gatk" => Ok(CallerPreset::GATK),
+            "deepvariant" => Ok(CallerPreset::DEEPVARIANT),
+            "freebayes" => Ok(CallerPreset::FREEBAYES),
+            "strelka2" | "strelka" => Ok(CallerPreset::STRELKA2),
+            "bcftools" => Ok(CallerPreset::BCFTOOLS),
+            _ => Err(Error::option(format!(
+                "Unknown caller preset {}, available presets: gatk,deepvariant,freebayes,strelka2,bcftools",
+                s
+            ))),
+        }
+    }
+}
+
+/// The FORMAT tags for extracting the total depth and the per-allele read counts of a sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatTags {
+    /// FORMAT tag of the total read depth
+    pub depth_tag: String,
+    /// where to find the per-allele read counts
+    pub allele_counts: AlleleCountSource,
+    /// use the sum of the allele counts instead of the depth tag as the total read depth
+    pub sum_allele_depths: bool,
+}
+
+impl Default for FormatTags {
+    fn default() -> Self {
+        FormatTags::from_preset(CallerPreset::GATK)
+    }
+}
+
+impl FormatTags {
+    /// The FORMAT tags written by a variant caller
+    ///
+    /// # Arguments
+    /// * `preset`: the variant caller
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::format_tags::{AlleleCountSource, CallerPreset, FormatTags};
+    /// let format_tags = FormatTags::from_preset(CallerPreset::FREEBAYES);
+    /// assert_eq!(
+    ///     format_tags.allele_counts,
+    ///     AlleleCountSource::REFALT("RO".to_string(), "AO".to_string())
+    /// );
+    /// ```
+    pub fn from_preset(preset: CallerPreset) -> Self {
+        let allele_counts = match preset {
+            CallerPreset::GATK | CallerPreset::DEEPVARIANT => {
+                AlleleCountSource::ALLELE("AD".to_string())
+            }
+            CallerPreset::FREEBAYES => {
+                AlleleCountSource::REFALT("RO".to_string(), "AO".to_string())
+            }
+            CallerPreset::STRELKA2 => AlleleCountSource::TIER1,
+            CallerPreset::BCFTOOLS => {
+                AlleleCountSource::STRAND("ADF".to_string(), "ADR".to_string())
+            }
+        };
+        FormatTags {
+            depth_tag: "DP".to_string(),
+            allele_counts,
+            // strelka2 DP also counts the filtered (tier-2) reads
+            sum_allele_depths: preset == CallerPreset::STRELKA2,
+        }
+    }
+
+    /// Extract the total read depth and the per-allele read counts of a sample
+    ///
+    /// # Arguments
+    /// * `record`: the vcf record
+    /// * `sample_genotype`: the genotype fields of the sample in the record
+    ///
+    /// # Returns
+    /// * total read depth and read counts for the reference and each of the alt alleles
+    pub fn read_counts(
+        &self,
+        record: &Record,
+        sample_genotype: &Genotype,
+    ) -> Result<(usize, Vec<Option<usize>>), Error> {
+        let allele_count = record.alternate_bases().len() + 1;
+        let mut allele_depths: Vec<Option<i32>> = match &self.allele_counts {
+            AlleleCountSource::ALLELE(tag) => required_values(sample_genotype, tag)?,
+            AlleleCountSource::REFALT(ref_tag, alt_tag) => {
+                let mut depths = required_values(sample_genotype, ref_tag)?;
+                depths.truncate(1);
+                depths.extend(required_values(sample_genotype, alt_tag)?);
+                depths
+            }
+            AlleleCountSource::STRAND(forward_tag, reverse_tag) => {
+                match (
+                    format_values(sample_genotype, forward_tag)?,
+                    format_values(sample_genotype, reverse_tag)?,
+                ) {
+                    (Some(forward), Some(reverse)) => forward
+                        .iter()
+                        .zip(reverse.iter())
+                        .map(|(f, r)| Some((*f)? + (*r)?))
+                        .collect(),
+                    _ => dp4_depths(record).ok_or_else(|| {
+                        Error::missing(format!(
+                            "FORMAT/{},{} or INFO/DP4 (of a bi-allelic site in a single-sample vcf)",
+                            forward_tag, reverse_tag
+                        ))
+                    })?,
+                }
+            }
+            AlleleCountSource::TIER1 => tier1_depths(record, sample_genotype)?,
+        };
+        allele_depths.resize(allele_count, None);
+        let allele_depths: Vec<Option<usize>> = allele_depths
+            .into_iter()
+            .map(|depth| depth.map(|d| d.max(0) as usize))
+            .collect();
+
+        let read_depth = match self.sum_allele_depths {
+            true => allele_depths.iter().flatten().sum(),
+            false => required_values(sample_genotype, &self.depth_tag)?
+                .first()
+                .copied()
+                .flatten()
+                .ok_or_else(|| Error::missing(format!("FORMAT/{}", self.depth_tag)))?
+                .max(0) as usize,
+        };
+        Ok((read_depth, allele_depths))
+    }
+
+    /// Extract the read depth of a gVCF reference block, which has no per-site read counts;
+    /// the minimum depth of the block (FORMAT/MIN_DP) is used, or the depth tag otherwise
+    ///
+    /// # Arguments
+    /// * `sample_genotype`: the genotype fields of the sample in the reference block
+    ///
+    /// # Returns
+    /// * the read depth of each site of the block
+    pub fn block_depth(&self, sample_genotype: &Genotype) -> Result<usize, Error> {
+        let depth = match format_values(sample_genotype, "MIN_DP")? {
+            Some(values) => values,
+            None => required_values(sample_genotype, &self.depth_tag)?,
+        };
+        depth
+            .first()
+            .copied()
+            .flatten()
+            .map(|depth| depth.max(0) as usize)
+            .ok_or_else(|| Error::missing(format!("FORMAT/MIN_DP or FORMAT/{}", self.depth_tag)))
+    }
+
+    /// Extract the ref and alt read counts on each strand of a sample, from the per-allele
+    /// strand FORMAT tags (ADF/ADR, or the tags of the bcftools preset), the GATK FORMAT/SB
+    /// tag or the INFO/DP4 tag of bi-allelic sites in a single-sample vcf, in this order
+    ///
+    /// # Arguments
+    /// * `record`: the vcf record
+    /// * `sample_genotype`: the genotype fields of the sample in the record
+    /// * `alt_allele`: the allele index of the alt allele (1 for the first alt allele)
+    ///
+    /// # Returns
+    /// * the strand counts, or None if the record has none of the tags
+    pub fn strand_counts(
+        &self,
+        record: &Record,
+        sample_genotype: &Genotype,
+        alt_allele: usize,
+    ) -> Result<Option<StrandCounts>, Error> {
+        let (forward_tag, reverse_tag) = match &self.allele_counts {
+            AlleleCountSource::STRAND(forward_tag, reverse_tag) => {
+                (forward_tag.as_str(), reverse_tag.as_str())
+            }
+            _ => ("ADF", "ADR"),
+        };
+        // ref-fwd, ref-rev, alt-fwd, alt-rev
+        let table: Option<Vec<Option<i32>>> = match (
+            format_values(sample_genotype, forward_tag)?,
+            format_values(sample_genotype, reverse_tag)?,
+        ) {
+            (Some(forward), Some(reverse)) => Some(vec![
+                forward.first().copied().flatten(),
+                reverse.first().copied().flatten(),
+                forward.get(alt_allele).copied().flatten(),
+                reverse.get(alt_allele).copied().flatten(),
+            ]),
+            // GATK SB is summed over the alt alleles
+            _ => match format_values(sample_genotype, "SB")? {
+                Some(sb) => Some(sb),
+                None => dp4_values(record).filter(|_| record.alternate_bases().len() == 1),
+            },
+        };
+        Ok(table.as_deref().and_then(strand_table_counts))
+    }
+}
+
+/// strand counts from the values of a ref-fwd, ref-rev, alt-fwd, alt-rev table, None if any
+/// value is missing
+fn strand_table_counts(table: &[Option<i32>]) -> Option<StrandCounts> {
+    let count =
+        |i: usize| -> Option<usize> { table.get(i).copied().flatten().map(|n| n.max(0) as usize) };
+    if table.len() != 4 {
+        return None;
+    }
+    Some(StrandCounts {
+        ref_forward: count(0)?,
+        ref_reverse: count(1)?,
+        alt_forward: count(2)?,
+        alt_reverse: count(3)?,
+    })
+}
+
+/// integer values of a FORMAT field, undeclared FORMAT tags are parsed as strings
+fn integer_values(value: &Value) -> Vec<Option<i32>> {
+    match value {
+        Value::Integer(n) => vec![Some(*n)],
+        Value::IntegerArray(values) => values.clone(),
+        Value::String(s) => s.split(',').map(|v| v.parse::<i32>().ok()).collect(),
+        Value::StringArray(values) => values
+            .iter()
+            .map(|v| v.as_ref().and_then(|v| v.parse::<i32>().ok()))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// integer values of a FORMAT tag of a sample, or None if the tag is absent
+fn format_values(sample_genotype: &Genotype, tag: &str) -> Result<Option<Vec<Option<i32>>>, Error> {
+    let key = tag
+        .parse::<Key>()
+        .map_err(|_| Error::parse(format!("Invalid FORMAT tag: {}", tag)))?;
+    Ok(sample_genotype
+        .get(&key)
+        .and_then(|field| field.value())
+        .map(integer_values))
+}
+
+/// integer values of a FORMAT tag of a sample, which must be present
+fn required_values(sample_genotype: &Genotype, tag: &str) -> Result<Vec<Option<i32>>, Error> {
+    format_values(sample_genotype, tag)?.ok_or_else(|| Error::missing(format!("FORMAT/{}", tag)))
+}
+
+/// the values of the INFO DP4 tag (ref-fwd, ref-rev, alt-fwd, alt-rev), or None if absent;
+/// DP4 counts the reads of all samples, so it is only used for a single-sample vcf
+fn dp4_values(record: &Record) -> Option<Vec<Option<i32>>> {
+    if record.genotypes().len() != 1 {
+        return None;
+    }
+    let key = InfoKey::from_str("DP4").ok()?;
+    match record.info().get(&key)?.value()? {
+        InfoValue::IntegerArray(values) => Some(values.clone()),
+        InfoValue::String(s) => Some(s.split(',').map(|v| v.parse::<i32>().ok()).collect()),
+        _ => None,
+    }
+}
+
+/// reference and alt read counts from the INFO DP4 tag, only for bi-allelic sites
+fn dp4_depths(record: &Record) -> Option<Vec<Option<i32>>> {
+    if record.alternate_bases().len() != 1 {
+        return None;
+    }
+    let dp4 = dp4_values(record)?;
+    if dp4.len() != 4 {
+        return None;
+    }
+    Some(vec![Some(dp4[0]? + dp4[1]?), Some(dp4[2]? + dp4[3]?)])
+}
+
+/// Strelka2 tier-1 read counts: `{BASE}U` for SNVs, `TAR` (ref) and `TIR` (indel) for indels
+fn tier1_depths(record: &Record, sample_genotype: &Genotype) -> Result<Vec<Option<i32>>, Error> {
+    let ref_base = record.reference_bases().to_string();
+    let alt_bases: Vec<String> = record
+        .alternate_bases()
+        .iter()
+        .map(|alt| alt.to_string())
+        .collect();
+    let is_snv = ref_base.len() == 1 && alt_bases.iter().all(|alt| alt.len() == 1);
+    match is_snv {
+        true => std::iter::once(&ref_base)
+            .chain(alt_bases.iter())
+            .map(|base| {
+                Ok(required_values(sample_genotype, &format!("{}U", base))?
+                    .first()
+                    .copied()
+                    .flatten())
+            })
+            .collect(),
+        false => Ok(vec![
+            required_values(sample_genotype, "TAR")?
+                .first()
+                .copied()
+                .flatten(),
+            required_values(sample_genotype, "TIR")?
+                .first()
+                .copied()
+                .flatten(),
+        ]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use noodles_vcf as vcf;
+    use rstest::*;
+
+    const HEADER: &str = "##fileformat=VCFv4.2
+##INFO=<ID=DP4,Number=4,Type=Integer,Description=\"ref-fwd, ref-rev, alt-fwd, alt-rev\">
+##FORMAT=<ID=GT,Number=1,Type=String,Description=\"Genotype\">
+##FORMAT=<ID=DP,Number=1,Type=Integer,Description=\"Read depth\">
+##FORMAT=<ID=AD,Number=R,Type=Integer,Description=\"Allele depths\">
+##FORMAT=<ID=MIN_DP,Number=1,Type=Integer,Description=\"Minimum DP observed within the GVCF block\">
+##FORMAT=<ID=RO,Number=1,Type=Integer,Description=\"Reference allele observations\">
+##FORMAT=<ID=AO,Number=A,Type=Integer,Description=\"Alternate allele observations\">
+##FORMAT=<ID=ADF,Number=R,Type=Integer,Description=\"Forward strand allele depths\">
+##FORMAT=<ID=ADR,Number=R,Type=Integer,Description=\"Reverse strand allele depths\">
+##FORMAT=<ID=SB,Number=4,Type=Integer,Description=\"Per-sample component statistics for strand bias\">
+##FORMAT=<ID=AU,Number=2,Type=Integer,Description=\"Tier1/2 A counts\">
+##FORMAT=<ID=GU,Number=2,Type=Integer,Description=\"Tier1/2 G counts\">
+##FORMAT=<ID=TAR,Number=2,Type=Integer,Description=\"Tier1/2 ref counts\">
+##FORMAT=<ID=TIR,Number=2,Type=Integer,Description=\"Tier1/2 indel counts\">
+#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tsample
+";
+
+    #[rstest]
+    #[case(CallerPreset::GATK, "X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP:AD\t0/1:60:30,20", 60, vec![Some(30), Some(20)])]
+    #[case(CallerPreset::DEEPVARIANT, "X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP:AD\t0/1:60:30,20", 60, vec![Some(30), Some(20)])]
+    #[case(CallerPreset::FREEBAYES, "X\t1\t.\tA\tG,T\t.\tPASS\t.\tGT:DP:RO:AO\t1/2:60:10:20,30", 60, vec![Some(10), Some(20), Some(30)])]
+    #[case(CallerPreset::STRELKA2, "X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP:AU:GU\t0/1:70:30,32:25,30", 55, vec![Some(30), Some(25)])]
+    #[case(CallerPreset::STRELKA2, "X\t1\t.\tA\tAG\t.\tPASS\t.\tGT:DP:TAR:TIR\t0/1:70:30,32:25,30", 55, vec![Some(30), Some(25)])]
+    #[case(CallerPreset::BCFTOOLS, "X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP:ADF:ADR\t0/1:60:10,5:20,15", 60, vec![Some(30), Some(20)])]
+    #[case(CallerPreset::BCFTOOLS, "X\t1\t.\tA\tG\t.\tPASS\tDP4=10,20,5,15\tGT:DP\t0/1:60", 60, vec![Some(30), Some(20)])]
+    fn test_read_counts(
+        #[case] preset: CallerPreset,
+        #[case] line: &str,
+        #[case] expected_depth: usize,
+        #[case] expected_allele_depths: Vec<Option<usize>>,
+    ) {
+        let header: vcf::Header = HEADER.parse().unwrap();
+        let record = Record::try_from_str(line, &header).unwrap();
+        let sample_genotype = record.genotypes().first().unwrap();
+        let (read_depth, allele_depths) = FormatTags::from_preset(preset)
+            .read_counts(&record, sample_genotype)
+            .unwrap();
+        assert_eq!(read_depth, expected_depth);
+        assert_eq!(allele_depths, expected_allele_depths);
+    }
+
+    #[rstest]
+    #[case(false, 60)]
+    #[case(true, 50)]
+    fn test_read_counts_sum_allele_depths(
+        #[case] sum_allele_depths: bool,
+        #[case] expected: usize,
+    ) {
+        let header: vcf::Header = HEADER.parse().unwrap();
+        let record =
+            Record::try_from_str("X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP:AD\t0/1:60:30,20", &header)
+                .unwrap();
+        let format_tags = FormatTags {
+            sum_allele_depths,
+            ..FormatTags::default()
+        };
+        let (read_depth, _) = format_tags
+            .read_counts(&record, record.genotypes().first().unwrap())
+            .unwrap();
+        assert_eq!(read_depth, expected);
+    }
+
+    #[rstest]
+    #[case(
+        "X\t1\t.\tA\t<NON_REF>\t.\t.\tEND=10\tGT:DP:GQ:MIN_DP\t0/0:35:60:30",
+        30
+    )]
+    #[case("X\t1\t.\tA\t<NON_REF>\t.\t.\tEND=10\tGT:DP\t0/0:35", 35)]
+    fn test_block_depth(#[case] line: &str, #[case] expected: usize) {
+        let header: vcf::Header = HEADER.parse().unwrap();
+        let record = Record::try_from_str(line, &header).unwrap();
+        let block_depth = FormatTags::default()
+            .block_depth(record.genotypes().first().unwrap())
+            .unwrap();
+        assert_eq!(block_depth, expected);
+    }
+
+    #[rstest]
+    #[case(CallerPreset::GATK, "X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP:AD:SB\t0/1:60:30,20:10,20,5,15", Some((10, 20, 5, 15)))]
+    #[case(CallerPreset::GATK, "X\t1\t.\tA\tG,T\t.\tPASS\t.\tGT:DP:AD:ADF:ADR\t1/2:60:0,30,30:0,10,20:0,20,10", Some((0, 0, 20, 10)))]
+    #[case(CallerPreset::BCFTOOLS, "X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP:ADF:ADR\t0/1:60:10,5:20,15", Some((10, 20, 5, 15)))]
+    #[case(CallerPreset::BCFTOOLS, "X\t1\t.\tA\tG\t.\tPASS\tDP4=10,20,5,15\tGT:DP\t0/1:60", Some((10, 20, 5, 15)))]
+    #[case(
+        CallerPreset::BCFTOOLS,
+        "X\t1\t.\tA\tG,T\t.\tPASS\tDP4=10,20,5,15\tGT:DP\t0/1:60",
+        None
+    )] // DP4 of a multi-allelic site
+    #[case(
+        CallerPreset::GATK,
+        "X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP:AD\t0/1:60:30,20",
+        None
+    )]
+    fn test_strand_counts(
+        #[case] preset: CallerPreset,
+        #[case] line: &str,
+        #[case] expected: Option<(usize, usize, usize, usize)>,
+    ) {
+        let header: vcf::Header = HEADER.parse().unwrap();
+        let record = Record::try_from_str(line, &header).unwrap();
+        let sample_genotype = record.genotypes().first().unwrap();
+        let alt_allele = record.alternate_bases().len();
+        let strand_counts = FormatTags::from_preset(preset)
+            .strand_counts(&record, sample_genotype, alt_allele)
+            .unwrap()
+            .map(|counts| {
+                (
+                    counts.ref_forward,
+                    counts.ref_reverse,
+                    counts.alt_forward,
+                    counts.alt_reverse,
+                )
+            });
+        assert_eq!(strand_counts, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "MISSING error: Missing FORMAT/AD")]
+    fn test_read_counts_missing_tag() {
+        let header: vcf::Header = HEADER.parse().unwrap();
+        let record =
+            Record::try_from_str("X\t1\t.\tA\tG\t.\tPASS\t.\tGT:DP\t0/1:60", &header).unwrap();
+        FormatTags::default()
+            .read_counts(&record, record.genotypes().first().unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_dp4_multi_sample() {
+        // DP4 pools the reads of both samples, so it cannot be assigned to either of them
+        let header: vcf::Header = HEADER
+            .replace("FORMAT\tsample", "FORMAT\tsample\tsample2")
+            .parse()
+            .unwrap();
+        let record = Record::try_from_str(
+            "X\t1\t.\tA\tG\t.\tPASS\tDP4=10,20,5,15\tGT:DP\t0/1:30\t0/0:30",
+            &header,
+        )
+        .unwrap();
+        let format_tags = FormatTags::from_preset(CallerPreset::BCFTOOLS);
+        for sample_genotype in record.genotypes().iter() {
+            let error = format_tags
+                .read_counts(&record, sample_genotype)
+                .unwrap_err();
+            assert!(error.to_string().contains("single-sample vcf"));
+            let strand_counts = format_tags
+                .strand_counts(&record, sample_genotype, 1)
+                .unwrap();
+            assert!(strand_counts.is_none());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown caller preset")]
+    fn test_caller_preset_exception() {
+        "varscan".parse::<CallerPreset>().unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+use crate::error::Error;
+use noodles_core::{Position, Region};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::string::String;
+use std::vec::Vec;
+
+/// A genomic interval with 1-based and inclusive start/end positions, as in vcf records
+/// and region strings
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interval {
+    /// contig name
+    pub contig: String,
+    /// 1-based start position
+    pub start: usize,
+    /// 1-based (inclusive) end position
+    pub end: usize,
+}
+
+impl Interval {
+    /// Create an interval from 1-based and inclusive start/end positions
+    pub fn new(contig: &str, start: usize, end: usize) -> Result<Self, Error> {
+        if start == 0 || end < start {
+            return Err(Error::parse(format!(
+                "Invalid interval {}:{}-{}",
+                contig, start, end
+            )));
+        }
+        Ok(Self {
+            contig: contig.to_string(),
+            start,
+            end,
+        })
+    }
+
+    /// Create an interval from 0-based, half-open bed coordinates (chromStart, chromEnd)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::interval_set::Interval;
+    /// let interval = Interval::from_bed("X", 2, 5).unwrap();
+    /// assert_eq!(interval.to_string(), "X:3-5");
+    /// ```
+    pub fn from_bed(contig: &str, chrom_start: usize, chrom_end: usize) -> Result<Self, Error> {
+        Interval::new(contig, chrom_start + 1, chrom_end)
+    }
+
+    /// Check if the interval overlaps a locus with 1-based and inclusive start/end positions
+    pub fn overlaps(&self, contig: &str, start: usize, end: usize) -> bool {
+        self.contig == contig && start <= self.end && end >= self.start
+    }
+
+    /// the interval as a noodles region, for index queries
+    pub fn to_region(&self) -> Result<Region, Error> {
+        let start = Position::try_from(self.start).map_err(|_| self.invalid_region())?;
+        let end = Position::try_from(self.end).map_err(|_| self.invalid_region())?;
+        Ok(Region::new(self.contig.as_str(), start..=end))
+    }
+
+    fn invalid_region(&self) -> Error {
+        Error::parse(format!("Invalid region {}", self))
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.contig, self.start, self.end)
+    }
+}
+
+impl FromStr for Interval {
+    type Err = Error;
+
+    /// Parse a region string (contig, contig:start or contig:start-end, 1-based and inclusive)
+    fn from_str(region_string: &str) -> Result<Self, Self::Err> {
+        let region: Region = region_string
+            .parse()
+            .map_err(|_| Error::parse(format!("Cannot parse region {}", region_string)))?;
+        let interval = region.interval();
+        Interval::new(
+            region.name(),
+            interval.start().map(usize::from).unwrap_or(1),
+            interval.end().map(usize::from).unwrap_or(usize::MAX),
+        )
+    }
+}
+
+/// A set of genomic intervals, sorted by position with overlapping and
+/// adjacent intervals merged, so that each locus is covered at most once
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    /// sorted, non-overlapping (start, end) intervals of each contig
+    contigs: BTreeMap<String, Vec<(usize, usize)>>,
+}
+
+impl IntervalSet {
+    /// Build an interval set, the intervals are sorted, deduplicated and merged
+    ///
+    /// # Arguments
+    /// * `intervals`: intervals in any order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::interval_set::{Interval, IntervalSet};
+    /// let interval_set = IntervalSet::new(vec![
+    ///     Interval::new("X", 4, 7).unwrap(),
+    ///     Interval::new("X", 3, 5).unwrap(),
+    ///     Interval::new("X", 3, 5).unwrap(),
+    /// ]);
+    /// assert_eq!(interval_set.len(), 1);
+    /// assert!(interval_set.overlaps("X", 7, 10));
+    /// assert!(!interval_set.overlaps("X", 8, 10));
+    /// ```
+    pub fn new(intervals: Vec<Interval>) -> Self {
+        let mut contigs: BTreeMap<String, Vec<(usize, usize)>> = BTreeMap::new();
+        for interval in intervals {
+            contigs
+                .entry(interval.contig)
+                .or_default()
+                .push((interval.start, interval.end));
+        }
+        for contig_intervals in contigs.values_mut() {
+            contig_intervals.sort_unstable();
+            let mut merged: Vec<(usize, usize)> = Vec::with_capacity(contig_intervals.len());
+            for &(start, end) in contig_intervals.iter() {
+                match merged.last_mut() {
+                    Some((_, last_end)) if start <= last_end.saturating_add(1) => {
+                        *last_end = end.max(*last_end)
+                    }
+                    _ => merged.push((start, end)),
+                }
+            }
+            *contig_intervals = merged;
+        }
+        Self { contigs }
+    }
+
+    /// Parse region strings (contig:start-end, 1-based and inclusive) into an interval set
+    pub fn from_regions(regions: &[&str]) -> Result<Self, Error> {
+        let intervals = regions
+            .iter()
+            .map(|region| region.parse::<Interval>())
+            .collect::<Result<Vec<Interval>, Error>>()?;
+        Ok(IntervalSet::new(intervals))
+    }
+
+    /// number of (merged) intervals
+    pub fn len(&self) -> usize {
+        self.contigs.values().map(|intervals| intervals.len()).sum()
+    }
+
+    /// true if there is no interval
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// the merged intervals, sorted by contig name and start position
+    pub fn intervals(&self) -> impl Iterator<Item = Interval> + '_ {
+        self.contigs.iter().flat_map(|(contig, intervals)| {
+            intervals.iter().map(move |&(start, end)| Interval {
+                contig: contig.to_string(),
+                start,
+                end,
+            })
+        })
+    }
+
+    /// the contig names of the intervals
+    pub fn contigs(&self) -> impl Iterator<Item = &str> + '_ {
+        self.contigs.keys().map(|contig| contig.as_str())
+    }
+
+    /// Rename the contigs of the intervals, intervals on contigs renamed to None are dropped
+    ///
+    /// # Arguments
+    /// * `rename`: the new name of a contig, or None to drop the contig
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::interval_set::IntervalSet;
+    /// let interval_set = IntervalSet::from_regions(&["chrX:3-5", "chr1:4-7", "chrY:1-2"]).unwrap();
+    /// let renamed = interval_set.rename_contigs(|contig| match contig {
+    ///     "chrY" => None,
+    ///     _ => Some(contig.trim_start_matches("chr").to_string()),
+    /// });
+    /// assert_eq!(renamed, IntervalSet::from_regions(&["X:3-5", "1:4-7"]).unwrap());
+    /// ```
+    pub fn rename_contigs<F>(&self, mut rename: F) -> Self
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let intervals = self
+            .contigs()
+            .filter_map(|contig| rename(contig).map(|renamed| (contig, renamed)))
+            .flat_map(|(contig, renamed)| {
+                self.contigs[contig]
+                    .iter()
+                    .map(move |&(start, end)| Interval {
+                        contig: renamed.clone(),
+                        start,
+                        end,
+                    })
+            })
+            .collect();
+        IntervalSet::new(intervals)
+    }
+
+    /// Check if a locus overlaps any of the intervals
+    ///
+    /// # Arguments
+    /// * `contig`: contig name of the locus
+    /// * `start`: 1-based start position of the locus
+    /// * `end`: 1-based (inclusive) end position of the locus
+    pub fn overlaps(&self, contig: &str, start: usize, end: usize) -> bool {
+        match self.contigs.get(contig) {
+            Some(intervals) => {
+                // the first interval ending at or after the start of the locus
+                let i = intervals.partition_point(|&(_, interval_end)| interval_end < start);
+                i < intervals.len() && intervals[i].0 <= end
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("X", 1, 2, false)]
+    #[case("X", 3, 3, true)]
+    #[case("X", 6, 9, true)]
+    #[case("X", 8, 9, false)]
+    #[case("X", 1, 100, true)]
+    #[case("X", 38145490, 38145490, true)]
+    #[case("X", 38145491, 38145500, false)]
+    #[case("1", 7, 7, true)]
+    #[case("2", 3, 7, false)]
+    #[case("Y", 1, 1_000_000, true)]
+    fn test_overlaps(
+        #[case] contig: &str,
+        #[case] start: usize,
+        #[case] end: usize,
+        #[case] expected: bool,
+    ) {
+        let interval_set =
+            IntervalSet::from_regions(&["X:4-7", "X:3-5", "X:38145487-38145490", "1:4-7", "Y"])
+                .unwrap();
+        assert_eq!(interval_set.overlaps(contig, start, end), expected);
+    }
+
+    #[rstest]
+    #[case(vec!["X:3-5", "X:4-7"], vec!["X:3-7"])] // overlapping
+    #[case(vec!["X:3-5", "X:6-7"], vec!["X:3-7"])] // adjacent
+    #[case(vec!["X:3-5", "X:7-9"], vec!["X:3-5", "X:7-9"])]
+    #[case(vec!["X:3-5", "X:3-5", "X:4-4"], vec!["X:3-5"])] // duplicated and contained
+    #[case(vec!["X:10-20", "1:5-6", "X:1-2"], vec!["1:5-6", "X:1-2", "X:10-20"])] // sorted
+    fn test_merge(#[case] regions: Vec<&str>, #[case] expected: Vec<&str>) {
+        let interval_set = IntervalSet::from_regions(&regions).unwrap();
+        let merged: Vec<String> = interval_set.intervals().map(|i| i.to_string()).collect();
+        assert_eq!(merged, expected);
+        assert_eq!(interval_set.len(), expected.len());
+    }
+
+    #[rstest]
+    #[case("X:3-5", Ok(("X", 3, 5)))]
+    #[case("chr1:10", Ok(("chr1", 10, usize::MAX)))]
+    #[case("X:5-3", Err(()))]
+    fn test_parse_interval(
+        #[case] region: &str,
+        #[case] expected: Result<(&str, usize, usize), ()>,
+    ) {
+        let interval = region.parse::<Interval>();
+        match expected {
+            Ok((contig, start, end)) => {
+                assert_eq!(
+                    interval.unwrap(),
+                    Interval::new(contig, start, end).unwrap()
+                )
+            }
+            Err(_) => assert!(interval.is_err()),
+        }
+    }
+}
@@ -1,83 +1,334 @@
+pub mod bcfreader;
 pub mod bedreader;
 pub mod cli;
+pub mod contaminant_identifier;
 pub mod contamination_estimator;
+pub mod contig_alias;
+pub mod dedup;
+pub mod error;
+pub mod expression;
+pub mod fastareader;
+pub mod fetal_fraction_estimator;
+pub mod format_tags;
+pub mod interval_set;
 pub mod model;
+pub mod phase_consistency;
+pub mod relatedness_estimator;
+pub mod sequence_context;
+pub mod sites_panel;
 pub mod vcfreader;
 
-use bedreader::read_bed;
+use bedreader::read_intervals;
+use contaminant_identifier::{collect_candidate_vcfs, rank_contaminant_candidates};
 use contamination_estimator::calculate_contam_hypothesis;
-use log::info;
-use model::{ContamProbResult, VariantPosition};
+use dedup::{remove_near_indels, resolve_duplicates, DedupRule};
+use error::Error;
+use fetal_fraction_estimator::calculate_fetal_fraction_hypothesis;
+use log::{info, warn};
+use model::{
+    CandidateResult, ContamProbResult, DedupCount, EstimationResult, EstimatorMode,
+    PhaseConsistencyResult, RelatednessResult, SampleVariants, Strand, StratumResult,
+    SubstitutionClass, VariantPosition,
+};
+use phase_consistency::check_phase_consistency;
+use rayon::prelude::*;
+use relatedness_estimator::estimate_relatedness;
+use sequence_context::{annotate_sequence_context, sequence_context_strata};
+use serde_json::json;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
+use std::ops::Range;
 use std::option::Option;
 use std::string::String;
 use std::vec::Vec;
-use vcfreader::build_variant_list;
+use vcfreader::{build_variant_list, build_variant_lists, VariantFilter};
 
 const MAX_CONTAM: usize = 400; // should be 0.399 because we divide 1000
+const MIN_FETAL_FRACTION: usize = 20; // 0.02
+const MAX_FETAL_FRACTION: usize = 301; // should be 0.300 because we divide 1000
 const DECIMAL_PLACE: f64 = 0.001; // how precise we want for the contamination level
 
+type HypothesisEvaluator = fn(&mut Vec<VariantPosition>, f64) -> Result<f64, Error>;
+
 /// write string to file
 ///
 /// # Arguments:
 /// * `filename`: the file name of the new file to be written to
 /// * `json_string`: String to be written to the file
-pub fn write_json(filename: &str, json_string: String) -> Result<(), String> {
-    let mut output_file = File::create(filename).map_err(|e| e.to_string())?;
-    write!(output_file, "{}", json_string).unwrap();
+pub fn write_json(filename: &str, json_string: String) -> Result<(), Error> {
+    File::create(filename)
+        .and_then(|mut output_file| write!(output_file, "{}", json_string))
+        .map_err(|e| Error::io(filename, e))?;
     info!("Written debug file at: {}", filename);
     Ok(())
 }
 
-/// the actual workflow to takes in a variant vcf file and calcualte the
-/// contamination level
+/// the name of the estimate column in the json/tsv outputs
+fn estimate_field_name(mode: EstimatorMode) -> &'static str {
+    match mode {
+        EstimatorMode::CONTAMINATION => "contamination_percentage",
+        EstimatorMode::FETAL => "fetal_fraction_percentage",
+    }
+}
+
+/// Summarize an estimation result into the json output record
 ///
 /// # Arguments:
 ///
-/// * `vcf_file`: the file path to the input vcf file for the analysis
-/// * `snv_only_flag`: boolean flag indicating whether we should only look at SNV instead of both SNV and indel
-/// * `depth_threshold`: removing all variants with read depth below this threshold
-/// * `prob_json`: for debug, a json file name for writing the contam level and the
-///              respecitive log likelihoos into ("_no_file" will turn off writing a file)
-/// * `prob_json`: for debug, a json file name for writing the list of variants that are being
-///              used for the contam level compuatation ("_no_file" will turn off writing a file)
-///
-/// # Return:
-/// * the contamination level with the highest log likelihood
+/// * `vcf_file`: the input vcf file
+/// * `mode`: the estimator mode that produced the result
+/// * `result`: the estimation result of a sample
 ///
 /// # Examples:
 ///
 /// ```
-/// use diploid_contam_estimator::run;
-/// let best_guess_contam = run("data/test.vcf", None, true, 100, Some("prob.json"), Some("variant.json")).unwrap();
-/// assert_eq!(best_guess_contam, 0.046);
+/// use diploid_contam_estimator::{run, summarize_result, RunConfig};
+/// let config = RunConfig::new("data/test.vcf");
+/// let result = run(&config).unwrap();
+/// let summary = summarize_result(&config.vcf_file, config.mode, &result);
+/// assert_eq!(summary["vcf_file"], "data/test.vcf");
 /// ```
-pub fn run(
+pub fn summarize_result(
     vcf_file: &str,
-    loci_bed: Option<&str>,
-    snv_only_flag: bool,
-    depth_threshold: usize,
-    prob_json: Option<&str>,
-    variant_json: Option<&str>,
-) -> Result<f64, String> {
-    // collect varaints
-    let regions: Vec<String> = match loci_bed {
-        Some(bed) => read_bed(bed)?,
-        _ => vec![],
+    mode: EstimatorMode,
+    result: &EstimationResult,
+) -> serde_json::Value {
+    let mut json_data = json!(
+        {
+            "vcf_file": vcf_file,
+            "sample": result.sample_name,
+        }
+    );
+    json_data[estimate_field_name(mode)] = json!(result.best_guess.contamination_level * 100.0);
+    if !result.contaminant_candidates.is_empty() {
+        json_data["contaminant_candidates"] = json!(result.contaminant_candidates);
+    }
+    if !result.strata.is_empty() {
+        json_data["strata"] = json!(result.strata);
+    }
+    if let Some(relatedness) = &result.relatedness {
+        json_data["relatedness"] = json!(relatedness);
+    }
+    json_data["filter_counts"] = json!(result.filter_counts);
+    json_data["rejection_counts"] = json!(result.rejection_counts);
+    if let Some(panel_count) = &result.panel_count {
+        json_data["panel_counts"] = json!(panel_count);
+    }
+    if let Some(dedup_count) = &result.dedup_count {
+        json_data["dedup_counts"] = json!(dedup_count);
+    }
+    if let Some(phase_consistency) = &result.phase_consistency {
+        json_data["phase_consistency"] = json!(phase_consistency);
+    }
+    json_data
+}
+
+/// Flatten the scalar fields of a json value into columns named by their dotted path
+/// (e.g. `filter_counts.PASS.accepted`), the items of a list are keyed by their 0-based index
+fn flatten_json(path: &str, value: &serde_json::Value, columns: &mut Vec<(String, String)>) {
+    let child_path = |key: &str| match path.is_empty() {
+        true => key.to_string(),
+        false => format!("{}.{}", path, key),
     };
-    let mut variant_vector: Vec<VariantPosition> =
-        build_variant_list(vcf_file, snv_only_flag, depth_threshold, regions)?;
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (key, field) in fields {
+                flatten_json(&child_path(key), field, columns);
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten_json(&child_path(&i.to_string()), item, columns);
+            }
+        }
+        serde_json::Value::String(field) => columns.push((path.to_string(), field.clone())),
+        serde_json::Value::Null => columns.push((path.to_string(), String::new())),
+        field => columns.push((path.to_string(), field.to_string())),
+    }
+}
 
+/// Write the estimation results into a tab-delimited table, one row per sample
+///
+/// The columns are the fields of the json output (see [summarize_result]), with nested fields
+/// flattened into dotted column names (e.g. `filter_counts.PASS.accepted`). The table starts with
+/// the `vcf_file`, `sample` and estimate columns, followed by the union of the other columns of
+/// all samples in the order of the json output; fields missing from a sample are left empty.
+///
+/// # Arguments:
+///
+/// * `filename`: the output tsv file name
+/// * `vcf_file`: the input vcf file
+/// * `mode`: the estimator mode that produced the results
+/// * `results`: the estimation results
+pub fn write_tsv(
+    filename: &str,
+    vcf_file: &str,
+    mode: EstimatorMode,
+    results: &[EstimationResult],
+) -> Result<(), Error> {
+    let flattened_results: Vec<Vec<(String, String)>> = results
+        .iter()
+        .map(|result| {
+            let mut columns: Vec<(String, String)> = vec![];
+            flatten_json("", &summarize_result(vcf_file, mode, result), &mut columns);
+            columns
+        })
+        .collect();
+    let mut header: Vec<String> = vec![
+        "vcf_file".to_string(),
+        "sample".to_string(),
+        estimate_field_name(mode).to_string(),
+    ];
+    for columns in flattened_results.iter() {
+        for (column, _) in columns {
+            if !header.contains(column) {
+                header.push(column.to_string());
+            }
+        }
+    }
+    let rows: Vec<HashMap<String, String>> = flattened_results
+        .into_iter()
+        .map(|columns| columns.into_iter().collect())
+        .collect();
+
+    let mut output_file = File::create(filename).map_err(|e| Error::io(filename, e))?;
+    writeln!(output_file, "{}", header.join("\t")).map_err(|e| Error::io(filename, e))?;
+    for row in rows {
+        let fields: Vec<&str> = header
+            .iter()
+            .map(|column| row.get(column).map_or("", |field| field.as_str()))
+            .collect();
+        writeln!(output_file, "{}", fields.join("\t")).map_err(|e| Error::io(filename, e))?;
+    }
+    info!("Written result tsv at: {}", filename);
+    Ok(())
+}
+
+/// options for running the workflow
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    /// the file path to the input vcf file for the analysis
+    pub vcf_file: String,
+    /// the sample (name or 0-based index) in a multi-sample vcf file, the first sample is used if not given
+    pub sample: Option<String>,
+    /// bed (or Picard interval_list/GATK intervals) file containing loci for extracting variants
+    pub loci_bed: Option<String>,
+    /// a tabix or csi index of the vcf file, otherwise `<vcf_file>.csi` or `<vcf_file>.tbi` is used if it exists
+    pub index_file: Option<String>,
+    /// the options for selecting records and variants from the vcf file
+    /// (e.g. SNV only, minimum depth, FORMAT tags and FILTER values)
+    pub variant_filter: VariantFilter,
+    /// for debug, a json file name for writing the contam level and the
+    /// respecitive log likelihoos into
+    pub prob_json: Option<String>,
+    /// for debug, a json file name for writing the list of variants that are being
+    /// used for the contam level compuatation
+    pub variant_json: Option<String>,
+    /// estimating contamination level (0-0.4) or fetal fraction (0.02-0.3) of a cell-free DNA sample
+    pub mode: EstimatorMode,
+    /// candidate contaminant genotype vcf files (or directories of them) to be ranked
+    pub candidate_vcfs: Vec<String>,
+    /// a fixed kinship coefficient between sample and contaminant for the relatedness-aware model
+    pub kinship: Option<f64>,
+    /// profile the kinship coefficient (0 - 0.25) in the relatedness-aware model
+    pub profile_kinship: bool,
+    /// only use transversion SNVs, which are not affected by deamination damage
+    pub transversion_only: bool,
+    /// report estimates stratified by substitution class, to spot DNA damage
+    pub damage_aware: bool,
+    /// a samtools-faidx indexed reference fasta file for sequence context annotation
+    pub reference_fasta: Option<String>,
+    /// removing all variants in homopolymers longer than this
+    pub max_homopolymer_length: Option<usize>,
+    /// removing all variants in short tandem repeats
+    pub exclude_str: bool,
+    /// removing all variants with flanking GC content below this
+    pub min_gc: Option<f64>,
+    /// removing all variants with flanking GC content above this
+    pub max_gc: Option<f64>,
+    /// report estimates stratified by sequence context (repeat type and GC content)
+    pub context_strata: bool,
+    /// report estimates stratified by variant type
+    pub type_strata: bool,
+    /// keep one variant per locus of overlapping variants, chosen by this rule
+    pub dedup_rule: Option<DedupRule>,
+    /// removing all variants within this distance (in bp) of an indel
+    pub indel_gap: Option<usize>,
+    /// report estimates from the read counts of each strand, contamination is expected on
+    /// both strands while artifacts are often strand-specific
+    pub strand_likelihood: bool,
+    /// test whether the allele fraction deviations of phased hets are coherent within phase
+    /// blocks, which points to allelic imbalance (e.g. LOH) rather than contamination
+    pub phase_check: bool,
+}
+
+impl RunConfig {
+    /// Create a RunConfig with default options for a given vcf file
+    ///
+    /// # Arguments
+    /// * `vcf_file`: the file path to the input vcf file for the analysis
+    ///
+    /// Example::
+    ///
+    /// ```
+    /// use diploid_contam_estimator::RunConfig;
+    /// let config = RunConfig::new("data/test.vcf");
+    /// assert_eq!(config.variant_filter.depth_threshold, 0);
+    /// ```
+    pub fn new(vcf_file: &str) -> Self {
+        Self {
+            vcf_file: vcf_file.to_string(),
+            sample: None,
+            loci_bed: None,
+            index_file: None,
+            variant_filter: VariantFilter::default(),
+            prob_json: None,
+            variant_json: None,
+            mode: EstimatorMode::CONTAMINATION,
+            candidate_vcfs: vec![],
+            kinship: None,
+            profile_kinship: false,
+            transversion_only: false,
+            damage_aware: false,
+            reference_fasta: None,
+            max_homopolymer_length: None,
+            exclude_str: false,
+            min_gc: None,
+            max_gc: None,
+            context_strata: false,
+            type_strata: false,
+            dedup_rule: None,
+            indel_gap: None,
+            strand_likelihood: false,
+            phase_check: false,
+        }
+    }
+}
+
+/// evaluate the log likelihood of the variants across a range of hypothetical
+/// contamination levels and find the one with the highest log likelihood
+///
+/// # Arguments:
+/// * `variant_vector`: the variants used for the estimation
+/// * `evaluate_hypothesis`: the function calculating the log likelihood at a given level
+/// * `levels`: the range of levels to evaluate (in unit of `DECIMAL_PLACE`)
+///
+/// # Return:
+/// * the log likelihood of every evaluated level, and the best one
+fn grid_search(
+    variant_vector: &mut Vec<VariantPosition>,
+    evaluate_hypothesis: HypothesisEvaluator,
+    levels: Range<usize>,
+) -> Result<(Vec<ContamProbResult>, ContamProbResult), Error> {
     // using variants as input to estimate contamination
-    let mut result_vector: Vec<ContamProbResult> = Vec::with_capacity(MAX_CONTAM); // initialize a result array to store all result
+    let mut result_vector: Vec<ContamProbResult> = Vec::with_capacity(levels.len()); // initialize a result array to store all result
     let mut best_guess: Option<ContamProbResult> = None;
-    let contamination_range_to_evaluate = (1..MAX_CONTAM).map(|x| x as f64 * DECIMAL_PLACE);
+    let contamination_range_to_evaluate = levels.map(|x| x as f64 * DECIMAL_PLACE);
     for hypothetical_contamination_level in contamination_range_to_evaluate {
         // loop over the hypothetical contamination level
         // and calculate the log likelihood
-        let log_prob: f64 =
-            calculate_contam_hypothesis(&mut variant_vector, hypothetical_contamination_level)?;
+        let log_prob: f64 = evaluate_hypothesis(variant_vector, hypothetical_contamination_level)?;
 
         // store them into a result object
         let output: ContamProbResult = ContamProbResult {
@@ -101,32 +352,474 @@ pub fn run(
             }
         }
     }
-    let best_guess_contam_level = best_guess
-        .ok_or("No best guess contam object")?
-        .contamination_level;
+    let best_guess = best_guess.ok_or_else(|| Error::model("No best guess contam object"))?;
+    Ok((result_vector, best_guess))
+}
+
+/// estimate the contamination level separately for each stratum of the variants
+///
+/// # Arguments:
+/// * `variant_vector`: the variants used for the estimation
+/// * `stratify`: assign a variant to the strata (if any) it belongs to
+/// * `evaluate_hypothesis`: the function calculating the log likelihood at a given level
+/// * `levels`: the range of levels to evaluate (in unit of `DECIMAL_PLACE`)
+///
+/// # Return:
+/// * the best guess for each non-empty stratum, in order of first appearance
+fn estimate_strata<F>(
+    variant_vector: &[VariantPosition],
+    stratify: F,
+    evaluate_hypothesis: HypothesisEvaluator,
+    levels: Range<usize>,
+) -> Result<Vec<StratumResult>, Error>
+where
+    F: Fn(&VariantPosition) -> Vec<String>,
+{
+    let mut strata: Vec<(String, Vec<VariantPosition>)> = vec![];
+    for variant in variant_vector {
+        for stratum in stratify(variant) {
+            match strata.iter_mut().find(|(name, _)| *name == stratum) {
+                Some((_, variants)) => variants.push(variant.clone()),
+                None => strata.push((stratum, vec![variant.clone()])),
+            }
+        }
+    }
+    strata
+        .into_iter()
+        .map(|(stratum, mut variants)| {
+            let (_, best_guess) = grid_search(&mut variants, evaluate_hypothesis, levels.clone())?;
+            Ok(StratumResult {
+                stratum,
+                variant_count: variants.len(),
+                contamination_level: best_guess.contamination_level,
+                log_likelihood: best_guess.log_likelihood,
+            })
+        })
+        .collect()
+}
+
+/// estimate the level from the read counts of each strand separately, using the variants
+/// with strand counts (see [VariantPosition::strand_variant])
+///
+/// # Arguments:
+/// * `variant_vector`: the variants used for the estimation
+/// * `evaluate_hypothesis`: the function calculating the log likelihood at a given level
+/// * `levels`: the range of levels to evaluate (in unit of `DECIMAL_PLACE`)
+///
+/// # Return:
+/// * the best guess of each strand with any variant, labelled by the strand
+fn estimate_strands(
+    variant_vector: &[VariantPosition],
+    evaluate_hypothesis: HypothesisEvaluator,
+    levels: Range<usize>,
+) -> Result<Vec<StratumResult>, Error> {
+    let mut strata: Vec<StratumResult> = vec![];
+    for strand in [Strand::FORWARD, Strand::REVERSE] {
+        let mut variants: Vec<VariantPosition> = variant_vector
+            .iter()
+            .filter_map(|variant| variant.strand_variant(strand))
+            .collect();
+        if variants.is_empty() {
+            continue;
+        }
+        let (_, best_guess) = grid_search(&mut variants, evaluate_hypothesis, levels.clone())?;
+        strata.push(StratumResult {
+            stratum: strand.to_string(),
+            variant_count: variants.len(),
+            contamination_level: best_guess.contamination_level,
+            log_likelihood: best_guess.log_likelihood,
+        });
+    }
+    Ok(strata)
+}
+
+/// add the sample name to a (debug) output file name, e.g. prob.json -> prob.sample.json
+fn per_sample_file_name(file_name: &str, sample_name: &str) -> String {
+    match file_name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}.{}.{}", stem, sample_name, extension),
+        None => format!("{}.{}", file_name, sample_name),
+    }
+}
+
+/// the actual workflow to takes in a variant vcf file and calcualte the
+/// contamination level
+///
+/// # Arguments:
+///
+/// * `config`: the input files and options for the analysis
+///
+/// # Return:
+/// * the contamination level (or fetal fraction) with the highest log likelihood,
+///   and the ranked candidate contaminants if any candidate vcf is given
+///
+/// # Examples:
+///
+/// ```
+/// use diploid_contam_estimator::{run, RunConfig};
+/// let mut config = RunConfig::new("data/test.vcf");
+/// config.variant_filter.snv_only_flag = true;
+/// config.variant_filter.depth_threshold = 100;
+/// let out_dir = std::env::temp_dir();
+/// config.prob_json = Some(out_dir.join("prob.json").to_str().unwrap().to_string());
+/// config.variant_json = Some(out_dir.join("variant.json").to_str().unwrap().to_string());
+/// let result = run(&config).unwrap();
+/// assert_eq!(result.best_guess.contamination_level, 0.046);
+/// ```
+pub fn run(config: &RunConfig) -> Result<EstimationResult, Error> {
+    // collect varaints
+    let regions = match &config.loci_bed {
+        Some(bed) => Some(read_intervals(bed)?),
+        None => None,
+    };
+    let sample_variants = build_variant_list(
+        &config.vcf_file,
+        &config.variant_filter,
+        regions.as_ref(),
+        config.index_file.as_deref(),
+        config.sample.as_deref(),
+    )?;
+    info!("Estimating from sample: {}", sample_variants.sample_name);
+    estimate(
+        config,
+        sample_variants,
+        config.prob_json.as_deref(),
+        config.variant_json.as_deref(),
+    )
+}
+
+/// the workflow to estimate every sample of a multi-sample vcf file,
+/// all samples are collected in a single pass of the vcf file and
+/// estimated in parallel
+///
+/// # Arguments:
+///
+/// * `config`: the input files and options for the analysis (`config.sample` is ignored),
+///   the sample name is added to the debug json file names
+///
+/// # Return:
+/// * the estimation result of each sample, in the order of the vcf header
+///
+/// # Examples:
+///
+/// ```
+/// use diploid_contam_estimator::{run_all_samples, RunConfig};
+/// let mut config = RunConfig::new("data/family.vcf");
+/// config.variant_filter.snv_only_flag = true;
+/// let results = run_all_samples(&config).unwrap();
+/// assert_eq!(results.len(), 2);
+/// assert_eq!(results[1].sample_name, "mother");
+/// ```
+pub fn run_all_samples(config: &RunConfig) -> Result<Vec<EstimationResult>, Error> {
+    let regions = match &config.loci_bed {
+        Some(bed) => Some(read_intervals(bed)?),
+        None => None,
+    };
+    let sample_variants = build_variant_lists(
+        &config.vcf_file,
+        &config.variant_filter,
+        regions.as_ref(),
+        config.index_file.as_deref(),
+    )?;
+    sample_variants
+        .into_par_iter()
+        .map(|sample_variants| {
+            let prob_json = config
+                .prob_json
+                .as_ref()
+                .map(|file_name| per_sample_file_name(file_name, &sample_variants.sample_name));
+            let variant_json = config
+                .variant_json
+                .as_ref()
+                .map(|file_name| per_sample_file_name(file_name, &sample_variants.sample_name));
+            estimate(
+                config,
+                sample_variants,
+                prob_json.as_deref(),
+                variant_json.as_deref(),
+            )
+        })
+        .collect()
+}
+
+/// estimate the contamination level (or fetal fraction) of a sample from its variants
+///
+/// # Arguments:
+///
+/// * `config`: the options for the analysis
+/// * `sample_variants`: the variants collected from the vcf file for the sample
+/// * `prob_json`: for debug, a json file name for writing the log likelihood of each level
+/// * `variant_json`: for debug, a json file name for writing the variants used
+fn estimate(
+    config: &RunConfig,
+    sample_variants: SampleVariants,
+    prob_json: Option<&str>,
+    variant_json: Option<&str>,
+) -> Result<EstimationResult, Error> {
+    let SampleVariants {
+        sample_name,
+        variants: mut variant_vector,
+        filter_counts,
+        mut panel_count,
+        rejection_counts,
+    } = sample_variants;
+    // the fetal fraction model shares the same variant input, but with
+    // its own set of hypotheses and a narrower range to evaluate
+    let (evaluate_hypothesis, levels): (HypothesisEvaluator, Range<usize>) = match config.mode {
+        EstimatorMode::CONTAMINATION => (calculate_contam_hypothesis, 1..MAX_CONTAM),
+        EstimatorMode::FETAL => (
+            calculate_fetal_fraction_hypothesis,
+            MIN_FETAL_FRACTION..MAX_FETAL_FRACTION,
+        ),
+    };
+
+    // overlapping variants share the same reads, and reads around indels are prone to misalignment
+    let mut dedup_count: Option<DedupCount> = None;
+    if config.dedup_rule.is_some() || config.indel_gap.is_some() {
+        let mut count = DedupCount::default();
+        if let Some(rule) = config.dedup_rule {
+            count.duplicates = resolve_duplicates(&mut variant_vector, rule);
+        }
+        if let Some(gap) = config.indel_gap {
+            count.near_indel = remove_near_indels(&mut variant_vector, gap);
+        }
+        info!(
+            "Removed {} duplicated variants and {} variants near indels, using {} variants",
+            count.duplicates,
+            count.near_indel,
+            variant_vector.len()
+        );
+        dedup_count = Some(count);
+    }
+
+    // sequence context from the reference, indel allele balance is skewed in
+    // homopolymers and short tandem repeats
+    if let Some(reference_fasta) = &config.reference_fasta {
+        annotate_sequence_context(
+            &mut variant_vector,
+            reference_fasta,
+            &config.variant_filter.contig_aliases,
+        )?;
+        let context_filters = config.max_homopolymer_length.is_some()
+            || config.exclude_str
+            || config.min_gc.is_some()
+            || config.max_gc.is_some();
+        let unannotated = variant_vector
+            .iter()
+            .filter(|variant| variant.sequence_context.is_none())
+            .count();
+        if context_filters && unannotated > 0 {
+            warn!(
+                "Removing {} variants on sequences missing from {}, which cannot pass the sequence context filters",
+                unannotated, reference_fasta
+            );
+        }
+        variant_vector.retain(|variant| match &variant.sequence_context {
+            None => !context_filters,
+            Some(context) => {
+                config
+                    .max_homopolymer_length
+                    .is_none_or(|max_length| context.homopolymer_length <= max_length)
+                    && !(config.exclude_str && context.str_period.is_some())
+                    && config
+                        .min_gc
+                        .is_none_or(|min_gc| context.gc_content >= min_gc)
+                    && config
+                        .max_gc
+                        .is_none_or(|max_gc| context.gc_content <= max_gc)
+            }
+        });
+        info!(
+            "Using {} variants after sequence context filters",
+            variant_vector.len()
+        );
+    } else if config.context_strata
+        || config.max_homopolymer_length.is_some()
+        || config.exclude_str
+        || config.min_gc.is_some()
+        || config.max_gc.is_some()
+    {
+        return Err(Error::option(
+            "Sequence context options require a reference fasta file",
+        ));
+    }
+
+    // transversions are not affected by deamination damage (C>T/G>A)
+    if config.transversion_only {
+        variant_vector.retain(|variant| {
+            variant.substitution_class() == Some(SubstitutionClass::TRANSVERSION)
+        });
+        info!(
+            "Using {} transversion variants for estimation",
+            variant_vector.len()
+        );
+    }
+
+    if let Some(panel_count) = panel_count.as_mut() {
+        panel_count.used = variant_vector.len();
+        info!(
+            "Using {} of {} panel sites found in the vcf file",
+            panel_count.used, panel_count.found
+        );
+    }
+
+    // allelic imbalance shifts the same haplotype across a phase block, contamination does not
+    let mut phase_consistency: Option<PhaseConsistencyResult> = None;
+    if config.phase_check {
+        let phase_result = check_phase_consistency(&variant_vector)?;
+        info!(
+            "{} of {} tested phase blocks ({} phased hets) show a coherent haplotype imbalance",
+            phase_result.imbalanced_blocks.len(),
+            phase_result.tested_blocks,
+            phase_result.phased_hets
+        );
+        if phase_result.imbalanced_hets > 0 {
+            warn!(
+                "{} phased hets are in phase blocks with allelic imbalance (e.g. LOH), which can be mistaken for contamination",
+                phase_result.imbalanced_hets
+            );
+        }
+        phase_consistency = Some(phase_result);
+    }
+
+    // using variants as input to estimate contamination
+    let (result_vector, best_guess) =
+        grid_search(&mut variant_vector, evaluate_hypothesis, levels.clone())?;
+    let best_guess_contam_level = best_guess.contamination_level;
+
+    // looking for the contaminant from the candidate samples
+    let mut contaminant_candidates: Vec<CandidateResult> = vec![];
+    if !config.candidate_vcfs.is_empty() {
+        if config.mode != EstimatorMode::CONTAMINATION {
+            return Err(Error::option(
+                "Candidate contaminants can only be ranked in contamination mode",
+            ));
+        }
+        let candidate_vcfs = collect_candidate_vcfs(&config.candidate_vcfs)?;
+        let contamination_levels: Vec<f64> = result_vector
+            .iter()
+            .map(|result| result.contamination_level)
+            .collect();
+        contaminant_candidates = rank_contaminant_candidates(
+            &variant_vector,
+            &candidate_vcfs,
+            &contamination_levels,
+            &config.variant_filter.contig_aliases,
+        )?;
+        if let Some(top_candidate) = contaminant_candidates.first() {
+            info!(
+                "Most likely contaminant: {} of {} (contamination level: {}, likelihood gain: {})",
+                top_candidate.sample,
+                top_candidate.candidate,
+                top_candidate.contamination_level,
+                top_candidate.likelihood_gain
+            );
+        }
+    }
+
+    // damage shows up as a lower estimate from transversions than transitions
+    let mut strata: Vec<StratumResult> = vec![];
+    if config.damage_aware {
+        strata = estimate_strata(
+            &variant_vector,
+            |variant| match variant.substitution_class() {
+                Some(SubstitutionClass::TRANSITION) if variant.is_deamination_like() => {
+                    vec!["transition".to_string(), "C>T/G>A".to_string()]
+                }
+                Some(SubstitutionClass::TRANSITION) => vec!["transition".to_string()],
+                Some(SubstitutionClass::TRANSVERSION) => vec!["transversion".to_string()],
+                None => vec![],
+            },
+            evaluate_hypothesis,
+            levels.clone(),
+        )?;
+    }
+    if config.context_strata {
+        strata.append(&mut estimate_strata(
+            &variant_vector,
+            sequence_context_strata,
+            evaluate_hypothesis,
+            levels.clone(),
+        )?);
+    }
+    if config.type_strata {
+        strata.append(&mut estimate_strata(
+            &variant_vector,
+            |variant| vec![variant.variant_type.to_string()],
+            evaluate_hypothesis,
+            levels.clone(),
+        )?);
+    }
+    if config.strand_likelihood {
+        strata.append(&mut estimate_strands(
+            &variant_vector,
+            evaluate_hypothesis,
+            levels.clone(),
+        )?);
+    }
+    for stratum in strata.iter() {
+        info!(
+            "{} ({} variants): {}",
+            stratum.stratum, stratum.variant_count, stratum.contamination_level
+        );
+    }
+
+    // relatedness-aware contaminant model
+    let mut relatedness: Option<RelatednessResult> = None;
+    if config.kinship.is_some() || config.profile_kinship {
+        if config.mode != EstimatorMode::CONTAMINATION {
+            return Err(Error::option(
+                "Relatedness can only be modeled in contamination mode",
+            ));
+        }
+        let contamination_levels: Vec<f64> = result_vector
+            .iter()
+            .map(|result| result.contamination_level)
+            .collect();
+        let kinship = match config.profile_kinship {
+            true => None,
+            false => config.kinship,
+        };
+        let relatedness_result =
+            estimate_relatedness(&variant_vector, &contamination_levels, kinship)?;
+        info!(
+            "Relatedness-aware model: kinship {}, contamination level {} (related contaminant: {})",
+            relatedness_result.kinship,
+            relatedness_result.contamination_level,
+            relatedness_result.related_contaminant
+        );
+        relatedness = Some(relatedness_result);
+    }
 
     // just writing out the result/intermediate files
-    if prob_json.is_some() {
+    if let Some(prob_json) = prob_json {
         // write result json file
-        let json_string =
-            serde_json::to_string_pretty(&result_vector).map_err(|e| e.to_string())?;
-        write_json(prob_json.ok_or("No prob json name found")?, json_string)?
+        let json_string = serde_json::to_string_pretty(&result_vector)
+            .map_err(|e| Error::io(prob_json, e.into()))?;
+        write_json(prob_json, json_string)?
     }
 
-    if variant_json.is_some() {
+    if let Some(variant_json) = variant_json {
         // recalculate loglik
         info!("Adding labels to the variant data json");
-        calculate_contam_hypothesis(&mut variant_vector, best_guess_contam_level)?;
+        evaluate_hypothesis(&mut variant_vector, best_guess_contam_level)?;
         // write variant json file
-        let json_string =
-            serde_json::to_string_pretty(&variant_vector).map_err(|e| e.to_string())?;
-        write_json(
-            variant_json.ok_or("No variant json name found")?,
-            json_string,
-        )?
+        let json_string = serde_json::to_string_pretty(&variant_vector)
+            .map_err(|e| Error::io(variant_json, e.into()))?;
+        write_json(variant_json, json_string)?
     }
 
-    Ok(best_guess_contam_level)
+    Ok(EstimationResult {
+        sample_name,
+        best_guess,
+        contaminant_candidates,
+        relatedness,
+        strata,
+        filter_counts,
+        panel_count,
+        rejection_counts,
+        dedup_count,
+        phase_consistency,
+    })
 }
 
 #[cfg(test)]
@@ -137,13 +830,22 @@ mod tests {
     use serde_json::Value;
     use std::io::Read;
 
+    /// path of a test output file in the temporary directory
+    fn temp_file(file_name: &str) -> String {
+        std::env::temp_dir()
+            .join(file_name)
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
     #[rstest]
     #[case(
         false,
         true,
         1000,
-        Some("prob.json"),
-        Some("variants.json"),
+        Some("run.prob.json"),
+        Some("run.variants.json"),
         0.046,
         None
     )]
@@ -153,6 +855,7 @@ mod tests {
     #[case(false, false, 1100, None, None, 0.399, None)]
     #[case(false, true, 1100, None, None, 0.043, None)]
     #[case(true, true, 200, None, None, 0.001, Some("data/test.bed"))] // fetch region from bed
+    #[case(false, true, 200, None, None, 0.001, Some("data/test.bed"))] // stream region from bed
     #[case(true, true, 200, None, None, 0.046, None)] // fetch region from bed
     fn test_run(
         #[case] gz_input: bool,
@@ -169,42 +872,325 @@ mod tests {
             false => "data/test.vcf",
             true => "data/test.vcf.gz",
         };
-        let best_guess_contam_level: f64 = run(
-            vcf_file,
-            bed_file,
-            snv_only_flag,
-            depth_threshold,
-            prob_json,
-            variant_json,
-        )
-        .unwrap();
-        assert_approx_eq!(best_guess_contam_level, expected_out);
+        let mut config = RunConfig::new(vcf_file);
+        config.loci_bed = bed_file.map(String::from);
+        config.variant_filter.snv_only_flag = snv_only_flag;
+        config.variant_filter.depth_threshold = depth_threshold;
+        config.prob_json = prob_json.map(temp_file);
+        config.variant_json = variant_json.map(temp_file);
+        let result = run(&config).unwrap();
+        assert_approx_eq!(result.best_guess.contamination_level, expected_out);
+    }
+
+    #[rstest]
+    #[case("data/test.vcf", true, 100, 0.086)]
+    #[case("data/cfdna.vcf", false, 100, 0.1)] // simulated with a fetal fraction of 10%
+    #[case("data/cfdna.vcf", true, 100, 0.1)]
+    fn test_run_fetal_fraction(
+        #[case] vcf_file: &str,
+        #[case] snv_only_flag: bool,
+        #[case] depth_threshold: usize,
+        #[case] expected_out: f64,
+    ) {
+        let mut config = RunConfig::new(vcf_file);
+        config.variant_filter.snv_only_flag = snv_only_flag;
+        config.variant_filter.depth_threshold = depth_threshold;
+        config.mode = EstimatorMode::FETAL;
+        let result = run(&config).unwrap();
+        assert_approx_eq!(result.best_guess.contamination_level, expected_out, 0.002);
+    }
+
+    #[test]
+    fn test_run_with_candidates() {
+        // simulated with 10% contamination from the contaminant sample
+        let mut config = RunConfig::new("data/mixture.vcf");
+        config.candidate_vcfs = vec![
+            "data/mixture_candidates.vcf".to_string(),
+            "data/candidates".to_string(),
+        ];
+        let result = run(&config).unwrap();
+        assert_eq!(result.contaminant_candidates.len(), 5);
+        let top_candidate = &result.contaminant_candidates[0];
+        assert_eq!(top_candidate.sample, "contaminant");
+        assert_approx_eq!(top_candidate.contamination_level, 0.098);
+        assert!(top_candidate.likelihood_gain > 0.0);
+        for candidate in result.contaminant_candidates[1..].iter() {
+            match candidate.candidate.starts_with("data/candidates") {
+                // no called genotype at the sites of the sample, so no evidence either way
+                true => assert_approx_eq!(candidate.likelihood_gain, 0.0),
+                false => assert!(candidate.likelihood_gain < 0.0),
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(Some(0.0), false, 0.0, false)]
+    #[case(Some(0.25), false, 0.25, false)] // the fixed kinship is kept
+    #[case(None, true, 0.0, false)]
+    fn test_run_with_kinship(
+        #[case] kinship: Option<f64>,
+        #[case] profile_kinship: bool,
+        #[case] expected_kinship: f64,
+        #[case] related_contaminant: bool,
+    ) {
+        let mut config = RunConfig::new("data/test.vcf");
+        config.variant_filter.snv_only_flag = true;
+        config.kinship = kinship;
+        config.profile_kinship = profile_kinship;
+        let relatedness = run(&config).unwrap().relatedness.unwrap();
+        assert_approx_eq!(relatedness.kinship, expected_kinship);
+        assert_eq!(relatedness.related_contaminant, related_contaminant);
+    }
+
+    #[rstest]
+    #[case(None, "proband", 0.046)]
+    #[case(Some("mother"), "mother", 0.2)] // mother has VAF of 0.3 at all het sites
+    fn test_run_multi_sample(
+        #[case] sample: Option<&str>,
+        #[case] expected_sample_name: &str,
+        #[case] expected_out: f64,
+    ) {
+        let mut config = RunConfig::new("data/family.vcf");
+        config.variant_filter.snv_only_flag = true;
+        config.sample = sample.map(String::from);
+        let result = run(&config).unwrap();
+        assert_eq!(result.sample_name, expected_sample_name);
+        assert_approx_eq!(result.best_guess.contamination_level, expected_out);
+    }
+
+    #[rstest]
+    #[case(Some("all_samples.prob.json"), Some("all_samples.variants.json"))]
+    #[case(None, None)]
+    fn test_run_all_samples(#[case] prob_json: Option<&str>, #[case] variant_json: Option<&str>) {
+        let mut config = RunConfig::new("data/family.vcf");
+        config.variant_filter.snv_only_flag = true;
+        config.prob_json = prob_json.map(temp_file);
+        config.variant_json = variant_json.map(temp_file);
+        let results = run_all_samples(&config).unwrap();
+        let sample_results: Vec<(&str, f64)> = results
+            .iter()
+            .map(|result| {
+                (
+                    result.sample_name.as_str(),
+                    result.best_guess.contamination_level,
+                )
+            })
+            .collect();
+        assert_eq!(sample_results, vec![("proband", 0.046), ("mother", 0.2)]);
+    }
+
+    #[rstest]
+    #[case("prob.json", "prob.mother.json")]
+    #[case("prob", "prob.mother")]
+    fn test_per_sample_file_name(#[case] file_name: &str, #[case] expected: &str) {
+        assert_eq!(per_sample_file_name(file_name, "mother"), expected);
+    }
+
+    #[test]
+    fn test_run_damage_aware() {
+        let mut config = RunConfig::new("data/test.vcf");
+        config.damage_aware = true;
+        let result = run(&config).unwrap();
+        let strata: Vec<(&str, usize)> = result
+            .strata
+            .iter()
+            .map(|stratum| (stratum.stratum.as_str(), stratum.variant_count))
+            .collect();
+        assert_eq!(
+            strata,
+            vec![("transition", 5), ("transversion", 2), ("C>T/G>A", 2)]
+        );
+    }
+
+    #[rstest]
+    #[case(None, false, 4)]
+    #[case(Some(4), false, 3)]
+    #[case(None, true, 3)]
+    #[case(Some(4), true, 2)]
+    fn test_run_context_filters(
+        #[case] max_homopolymer_length: Option<usize>,
+        #[case] exclude_str: bool,
+        #[case] expected_variants: usize,
+    ) {
+        let mut config = RunConfig::new("data/context.vcf");
+        config.reference_fasta = Some("data/test.fa".to_string());
+        config.max_homopolymer_length = max_homopolymer_length;
+        config.exclude_str = exclude_str;
+        config.context_strata = true;
+        let result = run(&config).unwrap();
+        let variant_count: usize = result
+            .strata
+            .iter()
+            .filter(|stratum| stratum.stratum.starts_with("GC"))
+            .map(|stratum| stratum.variant_count)
+            .sum();
+        assert_eq!(variant_count, expected_variants);
+    }
+
+    #[rstest]
+    #[case(None, 3)] // only stratified, the variant on the missing sequence is kept
+    #[case(Some(1.0), 2)]
+    fn test_run_context_filters_missing_sequence(
+        #[case] max_gc: Option<f64>,
+        #[case] expected_snvs: usize,
+    ) {
+        let mut config = RunConfig::new("data/context.vcf");
+        config.reference_fasta = Some("data/test.fa".to_string());
+        config.max_gc = max_gc;
+        config.type_strata = true;
+        let result = run(&config).unwrap();
+        let snv_stratum = result
+            .strata
+            .iter()
+            .find(|stratum| stratum.stratum == "SNV")
+            .unwrap();
+        assert_eq!(snv_stratum.variant_count, expected_snvs);
     }
 
     #[test]
-    #[should_panic(expected = "Fetching bed loci from non bgzipped")]
-    fn test_workflow_exception() {
-        run(
-            "data/test.vcf",
-            Some("data/test.bed"),
-            true,
-            100,
-            None,
-            None,
-        )
-        .unwrap();
+    #[should_panic(expected = "require a reference fasta")]
+    fn test_run_context_filters_exception() {
+        let mut config = RunConfig::new("data/context.vcf");
+        config.exclude_str = true;
+        run(&config).unwrap();
+    }
+
+    #[rstest]
+    #[case(false, 0.046)]
+    #[case(true, 0.039)]
+    fn test_run_transversion_only(#[case] transversion_only: bool, #[case] expected_out: f64) {
+        let mut config = RunConfig::new("data/test.vcf");
+        config.variant_filter.snv_only_flag = true;
+        config.transversion_only = transversion_only;
+        let result = run(&config).unwrap();
+        assert_approx_eq!(result.best_guess.contamination_level, expected_out);
+    }
+
+    #[test]
+    fn test_run_type_strata() {
+        let mut config = RunConfig::new("data/test.vcf");
+        config.type_strata = true;
+        let result = run(&config).unwrap();
+        let strata: Vec<(&str, usize)> = result
+            .strata
+            .iter()
+            .map(|stratum| (stratum.stratum.as_str(), stratum.variant_count))
+            .collect();
+        assert_eq!(strata, vec![("indel", 7), ("SNV", 7)]);
+    }
+
+    #[rstest]
+    #[case(None, None, None)]
+    #[case(Some(DedupRule::DEPTH), None, Some((2, 0)))]
+    #[case(Some(DedupRule::QUAL), Some(5), Some((2, 0)))]
+    #[case(None, Some(5), Some((0, 1)))]
+    #[case(Some(DedupRule::DEPTH), Some(100), Some((2, 2)))]
+    fn test_run_dedup(
+        #[case] dedup_rule: Option<DedupRule>,
+        #[case] indel_gap: Option<usize>,
+        #[case] expected: Option<(usize, usize)>,
+    ) {
+        let mut config = RunConfig::new("data/duplicates.vcf");
+        config.dedup_rule = dedup_rule;
+        config.indel_gap = indel_gap;
+        let result = run(&config).unwrap();
+        let dedup_count = result
+            .dedup_count
+            .map(|count| (count.duplicates, count.near_indel));
+        assert_eq!(dedup_count, expected);
+        let summary = summarize_result(&config.vcf_file, config.mode, &result);
+        assert_eq!(summary.get("dedup_counts").is_some(), expected.is_some());
+    }
+
+    #[test]
+    fn test_run_strand_likelihood() {
+        let mut config = RunConfig::new("data/strand.vcf");
+        config.strand_likelihood = true;
+        let result = run(&config).unwrap();
+        let strata: Vec<(&str, usize)> = result
+            .strata
+            .iter()
+            .map(|stratum| (stratum.stratum.as_str(), stratum.variant_count))
+            .collect();
+        // the record at 200 has no reverse strand alt read, the record at 600 has no ADF/ADR
+        assert_eq!(strata, vec![("forward strand", 5), ("reverse strand", 5)]);
+    }
+
+    #[test]
+    fn test_run_phase_check() {
+        let mut config = RunConfig::new("data/phased.vcf");
+        config.phase_check = true;
+        let result = run(&config).unwrap();
+        let phase_consistency = result.phase_consistency.as_ref().unwrap();
+        assert_eq!(phase_consistency.phased_hets, 13);
+        assert_eq!(phase_consistency.tested_blocks, 2);
+        // the first block is LOH-like, the second block is balanced
+        assert_eq!(phase_consistency.imbalanced_hets, 8);
+        assert_eq!(phase_consistency.imbalanced_blocks[0].phase_set, Some(100));
+        let summary = summarize_result(&config.vcf_file, config.mode, &result);
+        assert_eq!(summary["phase_consistency"]["tested_blocks"], 2);
     }
 
     #[test]
     fn test_write_json() {
         let json_string = "{\"data/test.vcf\":0.046 }";
-        write_json("test.json", json_string.to_string()).unwrap();
+        let json_file = temp_file("write_json.json");
+        write_json(&json_file, json_string.to_string()).unwrap();
 
-        let mut file = File::open("test.json").unwrap();
+        let mut file = File::open(&json_file).unwrap();
         let mut data = String::new();
         file.read_to_string(&mut data).unwrap();
 
         let json_data: Value = serde_json::from_str(&data).expect("Bad json data?");
         assert_eq!(json_data["data/test.vcf"], 0.046);
     }
+
+    #[test]
+    fn test_write_tsv() {
+        let mut config = RunConfig::new("data/family.vcf");
+        config.damage_aware = true;
+        config.dedup_rule = Some(DedupRule::DEPTH);
+        let results = run_all_samples(&config).unwrap();
+        let tsv_file = temp_file("write_tsv.tsv");
+        write_tsv(&tsv_file, &config.vcf_file, config.mode, &results).unwrap();
+
+        let mut file = File::open(&tsv_file).unwrap();
+        let mut data = String::new();
+        file.read_to_string(&mut data).unwrap();
+        let lines: Vec<Vec<&str>> = data
+            .lines()
+            .map(|line| line.split('\t').collect())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        let header = &lines[0];
+        assert_eq!(
+            header[..3],
+            ["vcf_file", "sample", "contamination_percentage"]
+        );
+        // every row has the same scalar fields as the json output
+        for (row, result) in lines[1..].iter().zip(results.iter()) {
+            assert_eq!(row.len(), header.len());
+            let fields: HashMap<&str, &str> =
+                header.iter().copied().zip(row.iter().copied()).collect();
+            let summary = summarize_result(&config.vcf_file, config.mode, result);
+            assert_eq!(fields["sample"], result.sample_name);
+            assert_eq!(
+                fields["contamination_percentage"],
+                summary["contamination_percentage"].to_string()
+            );
+            assert_eq!(
+                fields["filter_counts.PASS.accepted"],
+                summary["filter_counts"]["PASS"]["accepted"].to_string()
+            );
+            assert_eq!(
+                fields["rejection_counts.no_call"],
+                summary["rejection_counts"]["no_call"].to_string()
+            );
+            assert_eq!(
+                fields["dedup_counts.duplicates"],
+                summary["dedup_counts"]["duplicates"].to_string()
+            );
+            assert_eq!(fields["strata.0.stratum"], summary["strata"][0]["stratum"]);
+        }
+    }
 }
@@ -1,50 +1,180 @@
+use diploid_contam_estimator::bedreader::read_intervals;
 use diploid_contam_estimator::cli::parse_args;
-use diploid_contam_estimator::{run, write_json};
+use diploid_contam_estimator::contig_alias::ContigAliases;
+use diploid_contam_estimator::error::Error;
+use diploid_contam_estimator::format_tags::{AlleleCountSource, FormatTags};
+use diploid_contam_estimator::model::{EstimatorMode, VariantType};
+use diploid_contam_estimator::sites_panel::SitesPanel;
+use diploid_contam_estimator::{
+    run, run_all_samples, summarize_result, write_json, write_tsv, RunConfig,
+};
 use log::info;
 use serde_json::json;
+use std::str::FromStr;
 
-pub fn wrapper() -> Result<i8, String> {
+/// parse the value of a numeric option
+fn parse_value<T: FromStr>(option: &str, value: &str) -> Result<T, Error> {
+    value
+        .parse::<T>()
+        .map_err(|_| Error::parse(format!("Invalid value {} for --{}", value, option)))
+}
+
+pub fn wrapper() -> Result<i8, Error> {
     let args = parse_args();
     let vcf_file: &str = args.value_of::<&str>("in_vcf").unwrap();
-    let prob_json: Option<&str> = args.value_of::<&str>("debug_json");
     let out_json: Option<&str> = args.value_of::<&str>("out_json");
-    let variant_json: Option<&str> = args.value_of::<&str>("debug_variant_json");
-    let loci_bed: Option<&str> = args.value_of::<&str>("loci_bed");
-    let depth_threshold: usize = args
-        .value_of::<&str>("depth_threshold")
-        .unwrap_or("0")
-        .to_string()
-        .parse::<usize>()
-        .unwrap();
-    let snv_only_flag: bool = args.is_present("snv_only");
-
-    let best_guess_contam_level: f64 = run(
-        vcf_file,
-        loci_bed,
-        snv_only_flag,
-        depth_threshold,
-        prob_json,
-        variant_json,
+    let mut config = RunConfig::new(vcf_file);
+    config.sample = args.value_of::<&str>("sample").map(String::from);
+    config.prob_json = args.value_of::<&str>("debug_json").map(String::from);
+    config.variant_json = args
+        .value_of::<&str>("debug_variant_json")
+        .map(String::from);
+    config.loci_bed = args.value_of::<&str>("loci_bed").map(String::from);
+    config.index_file = args.value_of::<&str>("index_file").map(String::from);
+    config.variant_filter.depth_threshold = parse_value(
+        "min-depth",
+        args.value_of::<&str>("depth_threshold").unwrap_or("0"),
     )?;
-    info!(
-        "Maximum likelihood contamination level: {}",
-        best_guess_contam_level
-    );
+    config.variant_filter.snv_only_flag = args.is_present("snv_only");
+    if let Some(values) = args.values_of::<&str>("include_types") {
+        config.variant_filter.variant_types = values
+            .map(|value| value.parse())
+            .collect::<Result<Vec<VariantType>, Error>>()?;
+    }
+    if let Some(values) = args.values_of::<&str>("exclude_types") {
+        let excluded_types = values
+            .map(|value| value.parse())
+            .collect::<Result<Vec<VariantType>, Error>>()?;
+        config
+            .variant_filter
+            .variant_types
+            .retain(|variant_type| !excluded_types.contains(variant_type));
+    }
+    config.variant_filter.allowed_filters = args
+        .values_of::<&str>("allow_filters")
+        .map(|values| values.map(String::from).collect())
+        .unwrap_or_default();
+    config.variant_filter.denied_filters = args
+        .values_of::<&str>("deny_filters")
+        .map(|values| values.map(String::from).collect())
+        .unwrap_or_default();
+    config.mode = match args.is_present("fetal_fraction") {
+        true => EstimatorMode::FETAL,
+        false => EstimatorMode::CONTAMINATION,
+    };
+    config.candidate_vcfs = args
+        .values_of::<&str>("candidate_vcfs")
+        .map(|values| values.map(String::from).collect())
+        .unwrap_or_default();
+    config.kinship = args
+        .value_of::<&str>("kinship")
+        .map(|kinship| parse_value("kinship", kinship))
+        .transpose()?;
+    config.profile_kinship = args.is_present("profile_kinship");
+    config.transversion_only = args.is_present("transversion_only");
+    config.damage_aware = args.is_present("damage_aware");
+    config.reference_fasta = args.value_of::<&str>("reference").map(String::from);
+    config.max_homopolymer_length = args
+        .value_of::<&str>("max_homopolymer")
+        .map(|length| parse_value("max-homopolymer", length))
+        .transpose()?;
+    config.exclude_str = args.is_present("exclude_str");
+    config.min_gc = args
+        .value_of::<&str>("min_gc")
+        .map(|gc| parse_value("min-gc", gc))
+        .transpose()?;
+    config.max_gc = args
+        .value_of::<&str>("max_gc")
+        .map(|gc| parse_value("max-gc", gc))
+        .transpose()?;
+    config.context_strata = args.is_present("context_strata");
+    config.type_strata = args.is_present("type_strata");
+    config.variant_filter.max_fisher_strand = args
+        .value_of::<&str>("max_fisher_strand")
+        .map(|fs| parse_value("max-fs", fs))
+        .transpose()?;
+    config.variant_filter.max_strand_odds_ratio = args
+        .value_of::<&str>("max_strand_odds_ratio")
+        .map(|sor| parse_value("max-sor", sor))
+        .transpose()?;
+    config.strand_likelihood = args.is_present("strand_likelihood");
+    config.phase_check = args.is_present("phase_check");
+    config.dedup_rule = args
+        .value_of::<&str>("dedup")
+        .map(|rule| rule.parse())
+        .transpose()?;
+    config.indel_gap = args
+        .value_of::<&str>("indel_gap")
+        .map(|gap| parse_value("indel-gap", gap))
+        .transpose()?;
+    config.variant_filter.include = args
+        .value_of::<&str>("include")
+        .map(|expression| expression.parse())
+        .transpose()?;
+    config.variant_filter.exclude = args
+        .value_of::<&str>("exclude")
+        .map(|expression| expression.parse())
+        .transpose()?;
+    config.variant_filter.exclude_regions = args
+        .value_of::<&str>("exclude_bed")
+        .map(read_intervals)
+        .transpose()?;
+    config.variant_filter.lenient = args.is_present("lenient");
+    config.variant_filter.sites_panel = args
+        .value_of::<&str>("sites_panel")
+        .map(SitesPanel::from_file)
+        .transpose()?;
+    config.variant_filter.reference_block_depth = args.is_present("reference_block_depth");
+    if let Some(alias_file) = args.value_of::<&str>("contig_aliases") {
+        config.variant_filter.contig_aliases = ContigAliases::from_file(alias_file)?;
+    }
+    config.variant_filter.format_tags =
+        FormatTags::from_preset(args.value_of::<&str>("caller").unwrap_or("gatk").parse()?);
+    if let Some(depth_tag) = args.value_of::<&str>("depth_tag") {
+        config.variant_filter.format_tags.depth_tag = depth_tag.to_string();
+    }
+    if let Some(allele_depth_tag) = args.value_of::<&str>("allele_depth_tag") {
+        config.variant_filter.format_tags.allele_counts =
+            AlleleCountSource::ALLELE(allele_depth_tag.to_string());
+    }
+    config.variant_filter.format_tags.sum_allele_depths |= args.is_present("sum_allele_depth");
 
-    if out_json.is_some() {
-        let out_json_file = out_json.ok_or("JSON filename is not given")?;
-        let json_data = json!(
-            {
-                "vcf_file": vcf_file,
-                "contamination_percentage": best_guess_contam_level * 100.0,
-            }
+    let estimate_name = match config.mode {
+        EstimatorMode::CONTAMINATION => "contamination level",
+        EstimatorMode::FETAL => "fetal fraction",
+    };
+    let results = match args.is_present("all_samples") {
+        true => run_all_samples(&config)?,
+        false => vec![run(&config)?],
+    };
+    for result in results.iter() {
+        info!(
+            "Maximum likelihood {} of {}: {}",
+            estimate_name, result.sample_name, result.best_guess.contamination_level
         );
+    }
+
+    if let Some(out_json_file) = out_json {
+        let mut summaries: Vec<serde_json::Value> = results
+            .iter()
+            .map(|result| summarize_result(vcf_file, config.mode, result))
+            .collect();
+        let json_data = match args.is_present("all_samples") {
+            true => json!(summaries),
+            false => summaries
+                .pop()
+                .ok_or_else(|| Error::model("No estimation result"))?,
+        };
         write_json(
             out_json_file,
-            serde_json::to_string_pretty(&json_data).map_err(|e| e.to_string())?,
+            serde_json::to_string_pretty(&json_data)
+                .map_err(|e| Error::io(out_json_file, e.into()))?,
         )?;
         info!("Written result json at: {}", out_json_file);
     }
+    if let Some(out_tsv) = args.value_of::<&str>("out_tsv") {
+        write_tsv(out_tsv, vcf_file, config.mode, &results)?;
+    }
     Ok(0)
 }
 
@@ -53,6 +183,7 @@ fn main() {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     let result = wrapper();
     if let Err(e) = result {
-        println!("Error: {}", e)
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
     }
 }
@@ -1,24 +1,202 @@
 /// Defining models for the code
 ///
+use crate::error::Error;
 use serde::{Deserialize, Serialize};
+use statrs::function::factorial::ln_factorial;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
 use std::string::String;
 
-#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Hash, Serialize, Deserialize)]
 /// variant types in the vcf file
 pub enum VariantType {
     /// a single nucleotide variant
     SNV,
     /// insertion or deletion
     INDEL,
+    /// a multi-nucleotide variant, ref and alt alleles of the same length differing at
+    /// more than one base
+    MNV,
+    /// ref and alt alleles of different lengths that are not a simple insertion or deletion
+    COMPLEX,
+    /// a symbolic (e.g. `<DEL>`), breakend or spanning deletion (`*`) alt allele
+    SYMBOLIC,
 }
 
-#[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
+impl VariantType {
+    /// all variant types
+    pub const ALL: [VariantType; 5] = [
+        VariantType::SNV,
+        VariantType::INDEL,
+        VariantType::MNV,
+        VariantType::COMPLEX,
+        VariantType::SYMBOLIC,
+    ];
+
+    /// Classify a variant by its ref and alt alleles, the bases shared by both alleles at
+    /// either end (e.g. the padding base of an indel) are ignored
+    ///
+    /// # Arguments
+    /// * `ref_allele`: the reference allele
+    /// * `alt_allele`: the alt allele
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::VariantType;
+    /// assert_eq!(VariantType::classify("A", "G"), VariantType::SNV);
+    /// assert_eq!(VariantType::classify("CT", "CA"), VariantType::SNV);
+    /// assert_eq!(VariantType::classify("AC", "GT"), VariantType::MNV);
+    /// assert_eq!(VariantType::classify("AT", "A"), VariantType::INDEL);
+    /// assert_eq!(VariantType::classify("ACG", "TT"), VariantType::COMPLEX);
+    /// assert_eq!(VariantType::classify("A", "<DEL>"), VariantType::SYMBOLIC);
+    /// ```
+    pub fn classify(ref_allele: &str, alt_allele: &str) -> Self {
+        if is_symbolic_allele(alt_allele) {
+            return VariantType::SYMBOLIC;
+        }
+        let (ref_bases, alt_bases) = trim_shared_bases(ref_allele, alt_allele);
+        let (ref_length, alt_length) = (ref_bases.len(), alt_bases.len());
+        if ref_length == alt_length {
+            match ref_length > 1 {
+                true => VariantType::MNV,
+                false => VariantType::SNV,
+            }
+        } else if ref_length == 0 || alt_length == 0 {
+            VariantType::INDEL
+        } else {
+            VariantType::COMPLEX
+        }
+    }
+}
+
+/// The (upper-cased) ref and alt alleles without the bases shared by both alleles at either end,
+/// e.g. `CT>CA` is the substitution `T>A`
+fn trim_shared_bases(ref_allele: &str, alt_allele: &str) -> (String, String) {
+    let ref_bases = ref_allele.to_ascii_uppercase();
+    let alt_bases = alt_allele.to_ascii_uppercase();
+    let prefix_length = ref_bases
+        .bytes()
+        .zip(alt_bases.bytes())
+        .take_while(|(r, a)| r == a)
+        .count();
+    let (ref_bases, alt_bases) = (&ref_bases[prefix_length..], &alt_bases[prefix_length..]);
+    let suffix_length = ref_bases
+        .bytes()
+        .rev()
+        .zip(alt_bases.bytes().rev())
+        .take_while(|(r, a)| r == a)
+        .count();
+    (
+        ref_bases[..ref_bases.len() - suffix_length].to_string(),
+        alt_bases[..alt_bases.len() - suffix_length].to_string(),
+    )
+}
+
+/// whether an alt allele is a symbolic allele (`<DEL>`), a breakend (`G]17:198982]`, `.A`)
+/// or a spanning deletion (`*`)
+fn is_symbolic_allele(alt_allele: &str) -> bool {
+    alt_allele.starts_with('<')
+        || alt_allele == "*"
+        || alt_allele.contains(['[', ']'])
+        || (alt_allele.len() > 1 && (alt_allele.starts_with('.') || alt_allele.ends_with('.')))
+}
+
+impl fmt::Display for VariantType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            VariantType::SNV => "SNV",
+            VariantType::INDEL => "indel",
+            VariantType::MNV => "MNV",
+            VariantType::COMPLEX => "complex",
+            VariantType::SYMBOLIC => "symbolic",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+impl FromStr for VariantType {
+    type Err = Error;
+
+    /// Parse a variant type, case insensitive (e.g. `snv`, `indel`, `mnv`, `complex`, `symbolic`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VariantType::ALL
+            .into_iter()
+            .find(|variant_type| variant_type.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                Error::option(format!(
+                    "Unknown variant type {}, available types: snv,indel,mnv,complex,symbolic",
+                    s
+                ))
+            })
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+/// substitution class of a single nucleotide variant
+pub enum SubstitutionClass {
+    /// purine to purine (A<->G) or pyrimidine to pyrimidine (C<->T)
+    TRANSITION,
+    /// purine to pyrimidine or vice versa
+    TRANSVERSION,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+/// the strand of the reads supporting an allele
+pub enum Strand {
+    FORWARD,
+    REVERSE,
+}
+
+impl fmt::Display for Strand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strand::FORWARD => write!(f, "forward strand"),
+            Strand::REVERSE => write!(f, "reverse strand"),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
 /// zygostiy of a variant
 pub enum Zygosity {
     /// a homozygous variant
     HOMOZYGOUS,
     /// a heterozygous variant
     HETEROZYGOUS,
+    /// a homozygous reference call at a known polymorphic site (e.g. from a gVCF),
+    /// the alt allele is the known alt allele of the site
+    HOM_REF,
+}
+
+impl Zygosity {
+    /// the expected alt allele fraction of an uncontaminated sample with this zygosity
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::Zygosity;
+    /// assert_eq!(Zygosity::HETEROZYGOUS.alt_fraction(), 0.5);
+    /// assert_eq!(Zygosity::HOM_REF.alt_fraction(), 0.0);
+    /// ```
+    pub fn alt_fraction(&self) -> f64 {
+        match self {
+            Zygosity::HOMOZYGOUS => 1.0,
+            Zygosity::HETEROZYGOUS => 0.5,
+            Zygosity::HOM_REF => 0.0,
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Serialize, Deserialize)]
+/// what fraction are we estimating from the variant calls
+pub enum EstimatorMode {
+    /// contamination level of a (supposedly) single-sample diploid library
+    CONTAMINATION,
+    /// fetal fraction of a maternal cell-free DNA sample
+    FETAL,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -48,7 +226,7 @@ impl Hypothesis {
     ///     0.1,
     /// ).unwrap();
     /// ```
-    pub fn new(label: String, variant_fraction: f64) -> Result<Hypothesis, String> {
+    pub fn new(label: String, variant_fraction: f64) -> Result<Hypothesis, Error> {
         if (0.0..=1.0).contains(&variant_fraction) {
             Ok(Self {
                 label,
@@ -56,7 +234,7 @@ impl Hypothesis {
                 loglik: None,
             })
         } else {
-            Err("variant_fraction must be between 0 and 1".to_string())
+            Err(Error::model("variant_fraction must be between 0 and 1"))
         }
     }
 
@@ -90,7 +268,281 @@ pub struct ContamProbResult {
     pub log_likelihood: f64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A struct to hold the fit of a candidate contaminant sample
+pub struct CandidateResult {
+    /// the candidate genotype vcf file
+    pub candidate: String,
+    /// the candidate sample in the genotype vcf file
+    pub sample: String,
+    /// the maximum likelihood contamination level with the candidate genotypes fixed
+    pub contamination_level: f64,
+    /// the log likelihood at the maximum likelihood contamination level
+    pub log_likelihood: f64,
+    /// log likelihood difference between the candidate model and the anonymous contaminant model
+    pub likelihood_gain: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+/// A struct to hold the fit of the relatedness-aware contaminant model
+pub struct RelatednessResult {
+    /// the (fixed or maximum likelihood) kinship coefficient between sample and contaminant
+    pub kinship: f64,
+    /// the maximum likelihood contamination level at the kinship coefficient
+    pub contamination_level: f64,
+    /// the log likelihood at the kinship coefficient and contamination level
+    pub log_likelihood: f64,
+    /// the maximum likelihood contamination level assuming an unrelated contaminant
+    pub unrelated_contamination_level: f64,
+    /// the log likelihood assuming an unrelated contaminant
+    pub unrelated_log_likelihood: f64,
+    /// whether the data significantly favor a related contaminant
+    pub related_contaminant: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A struct to hold the result of a workflow run
+pub struct EstimationResult {
+    /// the name of the sample being evaluated
+    pub sample_name: String,
+    /// the estimated fraction (contamination level or fetal fraction)
+    pub best_guess: ContamProbResult,
+    /// candidate contaminants ranked by likelihood gain, if candidates were given
+    pub contaminant_candidates: Vec<CandidateResult>,
+    /// the relatedness-aware contaminant model, if requested
+    pub relatedness: Option<RelatednessResult>,
+    /// estimates stratified by variant features, if requested
+    pub strata: Vec<StratumResult>,
+    /// how many vcf records were accepted or rejected for each FILTER value, counting the records
+    /// within the regions (if given) before the excluded regions and the known-sites panel
+    pub filter_counts: BTreeMap<String, FilterCount>,
+    /// how many known sites were found and used, if a known-sites panel was given
+    pub panel_count: Option<PanelCount>,
+    /// how many records or sample calls were skipped for each reason
+    pub rejection_counts: RejectionCounts,
+    /// how many variants were removed by the deduplication step, if it was requested
+    pub dedup_count: Option<DedupCount>,
+    /// the phase-block consistency diagnostic, if requested
+    pub phase_consistency: Option<PhaseConsistencyResult>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// A struct to hold how many sites of a known-sites panel were found and used
+pub struct PanelCount {
+    /// number of sites in the panel
+    pub panel_sites: usize,
+    /// number of panel sites (with matching alleles) found in the vcf file
+    pub found: usize,
+    /// number of panel sites used for the estimation after all filters
+    pub used: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// A struct to hold how many records or sample calls were skipped for each reason
+pub struct RejectionCounts {
+    /// sample calls without a genotype (e.g. `./.`)
+    pub no_call: usize,
+    /// sample calls without the allele read counts (e.g. AD)
+    pub missing_allele_depth: usize,
+    /// sample calls with a total read depth (DP) lower than the sum of the allele read counts
+    pub depth_below_allele_depths: usize,
+    /// records or sample calls that cannot be parsed or evaluated
+    pub malformed: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// A struct to hold how many variants were removed by the deduplication step
+pub struct DedupCount {
+    /// variants dropped because another variant at an overlapping locus was kept
+    pub duplicates: usize,
+    /// variants dropped because they are too close to an indel
+    pub near_indel: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// A struct to hold how many vcf records with a FILTER value were collected or rejected
+pub struct FilterCount {
+    /// number of records accepted by the FILTER options
+    pub accepted: usize,
+    /// number of records rejected by the FILTER options
+    pub rejected: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A struct to hold the variants collected for a sample from a vcf file
+pub struct SampleVariants {
+    /// the name of the sample
+    pub sample_name: String,
+    /// the variants of the sample that passed all filters
+    pub variants: Vec<VariantPosition>,
+    /// how many records were accepted or rejected for each FILTER value, counting the records
+    /// within the regions (if given) before the excluded regions and the known-sites panel
+    pub filter_counts: BTreeMap<String, FilterCount>,
+    /// how many known sites were found and collected, if a known-sites panel was given
+    pub panel_count: Option<PanelCount>,
+    /// how many records or sample calls were skipped for each reason
+    pub rejection_counts: RejectionCounts,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A struct to hold the estimate from a subset of the variants
+pub struct StratumResult {
+    /// the label of the subset of variants (e.g. transition)
+    pub stratum: String,
+    /// how many variants are in the subset
+    pub variant_count: usize,
+    /// the maximum likelihood level using only the subset of variants
+    pub contamination_level: f64,
+    /// the log likelihood at the maximum likelihood level
+    pub log_likelihood: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// A struct to hold the haplotype balance of the heterozygous calls in a phase block
+pub struct PhaseBlockResult {
+    /// the contig of the phase block
+    pub contig: String,
+    /// the phase set (FORMAT/PS) of the block, None for phased calls without PS
+    pub phase_set: Option<i32>,
+    /// the position of the first phased heterozygous call in the block
+    pub start: usize,
+    /// the position of the last phased heterozygous call in the block
+    pub end: usize,
+    /// number of phased heterozygous calls in the block
+    pub het_count: usize,
+    /// fraction of the reads supporting the first haplotype (the first allele of GT)
+    pub haplotype_fraction: f64,
+    /// how coherently the allele fractions deviate towards the same haplotype, from 0
+    /// (deviations cancel out) to 1 (every call deviates towards the same haplotype)
+    pub coherence: f64,
+    /// two-sided sign test p-value of the calls deviating towards the first haplotype
+    pub p_value: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// A struct to hold the phase-block consistency diagnostic of a sample
+pub struct PhaseConsistencyResult {
+    /// number of phased heterozygous calls
+    pub phased_hets: usize,
+    /// number of phase blocks with enough phased heterozygous calls to be tested
+    pub tested_blocks: usize,
+    /// the phase blocks with a coherent haplotype imbalance (e.g. LOH)
+    pub imbalanced_blocks: Vec<PhaseBlockResult>,
+    /// number of phased heterozygous calls in the imbalanced blocks
+    pub imbalanced_hets: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+/// reference sequence context around a variant
+pub struct SequenceContext {
+    /// length of the longest homopolymer run at the variant position
+    pub homopolymer_length: usize,
+    /// period of the short tandem repeat (2-6bp) at the variant position, if any
+    pub str_period: Option<usize>,
+    /// length of the short tandem repeat tract
+    pub str_length: usize,
+    /// GC content of the flanking sequence
+    pub gc_content: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// read counts of the ref and alt alleles on each strand (e.g. from ADF/ADR or SB)
+pub struct StrandCounts {
+    /// reads supporting the ref allele on the forward strand
+    pub ref_forward: usize,
+    /// reads supporting the ref allele on the reverse strand
+    pub ref_reverse: usize,
+    /// reads supporting the alt allele on the forward strand
+    pub alt_forward: usize,
+    /// reads supporting the alt allele on the reverse strand
+    pub alt_reverse: usize,
+}
+
+impl StrandCounts {
+    /// The ref plus alt read depth and the alt read depth on a strand
+    pub fn strand_depths(&self, strand: Strand) -> (usize, usize) {
+        match strand {
+            Strand::FORWARD => (self.ref_forward + self.alt_forward, self.alt_forward),
+            Strand::REVERSE => (self.ref_reverse + self.alt_reverse, self.alt_reverse),
+        }
+    }
+
+    /// Phred-scaled p-value of the two-sided Fisher's exact test of the ref/alt by
+    /// forward/reverse table (as the FS annotation of GATK), higher is more strand biased
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::StrandCounts;
+    /// let balanced = StrandCounts { ref_forward: 20, ref_reverse: 20, alt_forward: 10, alt_reverse: 10 };
+    /// assert!(balanced.fisher_strand() < 0.001);
+    /// let biased = StrandCounts { ref_forward: 20, ref_reverse: 20, alt_forward: 20, alt_reverse: 0 };
+    /// assert!(biased.fisher_strand() > 30.0);
+    /// ```
+    pub fn fisher_strand(&self) -> f64 {
+        let (ref_forward, ref_reverse) = (self.ref_forward as u64, self.ref_reverse as u64);
+        let (alt_forward, alt_reverse) = (self.alt_forward as u64, self.alt_reverse as u64);
+        let ref_total = ref_forward + ref_reverse;
+        let forward_total = ref_forward + alt_forward;
+        let total = ref_total + alt_forward + alt_reverse;
+        // log probability of the table with the same margins and `x` ref forward reads
+        let table_ln_pmf = |x: u64| -> f64 {
+            ln_factorial(ref_total)
+                + ln_factorial(total - ref_total)
+                + ln_factorial(forward_total)
+                + ln_factorial(total - forward_total)
+                - ln_factorial(total)
+                - ln_factorial(x)
+                - ln_factorial(ref_total - x)
+                - ln_factorial(forward_total - x)
+                - ln_factorial(total + x - ref_total - forward_total)
+        };
+        let observed = table_ln_pmf(ref_forward);
+        let p_value: f64 = ((ref_total + forward_total).saturating_sub(total)
+            ..=ref_total.min(forward_total))
+            .map(table_ln_pmf)
+            .filter(|ln_pmf| *ln_pmf <= observed + 1e-7)
+            .map(f64::exp)
+            .sum();
+        (-10.0 * p_value.clamp(f64::MIN_POSITIVE, 1.0).log10()).max(0.0)
+    }
+
+    /// Symmetric odds ratio of the ref/alt by forward/reverse table (as the SOR annotation
+    /// of GATK, with a pseudocount of 1), higher is more strand biased
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::StrandCounts;
+    /// let balanced = StrandCounts { ref_forward: 20, ref_reverse: 20, alt_forward: 10, alt_reverse: 10 };
+    /// let biased = StrandCounts { ref_forward: 20, ref_reverse: 20, alt_forward: 20, alt_reverse: 0 };
+    /// assert!(balanced.strand_odds_ratio() < 1.0);
+    /// assert!(biased.strand_odds_ratio() > 3.0);
+    /// ```
+    pub fn strand_odds_ratio(&self) -> f64 {
+        let ref_forward = self.ref_forward as f64 + 1.0;
+        let ref_reverse = self.ref_reverse as f64 + 1.0;
+        let alt_forward = self.alt_forward as f64 + 1.0;
+        let alt_reverse = self.alt_reverse as f64 + 1.0;
+        let ratio = (ref_forward * alt_reverse) / (ref_reverse * alt_forward);
+        let symmetrical_ratio = ratio + 1.0 / ratio;
+        let ref_ratio = ref_forward.min(ref_reverse) / ref_forward.max(ref_reverse);
+        let alt_ratio = alt_forward.min(alt_reverse) / alt_forward.max(alt_reverse);
+        symmetrical_ratio.ln() + ref_ratio.ln() - alt_ratio.ln()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+/// the phase of a heterozygous call in a phased vcf
+pub struct Phase {
+    /// the phase set (FORMAT/PS) of the call, phased calls without PS are in one phase set
+    /// per contig
+    pub phase_set: Option<i32>,
+    /// the haplotype carrying the alt allele (0 for the first allele of GT, 1 for the second)
+    pub alt_haplotype: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 /// data structure for  a variant position
 pub struct VariantPosition {
     /// contig name for where the variant is located at
@@ -107,6 +559,19 @@ pub struct VariantPosition {
     pub zygosity: Zygosity,
     /// the best hypothesis of the contamination source
     pub contamination_label: Option<String>,
+    /// the reference allele
+    pub ref_allele: Option<String>,
+    /// the alternate allele carried by the sample
+    pub alt_allele: Option<String>,
+    /// the reference sequence context around the variant
+    pub sequence_context: Option<SequenceContext>,
+    /// the QUAL of the vcf record
+    pub quality: Option<f64>,
+    /// the ref and alt read counts on each strand, if the vcf record has them (not collected
+    /// for hom-ref calls at panel sites)
+    pub strand_counts: Option<StrandCounts>,
+    /// the phase of a phased heterozygous (ref/alt) call
+    pub phase: Option<Phase>,
 }
 
 impl VariantPosition {
@@ -135,10 +600,13 @@ impl VariantPosition {
         alt_depth: usize,
         variant_type: VariantType,
         zygosity: Zygosity,
-    ) -> Result<VariantPosition, &str> {
+    ) -> Result<VariantPosition, Error> {
         if total_read_depth < alt_depth || total_read_depth < 1 {
             // validation of the input
-            return Err("Total read depth should be >= alt depth and positive");
+            return Err(
+                Error::parse("Total read depth should be >= alt depth and positive")
+                    .with_record(&format!("{}:{}", contig, position)),
+            );
         }
         Ok(Self {
             contig: contig.to_string(),
@@ -148,17 +616,161 @@ impl VariantPosition {
             variant_type,
             zygosity,
             contamination_label: None,
+            ref_allele: None,
+            alt_allele: None,
+            sequence_context: None,
+            quality: None,
+            strand_counts: None,
+            phase: None,
         })
     }
 
     pub fn set_contamination_label(&mut self, contamination_label: String) {
         self.contamination_label = Some(contamination_label);
     }
+
+    /// Adding the ref and alt alleles of the variant
+    ///
+    /// Example::
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::{VariantPosition, Zygosity, VariantType};
+    /// let mut variant = VariantPosition::new(
+    ///     "chr1", 1, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS
+    /// ).unwrap();
+    /// variant.set_alleles("A", "G");
+    /// assert_eq!(variant.alt_allele.unwrap(), "G");
+    /// ```
+    pub fn set_alleles(&mut self, ref_allele: &str, alt_allele: &str) {
+        self.ref_allele = Some(ref_allele.to_string());
+        self.alt_allele = Some(alt_allele.to_string());
+    }
+
+    pub fn set_quality(&mut self, quality: f64) {
+        self.quality = Some(quality);
+    }
+
+    pub fn set_strand_counts(&mut self, strand_counts: StrandCounts) {
+        self.strand_counts = Some(strand_counts);
+    }
+
+    pub fn set_phase(&mut self, phase: Phase) {
+        self.phase = Some(phase);
+    }
+
+    /// The variant as seen by the reads of one strand, with the ref plus alt read depth
+    /// of the strand as the total read depth
+    ///
+    /// # Returns
+    /// * None if the strand counts are not known or there is no read on the strand
+    ///
+    /// Example::
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::{Strand, StrandCounts, VariantPosition, VariantType, Zygosity};
+    /// let mut variant = VariantPosition::new(
+    ///     "chr1", 1, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS
+    /// ).unwrap();
+    /// variant.set_strand_counts(StrandCounts { ref_forward: 20, ref_reverse: 30, alt_forward: 30, alt_reverse: 20 });
+    /// let forward = variant.strand_variant(Strand::FORWARD).unwrap();
+    /// assert_eq!((forward.total_read_depth, forward.alt_depth), (50, 30));
+    /// ```
+    pub fn strand_variant(&self, strand: Strand) -> Option<VariantPosition> {
+        let (total_read_depth, alt_depth) = self.strand_counts?.strand_depths(strand);
+        if total_read_depth == 0 {
+            return None;
+        }
+        let mut variant = self.clone();
+        variant.total_read_depth = total_read_depth;
+        variant.alt_depth = alt_depth;
+        Some(variant)
+    }
+
+    /// The last reference position covered by the ref allele, the position itself if the
+    /// alleles are not set
+    ///
+    /// Example::
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::{VariantPosition, Zygosity, VariantType};
+    /// let mut variant = VariantPosition::new(
+    ///     "chr1", 10, 100, 50, VariantType::INDEL, Zygosity::HETEROZYGOUS
+    /// ).unwrap();
+    /// variant.set_alleles("ACT", "A");
+    /// assert_eq!(variant.end_position(), 12);
+    /// ```
+    pub fn end_position(&self) -> usize {
+        let ref_length = self.ref_allele.as_ref().map_or(1, |allele| allele.len());
+        self.position + ref_length.max(1) - 1
+    }
+
+    pub fn set_sequence_context(&mut self, sequence_context: SequenceContext) {
+        self.sequence_context = Some(sequence_context);
+    }
+
+    /// the substituted bases of the variant, without the bases shared by the ref and alt
+    /// alleles at either end (see [VariantType::classify])
+    fn substituted_bases(&self) -> Option<(String, String)> {
+        Some(trim_shared_bases(
+            self.ref_allele.as_ref()?,
+            self.alt_allele.as_ref()?,
+        ))
+    }
+
+    /// Classify a single nucleotide variant as transition or transversion, the bases shared by
+    /// the ref and alt alleles at either end are ignored (e.g. `CT>CA` is a transversion)
+    ///
+    /// # Returns
+    /// * the substitution class, or None if the variant is not a single base substitution
+    ///   or the alleles are not set
+    ///
+    /// Example::
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::{SubstitutionClass, VariantPosition, Zygosity, VariantType};
+    /// let mut variant = VariantPosition::new(
+    ///     "chr1", 1, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS
+    /// ).unwrap();
+    /// variant.set_alleles("C", "T");
+    /// assert_eq!(variant.substitution_class(), Some(SubstitutionClass::TRANSITION));
+    /// ```
+    pub fn substitution_class(&self) -> Option<SubstitutionClass> {
+        let (ref_allele, alt_allele) = self.substituted_bases()?;
+        match (ref_allele.as_str(), alt_allele.as_str()) {
+            ("A", "G") | ("G", "A") | ("C", "T") | ("T", "C") => {
+                Some(SubstitutionClass::TRANSITION)
+            }
+            ("A", "C")
+            | ("A", "T")
+            | ("G", "C")
+            | ("G", "T")
+            | ("C", "A")
+            | ("C", "G")
+            | ("T", "A")
+            | ("T", "G") => Some(SubstitutionClass::TRANSVERSION),
+            _ => None,
+        }
+    }
+
+    /// whether the variant looks like a cytosine deamination damage (C>T or G>A), the bases
+    /// shared by the ref and alt alleles at either end are ignored
+    pub fn is_deamination_like(&self) -> bool {
+        let (ref_allele, alt_allele) = match self.substituted_bases() {
+            Some(bases) => bases,
+            None => return false,
+        };
+        matches!(
+            (ref_allele.as_str(), alt_allele.as_str()),
+            ("C", "T") | ("G", "A")
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::*;
 
     #[test]
     #[should_panic(expected = "Total read depth should be >= alt dept")]
@@ -206,6 +818,105 @@ mod tests {
         assert_eq!(vp.contamination_label.unwrap(), contam_label);
     }
 
+    #[rstest]
+    #[case(20, 20, 10, 10, 0.0, std::f64::consts::LN_2)]
+    #[case(20, 20, 20, 0, 44.50671453211929, 6.091309882077699)]
+    #[case(30, 10, 5, 25, 56.39632430295826, 2.939357050522214)]
+    #[case(12, 8, 3, 15, 20.48961323008511, 2.802104437438311)]
+    #[case(0, 0, 0, 0, 0.0, std::f64::consts::LN_2)]
+    fn test_strand_bias(
+        #[case] ref_forward: usize,
+        #[case] ref_reverse: usize,
+        #[case] alt_forward: usize,
+        #[case] alt_reverse: usize,
+        #[case] expected_fs: f64,
+        #[case] expected_sor: f64,
+    ) {
+        let strand_counts = StrandCounts {
+            ref_forward,
+            ref_reverse,
+            alt_forward,
+            alt_reverse,
+        };
+        assert_approx_eq!(strand_counts.fisher_strand(), expected_fs, 1e-6);
+        assert_approx_eq!(strand_counts.strand_odds_ratio(), expected_sor, 1e-9);
+    }
+
+    #[rstest]
+    #[case(Strand::FORWARD, Some((20, 5)))]
+    #[case(Strand::REVERSE, None)] // no read on the reverse strand
+    fn test_strand_variant(#[case] strand: Strand, #[case] expected: Option<(usize, usize)>) {
+        let mut variant =
+            VariantPosition::new("X", 1, 20, 5, VariantType::SNV, Zygosity::HETEROZYGOUS).unwrap();
+        assert!(variant.strand_variant(strand).is_none());
+        variant.set_strand_counts(StrandCounts {
+            ref_forward: 15,
+            ref_reverse: 0,
+            alt_forward: 5,
+            alt_reverse: 0,
+        });
+        let depths = variant
+            .strand_variant(strand)
+            .map(|variant| (variant.total_read_depth, variant.alt_depth));
+        assert_eq!(depths, expected);
+    }
+
+    #[rstest]
+    #[case("A", "g", VariantType::SNV)]
+    #[case("CT", "CA", VariantType::SNV)]
+    #[case("ACT", "AGT", VariantType::SNV)]
+    #[case("AC", "GT", VariantType::MNV)]
+    #[case("ACGT", "ATGA", VariantType::MNV)]
+    #[case("CT", "C", VariantType::INDEL)]
+    #[case("C", "CTT", VariantType::INDEL)]
+    #[case("CAT", "CT", VariantType::INDEL)]
+    #[case("ACG", "TT", VariantType::COMPLEX)]
+    #[case("A", "<DEL>", VariantType::SYMBOLIC)]
+    #[case("A", "*", VariantType::SYMBOLIC)]
+    #[case("G", "G]17:198982]", VariantType::SYMBOLIC)]
+    #[case("G", ".G", VariantType::SYMBOLIC)]
+    fn test_classify(
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+        #[case] expected: VariantType,
+    ) {
+        assert_eq!(VariantType::classify(ref_allele, alt_allele), expected);
+    }
+
+    #[rstest]
+    #[case("snv", Some(VariantType::SNV))]
+    #[case("INDEL", Some(VariantType::INDEL))]
+    #[case("Complex", Some(VariantType::COMPLEX))]
+    #[case("sv", None)]
+    fn test_variant_type_from_str(#[case] value: &str, #[case] expected: Option<VariantType>) {
+        assert_eq!(value.parse::<VariantType>().ok(), expected);
+    }
+
+    #[rstest]
+    #[case("A", "G", Some(SubstitutionClass::TRANSITION), false)]
+    #[case("C", "T", Some(SubstitutionClass::TRANSITION), true)]
+    #[case("G", "A", Some(SubstitutionClass::TRANSITION), true)]
+    #[case("c", "t", Some(SubstitutionClass::TRANSITION), true)] // soft-masked
+    #[case("g", "A", Some(SubstitutionClass::TRANSITION), true)]
+    #[case("T", "A", Some(SubstitutionClass::TRANSVERSION), false)]
+    #[case("CT", "C", None, false)]
+    #[case("CT", "CA", Some(SubstitutionClass::TRANSVERSION), false)] // padded SNV
+    #[case("ACA", "ATA", Some(SubstitutionClass::TRANSITION), true)]
+    #[case("AC", "GT", None, false)] // MNV
+    fn test_substitution_class(
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+        #[case] expected: Option<SubstitutionClass>,
+        #[case] deamination_like: bool,
+    ) {
+        let mut vp =
+            VariantPosition::new("chrX", 1, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS)
+                .unwrap();
+        vp.set_alleles(ref_allele, alt_allele);
+        assert_eq!(vp.substitution_class(), expected);
+        assert_eq!(vp.is_deamination_like(), deamination_like);
+    }
+
     #[test]
     fn test_hypothesis() {
         let mut hyp = Hypothesis::new("test_hyp".to_string(), 0.1).unwrap();
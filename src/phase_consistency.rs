@@ -0,0 +1,199 @@
+use crate::error::Error;
+use crate::model::{PhaseBlockResult, PhaseConsistencyResult, VariantPosition, Zygosity};
+use statrs::distribution::{Binomial, Univariate};
+use std::collections::BTreeMap;
+use std::string::String;
+use std::vec::Vec;
+
+const MIN_BLOCK_HETS: usize = 2; // phase blocks with fewer phased hets are not tested
+const IMBALANCE_P_VALUE: f64 = 0.01; // sign test p-value for a coherent haplotype imbalance
+
+/// two-sided p-value of observing `successes` out of `trials` under Binomial(trials, 0.5)
+fn sign_test(successes: usize, trials: usize) -> Result<f64, Error> {
+    if trials == 0 {
+        return Ok(1.0);
+    }
+    let binom = Binomial::new(0.5, trials as u64).map_err(|e| Error::model(e.to_string()))?;
+    let lower = binom.cdf(successes as f64);
+    let upper = 1.0 - binom.cdf(successes as f64 - 1.0);
+    Ok((2.0 * lower.min(upper)).min(1.0))
+}
+
+/// the haplotype balance of the phased hets of a phase block
+fn block_balance(
+    contig: &str,
+    phase_set: Option<i32>,
+    variants: &[&VariantPosition],
+) -> Result<PhaseBlockResult, Error> {
+    let mut first_haplotype_reads = 0;
+    let mut total_reads = 0;
+    let mut deviations: Vec<f64> = vec![];
+    for variant in variants {
+        let alt_haplotype = variant.phase.map_or(1, |phase| phase.alt_haplotype);
+        let reads = match alt_haplotype {
+            0 => variant.alt_depth,
+            _ => variant.total_read_depth - variant.alt_depth,
+        };
+        first_haplotype_reads += reads;
+        total_reads += variant.total_read_depth;
+        deviations.push(reads as f64 / variant.total_read_depth as f64 - 0.5);
+    }
+    let absolute_deviation: f64 = deviations.iter().map(|d| d.abs()).sum();
+    let coherence = match absolute_deviation > 0.0 {
+        true => deviations.iter().sum::<f64>().abs() / absolute_deviation,
+        false => 0.0,
+    };
+    let towards_first = deviations.iter().filter(|d| **d > 0.0).count();
+    let towards_second = deviations.iter().filter(|d| **d < 0.0).count();
+    Ok(PhaseBlockResult {
+        contig: contig.to_string(),
+        phase_set,
+        start: variants.iter().map(|v| v.position).min().unwrap_or(0),
+        end: variants.iter().map(|v| v.position).max().unwrap_or(0),
+        het_count: variants.len(),
+        haplotype_fraction: first_haplotype_reads as f64 / total_reads as f64,
+        coherence,
+        p_value: sign_test(towards_first, towards_first + towards_second)?,
+    })
+}
+
+/// Test whether the allele fraction deviations of the phased heterozygous calls are coherent
+/// within each phase block. Contamination and artifacts shift the allele fraction of each het
+/// independently of the sample haplotypes, so the deviations towards either haplotype of a block
+/// cancel out. Allelic imbalance (e.g. LOH or a copy number change) depletes or enriches the same
+/// haplotype across the block, so the deviations are coherent and the hets in such blocks
+/// should not be taken as evidence of contamination.
+///
+/// # Arguments
+/// * `variants`: the variants of a sample, only the phased heterozygous calls are used
+///
+/// # Returns
+/// * the number of phased hets and tested blocks, and the blocks with a coherent haplotype
+///   imbalance (sign test p-value < 0.01)
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::model::{Phase, VariantPosition, VariantType, Zygosity};
+/// use diploid_contam_estimator::phase_consistency::check_phase_consistency;
+/// let variants: Vec<VariantPosition> = (1..=10)
+///     .map(|position| {
+///         let mut variant =
+///             VariantPosition::new("X", position, 100, 20, VariantType::SNV, Zygosity::HETEROZYGOUS)
+///                 .unwrap();
+///         // the alt allele is always on the depleted second haplotype
+///         variant.set_phase(Phase { phase_set: Some(1), alt_haplotype: 1 });
+///         variant
+///     })
+///     .collect();
+/// let result = check_phase_consistency(&variants).unwrap();
+/// assert_eq!(result.tested_blocks, 1);
+/// assert_eq!(result.imbalanced_blocks[0].haplotype_fraction, 0.8);
+/// ```
+pub fn check_phase_consistency(
+    variants: &[VariantPosition],
+) -> Result<PhaseConsistencyResult, Error> {
+    let mut blocks: BTreeMap<(String, Option<i32>), Vec<&VariantPosition>> = BTreeMap::new();
+    for variant in variants {
+        if let (Some(phase), Zygosity::HETEROZYGOUS) = (&variant.phase, &variant.zygosity) {
+            blocks
+                .entry((variant.contig.clone(), phase.phase_set))
+                .or_default()
+                .push(variant);
+        }
+    }
+    let phased_hets = blocks.values().map(|block| block.len()).sum();
+    let mut tested_blocks = 0;
+    let mut imbalanced_blocks: Vec<PhaseBlockResult> = vec![];
+    for ((contig, phase_set), block) in blocks.iter() {
+        if block.len() < MIN_BLOCK_HETS {
+            continue;
+        }
+        tested_blocks += 1;
+        let block_result = block_balance(contig, *phase_set, block)?;
+        if block_result.p_value < IMBALANCE_P_VALUE {
+            imbalanced_blocks.push(block_result);
+        }
+    }
+    let imbalanced_hets = imbalanced_blocks.iter().map(|block| block.het_count).sum();
+    Ok(PhaseConsistencyResult {
+        phased_hets,
+        tested_blocks,
+        imbalanced_blocks,
+        imbalanced_hets,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Phase, VariantType};
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::*;
+
+    /// a phased het in phase set 1 with the given alt depth (out of 100 reads)
+    fn phased_het(position: usize, alt_depth: usize, alt_haplotype: usize) -> VariantPosition {
+        let mut variant = VariantPosition::new(
+            "X",
+            position,
+            100,
+            alt_depth,
+            VariantType::SNV,
+            Zygosity::HETEROZYGOUS,
+        )
+        .unwrap();
+        variant.set_phase(Phase {
+            phase_set: Some(1),
+            alt_haplotype,
+        });
+        variant
+    }
+
+    #[rstest]
+    #[case(0, 0, 1.0)]
+    #[case(5, 10, 1.0)]
+    #[case(10, 10, 0.001953125)]
+    #[case(0, 10, 0.001953125)]
+    fn test_sign_test(#[case] successes: usize, #[case] trials: usize, #[case] expected: f64) {
+        assert_approx_eq!(sign_test(successes, trials).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_coherent_block() {
+        // LOH-like: the first haplotype is enriched whichever allele it carries
+        let variants: Vec<VariantPosition> = (0..10)
+            .map(|i| match i % 2 {
+                0 => phased_het(i + 1, 70, 0),
+                _ => phased_het(i + 1, 30, 1),
+            })
+            .collect();
+        let result = check_phase_consistency(&variants).unwrap();
+        assert_eq!(result.phased_hets, 10);
+        assert_eq!(result.imbalanced_hets, 10);
+        let block = &result.imbalanced_blocks[0];
+        assert_eq!((block.start, block.end), (1, 10));
+        assert_approx_eq!(block.haplotype_fraction, 0.7);
+        assert_approx_eq!(block.coherence, 1.0);
+    }
+
+    #[test]
+    fn test_incoherent_block() {
+        // contamination-like: low alt fractions on either haplotype
+        let variants: Vec<VariantPosition> =
+            (0..10).map(|i| phased_het(i + 1, 40, i % 2)).collect();
+        let result = check_phase_consistency(&variants).unwrap();
+        assert_eq!(result.tested_blocks, 1);
+        assert!(result.imbalanced_blocks.is_empty());
+    }
+
+    #[test]
+    fn test_unphased_and_single_het_blocks() {
+        let variants = vec![
+            phased_het(1, 50, 0),
+            VariantPosition::new("X", 2, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS)
+                .unwrap(),
+        ];
+        let result = check_phase_consistency(&variants).unwrap();
+        assert_eq!((result.phased_hets, result.tested_blocks), (1, 0));
+    }
+}
@@ -0,0 +1,313 @@
+use crate::contamination_estimator::mixture_hypothesis;
+use crate::error::Error;
+use crate::model::{ContamProbResult, Hypothesis, RelatednessResult, VariantPosition};
+use rayon::prelude::*;
+
+const MAX_KINSHIP: f64 = 0.25; // parent/offspring or sibling
+const KINSHIP_STEP: f64 = 0.025; // how precise we want for the profiled kinship
+const POPULATION_ALT_FREQUENCY: f64 = 0.5; // uninformative allele frequency for non-IBD contaminant alleles
+const RELATED_LOG_LIKELIHOOD_RATIO: f64 = 1.92; // chi-square (df=1, p=0.05) / 2
+const DOSAGE_HYPOTHESES: [&str; 3] = [
+    "contaminant is hom-ref",
+    "contaminant is het",
+    "contaminant is hom-alt",
+];
+
+/// Probability of the contaminant carrying 0, 1 or 2 alt alleles given the sample genotype
+/// and the kinship coefficient between the sample and the contaminant
+///
+/// The contaminant is assumed to share one allele identical-by-descent (IBD) with the
+/// sample with probability `4 * kinship` (exact for parent/offspring), in which case the
+/// shared allele is a random draw of the sample alleles. All non-IBD alleles are drawn from
+/// the population with an uninformative alt allele frequency of 0.5.
+///
+/// # Arguments
+/// * `sample_alt_fraction`: the alt allele fraction of the sample genotype (0 for hom-ref, 0.5 for het, 1 for hom)
+/// * `kinship`: the kinship coefficient between sample and contaminant (0 - 0.25)
+///
+/// # Returns
+/// * probability of the contaminant alt allele dosage being 0, 1 and 2
+///
+/// # Examples
+///
+/// ```
+/// use assert_approx_eq::assert_approx_eq;
+/// use diploid_contam_estimator::relatedness_estimator::contaminant_dosage_probabilities;
+/// let probs = contaminant_dosage_probabilities(1.0, 0.25).unwrap();
+/// assert_approx_eq!(probs[0], 0.0);
+/// assert_approx_eq!(probs[1], 0.5);
+/// assert_approx_eq!(probs[2], 0.5);
+/// ```
+pub fn contaminant_dosage_probabilities(
+    sample_alt_fraction: f64,
+    kinship: f64,
+) -> Result<[f64; 3], Error> {
+    if !(0.0..=MAX_KINSHIP).contains(&kinship) {
+        return Err(Error::model(format!(
+            "Kinship must be between 0 and {}",
+            MAX_KINSHIP
+        )));
+    }
+    let q = POPULATION_ALT_FREQUENCY;
+    let ibd_one = 4.0 * kinship;
+    let ibd_zero = 1.0 - ibd_one;
+    Ok([
+        ibd_zero * (1.0 - q).powi(2) + ibd_one * (1.0 - sample_alt_fraction) * (1.0 - q),
+        ibd_zero * 2.0 * q * (1.0 - q)
+            + ibd_one * (sample_alt_fraction * (1.0 - q) + (1.0 - sample_alt_fraction) * q),
+        ibd_zero * q.powi(2) + ibd_one * sample_alt_fraction * q,
+    ])
+}
+
+/// Calculate the log likelihood of a variant at a hypothetical contamination level,
+/// marginalizing over the contaminant genotypes given the kinship coefficient, with a
+/// sequencing error floor on the expected alt allele fraction of each genotype
+///
+/// # Arguments
+/// * `variant_position`: the variant to be evaluated
+/// * `hypothetical_contamination_level`: hypothetical contamination level
+/// * `kinship`: the kinship coefficient between sample and contaminant
+///
+/// # Returns
+/// * log likelihood of seeing the alt depth of the variant
+pub fn calc_loglik_for_related_contaminant(
+    variant_position: &VariantPosition,
+    hypothetical_contamination_level: f64,
+    kinship: f64,
+) -> Result<f64, Error> {
+    let sample_alt_fraction = variant_position.zygosity.alt_fraction();
+    let dosage_probabilities = contaminant_dosage_probabilities(sample_alt_fraction, kinship)?;
+    let mut dosage_hypotheses: Vec<(Hypothesis, f64)> = vec![];
+    for (dosage, dosage_probability) in dosage_probabilities.iter().enumerate() {
+        let variant_fraction = (1.0 - hypothetical_contamination_level) * sample_alt_fraction
+            + hypothetical_contamination_level * dosage as f64 / 2.0;
+        dosage_hypotheses.push((
+            Hypothesis::new(DOSAGE_HYPOTHESES[dosage].to_string(), variant_fraction)?,
+            *dosage_probability,
+        ));
+    }
+    mixture_hypothesis(variant_position, dosage_hypotheses)?
+        .loglik
+        .ok_or_else(|| Error::model("loglik not calculated"))
+}
+
+/// Given a list of variant position, a hypothetical contamination level and kinship coefficient,
+/// we calculate the log probabilty of seeing the given numbers of alt base across all positions
+///
+/// # Arguments
+/// * `variant_list` - a list of VariantPosition
+/// * `hypothetical_contamination_level` - the hypthetical contamination level
+/// * `kinship` - the kinship coefficient between sample and contaminant
+///
+/// # Returns
+/// * the sum of log probabilty of seeing the given list of variants
+pub fn calculate_related_contam_hypothesis(
+    variant_list: &[VariantPosition],
+    hypothetical_contamination_level: f64,
+    kinship: f64,
+) -> Result<f64, Error> {
+    if !(0.0..1.0).contains(&hypothetical_contamination_level) {
+        return Err(Error::model("Contamination level must be > 0 and <= 1"));
+    }
+    variant_list
+        .par_iter()
+        .map(|variant_position| {
+            calc_loglik_for_related_contaminant(
+                variant_position,
+                hypothetical_contamination_level,
+                kinship,
+            )
+        })
+        .sum::<Result<f64, Error>>()
+}
+
+/// Find the maximum likelihood contamination level for a given kinship coefficient
+fn fit_contamination_level(
+    variant_list: &[VariantPosition],
+    contamination_levels: &[f64],
+    kinship: f64,
+) -> Result<ContamProbResult, Error> {
+    let mut best_guess: Option<ContamProbResult> = None;
+    for &hypothetical_contamination_level in contamination_levels {
+        let output = ContamProbResult {
+            contamination_level: hypothetical_contamination_level,
+            log_likelihood: calculate_related_contam_hypothesis(
+                variant_list,
+                hypothetical_contamination_level,
+                kinship,
+            )?,
+        };
+        match best_guess {
+            Some(bg) if bg.log_likelihood >= output.log_likelihood => {}
+            _ => best_guess = Some(output),
+        }
+    }
+    best_guess.ok_or_else(|| Error::model("No best guess contam object"))
+}
+
+/// Estimate the contamination level under a relatedness-aware contaminant model, and
+/// test whether the data favor a related contaminant over an unrelated one
+///
+/// # Arguments
+/// * `variant_list` - a list of VariantPosition
+/// * `contamination_levels` - the contamination levels to evaluate
+/// * `kinship` - a fixed kinship coefficient, or `None` to profile it over 0 - 0.25
+///
+/// # Returns
+/// * the fixed (or maximum likelihood) kinship and the maximum likelihood contamination level
+///   at that kinship, compared to the unrelated model
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::relatedness_estimator::estimate_relatedness;
+/// use diploid_contam_estimator::model::{VariantPosition, VariantType, Zygosity};
+/// let variant_list: Vec<VariantPosition> = vec![
+///     VariantPosition::new("X", 1, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS).unwrap(),
+///     VariantPosition::new("X", 2, 100, 100, VariantType::SNV, Zygosity::HOMOZYGOUS).unwrap(),
+/// ];
+/// let result = estimate_relatedness(&variant_list, &[0.001, 0.01, 0.1], Some(0.0)).unwrap();
+/// assert_eq!(result.kinship, 0.0);
+/// assert!(!result.related_contaminant);
+/// ```
+pub fn estimate_relatedness(
+    variant_list: &[VariantPosition],
+    contamination_levels: &[f64],
+    kinship: Option<f64>,
+) -> Result<RelatednessResult, Error> {
+    let unrelated = fit_contamination_level(variant_list, contamination_levels, 0.0)?;
+    let (best_kinship, best_fit) = match kinship {
+        // a fixed kinship is reported as is, even if the unrelated model fits better
+        Some(k) => (
+            k,
+            fit_contamination_level(variant_list, contamination_levels, k)?,
+        ),
+        None => {
+            let mut best: (f64, ContamProbResult) = (0.0, unrelated);
+            for step in 1..=(MAX_KINSHIP / KINSHIP_STEP).round() as usize {
+                let kinship = step as f64 * KINSHIP_STEP;
+                let fit = fit_contamination_level(variant_list, contamination_levels, kinship)?;
+                if fit.log_likelihood > best.1.log_likelihood {
+                    best = (kinship, fit);
+                }
+            }
+            best
+        }
+    };
+    Ok(RelatednessResult {
+        kinship: best_kinship,
+        contamination_level: best_fit.contamination_level,
+        log_likelihood: best_fit.log_likelihood,
+        unrelated_contamination_level: unrelated.contamination_level,
+        unrelated_log_likelihood: unrelated.log_likelihood,
+        related_contaminant: best_kinship > 0.0
+            && best_fit.log_likelihood - unrelated.log_likelihood > RELATED_LOG_LIKELIHOOD_RATIO,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{VariantType, Zygosity};
+    use assert_approx_eq::assert_approx_eq;
+    use rstest::*;
+
+    #[rstest]
+    #[case(0.5, 0.0, [0.25, 0.5, 0.25])]
+    #[case(1.0, 0.0, [0.25, 0.5, 0.25])]
+    #[case(0.5, 0.25, [0.25, 0.5, 0.25])]
+    #[case(1.0, 0.25, [0.0, 0.5, 0.5])]
+    #[case(1.0, 0.125, [0.125, 0.5, 0.375])]
+    fn test_contaminant_dosage_probabilities(
+        #[case] sample_alt_fraction: f64,
+        #[case] kinship: f64,
+        #[case] expected: [f64; 3],
+    ) {
+        let probs = contaminant_dosage_probabilities(sample_alt_fraction, kinship).unwrap();
+        for (p, e) in probs.iter().zip(expected.iter()) {
+            assert_approx_eq!(p, e);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Kinship must be between")]
+    fn test_contaminant_dosage_probabilities_exception() {
+        contaminant_dosage_probabilities(0.5, 0.5).unwrap();
+    }
+
+    #[rstest]
+    #[case(Zygosity::HOMOZYGOUS, 1500, 0.0)] // far below the expected fraction at a deep site
+    #[case(Zygosity::HOMOZYGOUS, 1999, 0.0)] // a stray ref read at an expected fraction of 1
+    #[case(Zygosity::HETEROZYGOUS, 1990, 0.25)]
+    #[case(Zygosity::HETEROZYGOUS, 10, 0.1)]
+    fn test_calc_loglik_for_related_contaminant_deep_site(
+        #[case] zygosity: Zygosity,
+        #[case] alt_depth: usize,
+        #[case] kinship: f64,
+    ) {
+        let variant =
+            VariantPosition::new("X", 1, 2000, alt_depth, VariantType::SNV, zygosity).unwrap();
+        for contamination_level in [0.0, 0.01, 0.1] {
+            let loglik =
+                calc_loglik_for_related_contaminant(&variant, contamination_level, kinship)
+                    .unwrap();
+            assert!(loglik.is_finite());
+        }
+    }
+
+    #[test]
+    fn test_estimate_relatedness() {
+        // hom variants with a contaminant that always carries at least one alt allele
+        let variant_list: Vec<VariantPosition> = (0..50)
+            .map(|i| {
+                VariantPosition::new(
+                    "X",
+                    i,
+                    1000,
+                    if i % 2 == 0 { 1000 } else { 950 },
+                    VariantType::SNV,
+                    Zygosity::HOMOZYGOUS,
+                )
+                .unwrap()
+            })
+            .collect();
+        let levels: Vec<f64> = (1..400).map(|x| x as f64 * 0.001).collect();
+        let result = estimate_relatedness(&variant_list, &levels, None).unwrap();
+        assert_approx_eq!(result.kinship, 0.25);
+        assert_approx_eq!(result.contamination_level, 0.1, 0.005);
+        assert!(result.related_contaminant);
+        assert!(result.log_likelihood > result.unrelated_log_likelihood);
+    }
+
+    #[test]
+    fn test_estimate_relatedness_fixed_kinship() {
+        // hom variants with an unrelated contaminant (20%) carrying 0, 1 or 2 alt alleles
+        let variant_list: Vec<VariantPosition> = (0..50)
+            .map(|i| {
+                VariantPosition::new(
+                    "X",
+                    i,
+                    1000,
+                    match i % 4 {
+                        0 => 800,
+                        1 | 2 => 900,
+                        _ => 1000,
+                    },
+                    VariantType::SNV,
+                    Zygosity::HOMOZYGOUS,
+                )
+                .unwrap()
+            })
+            .collect();
+        let levels: Vec<f64> = (1..400).map(|x| x as f64 * 0.001).collect();
+        let result = estimate_relatedness(&variant_list, &levels, Some(0.25)).unwrap();
+        assert_approx_eq!(result.kinship, 0.25);
+        assert_approx_eq!(result.unrelated_contamination_level, 0.2, 0.005);
+        assert!(result.log_likelihood < result.unrelated_log_likelihood);
+        assert_ne!(
+            result.contamination_level,
+            result.unrelated_contamination_level
+        );
+        assert!(!result.related_contaminant);
+    }
+}
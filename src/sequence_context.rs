@@ -0,0 +1,250 @@
+use crate::contig_alias::ContigAliases;
+use crate::error::Error;
+use crate::fastareader::IndexedFasta;
+use crate::model::{SequenceContext, VariantPosition};
+use log::warn;
+use std::collections::HashMap;
+use std::string::String;
+use std::vec::Vec;
+
+const FLANK_SIZE: usize = 25; // how many bases on each side of the variant to look at
+const MAX_STR_PERIOD: usize = 6; // longest repeat unit to be considered as a short tandem repeat
+const MIN_STR_COPIES: usize = 3; // minimum number of repeat units to be called a short tandem repeat
+pub const MIN_HOMOPOLYMER_STRATUM: usize = 4; // homopolymer length for the "homopolymer" stratum
+
+/// the number of identical bases in the run containing `idx`
+fn run_length(seq: &[u8], idx: usize) -> usize {
+    if idx >= seq.len() {
+        return 0;
+    }
+    let base = seq[idx];
+    let left = seq[..idx].iter().rev().take_while(|&&b| b == base).count();
+    let right = seq[idx..].iter().take_while(|&&b| b == base).count();
+    left + right
+}
+
+/// the length of the tandem repeat tract with the given period that contains `idx`
+fn tandem_repeat_length(seq: &[u8], idx: usize, period: usize) -> usize {
+    if idx + period > seq.len() {
+        return 0;
+    }
+    let mut left = idx;
+    while left > 0 && seq[left - 1] == seq[left - 1 + period] {
+        left -= 1;
+    }
+    let mut right = idx + period;
+    while right < seq.len() && seq[right] == seq[right - period] {
+        right += 1;
+    }
+    right - left
+}
+
+/// Describe the sequence context of a variant from its flanking reference sequence
+///
+/// # Arguments
+/// * `seq`: the reference sequence around the variant
+/// * `idx`: 0-based index of the variant (anchor) base in `seq`
+///
+/// # Returns
+/// * homopolymer length and short tandem repeat period around the variant, and GC content of `seq`
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::sequence_context::describe_sequence_context;
+/// let context = describe_sequence_context(b"GCATCACACACAGT", 3);
+/// assert_eq!(context.str_period, Some(2));
+/// assert_eq!(context.str_length, 8);
+/// ```
+pub fn describe_sequence_context(seq: &[u8], idx: usize) -> SequenceContext {
+    // indels are left-aligned and anchored on the base before the event,
+    // so the base after the anchor is also considered
+    let homopolymer_length = run_length(seq, idx).max(run_length(seq, idx + 1));
+
+    let mut str_period: Option<usize> = None;
+    let mut str_length: usize = homopolymer_length;
+    for period in 2..=MAX_STR_PERIOD {
+        let tract_length =
+            tandem_repeat_length(seq, idx + 1, period).max(tandem_repeat_length(seq, idx, period));
+        if tract_length >= period * MIN_STR_COPIES && tract_length > str_length {
+            str_period = Some(period);
+            str_length = tract_length;
+        }
+    }
+    if str_period.is_none() {
+        str_length = 0;
+    }
+
+    let gc_count = seq.iter().filter(|&&b| b == b'G' || b == b'C').count();
+    SequenceContext {
+        homopolymer_length,
+        str_period,
+        str_length,
+        gc_content: match seq.is_empty() {
+            true => 0.0,
+            false => gc_count as f64 / seq.len() as f64,
+        },
+    }
+}
+
+/// Annotate each variant with the sequence context from the reference fasta file
+///
+/// # Arguments
+/// * `variant_list`: the variants to be annotated
+/// * `fasta_file`: a samtools-faidx indexed reference fasta file
+/// * `contig_aliases`: aliases for matching the vcf contig names to the fasta sequence names
+///
+/// Variants on sequences that are missing from the fasta file, or beyond the end of the
+/// sequence, are left un-annotated
+pub fn annotate_sequence_context(
+    variant_list: &mut [VariantPosition],
+    fasta_file: &str,
+    contig_aliases: &ContigAliases,
+) -> Result<(), Error> {
+    let mut fasta = IndexedFasta::open(fasta_file)?;
+    let fasta_contigs = fasta.contig_names();
+    // the fasta sequence name of each vcf contig, None if the contig is missing from the fasta
+    let mut resolved_contigs: HashMap<String, Option<String>> = HashMap::new();
+    for variant in variant_list.iter_mut() {
+        let fasta_contig = resolved_contigs
+            .entry(variant.contig.clone())
+            .or_insert_with(|| {
+                let fasta_contig = contig_aliases.resolve(&variant.contig, &fasta_contigs);
+                if fasta_contig.is_none() {
+                    warn!(
+                        "Sequence {} is not found in {}, skipping sequence context annotation",
+                        variant.contig, fasta_file
+                    );
+                }
+                fasta_contig
+            });
+        let fasta_contig = match fasta_contig {
+            Some(fasta_contig) => fasta_contig.as_str(),
+            None => continue,
+        };
+        let contig_length = fasta.contig_length(fasta_contig).unwrap_or(0);
+        if variant.position > contig_length {
+            warn!(
+                "Variant {}:{} is beyond the end of sequence {} ({} bp) in {}, skipping sequence context annotation",
+                variant.contig, variant.position, fasta_contig, contig_length, fasta_file
+            );
+            continue;
+        }
+        let start = variant.position.saturating_sub(FLANK_SIZE).max(1);
+        let seq = fasta.fetch(fasta_contig, start, variant.position + FLANK_SIZE)?;
+        variant.set_sequence_context(describe_sequence_context(&seq, variant.position - start));
+    }
+    Ok(())
+}
+
+/// label of the sequence context strata a variant belongs to
+///
+/// # Returns
+/// * one of `homopolymer`, `STR` or `non-repetitive`, and a GC content bin
+pub fn sequence_context_strata(variant: &VariantPosition) -> Vec<String> {
+    match &variant.sequence_context {
+        None => vec![],
+        Some(context) => {
+            let repeat = if context.homopolymer_length >= MIN_HOMOPOLYMER_STRATUM {
+                "homopolymer"
+            } else if context.str_period.is_some() {
+                "STR"
+            } else {
+                "non-repetitive"
+            };
+            let gc = if context.gc_content < 0.4 {
+                "GC<0.4"
+            } else if context.gc_content <= 0.6 {
+                "GC 0.4-0.6"
+            } else {
+                "GC>0.6"
+            };
+            vec![repeat.to_string(), gc.to_string()]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{VariantType, Zygosity};
+    use rstest::*;
+
+    #[rstest]
+    #[case(b"ACGTAAAAAAGT", 3, 6, None, 0.25)] // deletion anchored before a homopolymer
+    #[case(b"ACGTAAAAAAGT", 1, 1, None, 0.25)]
+    #[case(b"GTCACACACACATT", 1, 1, Some(2), 0.42857142857)]
+    #[case(b"GGCCTCCTCCTCGG", 2, 2, Some(3), 0.78571428571)]
+    fn test_describe_sequence_context(
+        #[case] seq: &[u8],
+        #[case] idx: usize,
+        #[case] homopolymer_length: usize,
+        #[case] str_period: Option<usize>,
+        #[case] gc_content: f64,
+    ) {
+        let context = describe_sequence_context(seq, idx);
+        assert_eq!(context.homopolymer_length, homopolymer_length);
+        assert_eq!(context.str_period, str_period);
+        assert!((context.gc_content - gc_content).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_annotate_sequence_context() {
+        let mut variant_list = vec![
+            VariantPosition::new(
+                "chr_test",
+                20,
+                100,
+                50,
+                VariantType::INDEL,
+                Zygosity::HETEROZYGOUS,
+            )
+            .unwrap(),
+            VariantPosition::new("X", 20, 100, 50, VariantType::SNV, Zygosity::HETEROZYGOUS)
+                .unwrap(),
+            VariantPosition::new(
+                "test",
+                20,
+                100,
+                50,
+                VariantType::INDEL,
+                Zygosity::HETEROZYGOUS,
+            )
+            .unwrap(),
+        ];
+        let mut contig_aliases = ContigAliases::default();
+        contig_aliases.add(&["chr_test", "test"]);
+        annotate_sequence_context(&mut variant_list, "data/test.fa", &contig_aliases).unwrap();
+        let context = variant_list[0].sequence_context.as_ref().unwrap();
+        assert_eq!(context.homopolymer_length, 8);
+        assert_eq!(sequence_context_strata(&variant_list[0])[0], "homopolymer");
+        assert!(variant_list[1].sequence_context.is_none());
+        // matched to chr_test through the alias table
+        assert_eq!(
+            variant_list[2].sequence_context,
+            variant_list[0].sequence_context
+        );
+    }
+
+    #[rstest]
+    #[case(120, true)]
+    #[case(121, false)]
+    #[case(5000, false)]
+    fn test_annotate_sequence_context_beyond_contig_end(
+        #[case] position: usize,
+        #[case] annotated: bool,
+    ) {
+        let mut variant_list = vec![VariantPosition::new(
+            "chr_test",
+            position,
+            100,
+            50,
+            VariantType::SNV,
+            Zygosity::HETEROZYGOUS,
+        )
+        .unwrap()];
+        annotate_sequence_context(&mut variant_list, "data/test.fa", &ContigAliases::default())
+            .unwrap();
+        assert_eq!(variant_list[0].sequence_context.is_some(), annotated);
+    }
+}
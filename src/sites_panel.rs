@@ -0,0 +1,285 @@
+use crate::bedreader::open_text_file;
+use crate::error::Error;
+use crate::vcfreader::{is_non_ref_allele, is_reference_record, open_vcf_reader, record_span};
+use log::info;
+use noodles_vcf::record::Record;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::BufRead;
+use std::string::String;
+use std::vec::Vec;
+
+/// The formats of known-sites panel files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelFormat {
+    /// a (sites-only) vcf or bcf file, multi-allelic records are split into one site per alt allele
+    VCF,
+    /// a (optionally gzipped) tab-separated contig/position/ref/alt table, `#` lines are skipped
+    TSV,
+}
+
+impl PanelFormat {
+    /// Detect the format of a panel file from its extension (ignoring `.gz`)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::sites_panel::PanelFormat;
+    /// assert_eq!(PanelFormat::from_path("sites.vcf.gz"), PanelFormat::VCF);
+    /// assert_eq!(PanelFormat::from_path("sites.bcf"), PanelFormat::VCF);
+    /// assert_eq!(PanelFormat::from_path("sites.tsv"), PanelFormat::TSV);
+    /// ```
+    pub fn from_path(panel_file: &str) -> Self {
+        let path = panel_file.trim_end_matches(".gz");
+        match path.ends_with(".vcf") || path.ends_with(".bcf") {
+            true => PanelFormat::VCF,
+            false => PanelFormat::TSV,
+        }
+    }
+}
+
+/// A known site, a single alt allele at a 1-based position
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KnownSite {
+    /// contig name
+    pub contig: String,
+    /// 1-based position
+    pub position: usize,
+    /// the (upper-cased) reference allele
+    pub ref_allele: String,
+    /// the (upper-cased) alt allele
+    pub alt_allele: String,
+}
+
+impl KnownSite {
+    pub fn new(contig: &str, position: usize, ref_allele: &str, alt_allele: &str) -> Self {
+        Self {
+            contig: contig.to_string(),
+            position,
+            ref_allele: ref_allele.to_uppercase(),
+            alt_allele: alt_allele.to_uppercase(),
+        }
+    }
+}
+
+/// A panel of known (e.g. common polymorphic) sites that variants are restricted to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SitesPanel {
+    /// the sites sorted by contig and position
+    sites: BTreeSet<KnownSite>,
+}
+
+impl SitesPanel {
+    /// Read a known-sites panel from a vcf/bcf or tsv file (see [PanelFormat])
+    ///
+    /// # Arguments
+    /// * `panel_file`: the panel file, the format is detected by the file extension
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::sites_panel::SitesPanel;
+    /// let panel = SitesPanel::from_file("data/panel.tsv").unwrap();
+    /// assert_eq!(panel.len(), 6);
+    /// assert!(panel.contains("X", 38145492, "T", "C"));
+    /// assert!(!panel.contains("X", 38145492, "T", "G"));
+    /// ```
+    pub fn from_file(panel_file: &str) -> Result<Self, Error> {
+        let mut panel = SitesPanel::default();
+        match PanelFormat::from_path(panel_file) {
+            PanelFormat::VCF => {
+                for result in open_vcf_reader(panel_file)? {
+                    let record = result.map_err(|e| e.with_file(panel_file))?;
+                    let contig = record.chromosome().to_string();
+                    let ref_allele = record.reference_bases().to_string();
+                    for alt_allele in record.alternate_bases().iter() {
+                        panel.sites.insert(KnownSite::new(
+                            &contig,
+                            usize::from(record.position()),
+                            &ref_allele,
+                            &alt_allele.to_string(),
+                        ));
+                    }
+                }
+            }
+            PanelFormat::TSV => {
+                for (i, line) in open_text_file(panel_file)?.lines().enumerate() {
+                    let line = line.map_err(|e| Error::io(panel_file, e))?;
+                    if line.trim().is_empty() || line.starts_with('#') {
+                        continue;
+                    }
+                    let parse_error = |message: &str| {
+                        Error::parse(format!("{} in panel line: {}", message, line))
+                            .with_line(i + 1)
+                            .with_file(panel_file)
+                    };
+                    let columns: Vec<&str> = line.split('\t').collect();
+                    if columns.len() < 4 {
+                        return Err(parse_error("Missing columns"));
+                    }
+                    let position = columns[1]
+                        .parse::<usize>()
+                        .map_err(|_| parse_error("Invalid position"))?;
+                    for alt_allele in columns[3].split(',') {
+                        panel
+                            .sites
+                            .insert(KnownSite::new(columns[0], position, columns[2], alt_allele));
+                    }
+                }
+            }
+        }
+        info!("Collected {} known sites from {}", panel.len(), panel_file);
+        Ok(panel)
+    }
+
+    /// number of sites (alt alleles) in the panel
+    pub fn len(&self) -> usize {
+        self.sites.len()
+    }
+
+    /// true if there is no site
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    /// the contig names of the sites
+    pub fn contigs(&self) -> HashSet<&str> {
+        self.sites.iter().map(|site| site.contig.as_str()).collect()
+    }
+
+    /// Rename the contigs of the sites, sites on contigs renamed to None are dropped
+    pub fn rename_contigs<F>(&self, mut rename: F) -> Self
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let renamed_contigs: HashMap<&str, Option<String>> = self
+            .contigs()
+            .into_iter()
+            .map(|contig| (contig, rename(contig)))
+            .collect();
+        let sites = self
+            .sites
+            .iter()
+            .filter_map(|site| {
+                renamed_contigs[site.contig.as_str()]
+                    .as_ref()
+                    .map(|renamed| KnownSite {
+                        contig: renamed.to_string(),
+                        ..site.clone()
+                    })
+            })
+            .collect();
+        Self { sites }
+    }
+
+    /// Check if a variant (a single alt allele) is a known site
+    pub fn contains(
+        &self,
+        contig: &str,
+        position: usize,
+        ref_allele: &str,
+        alt_allele: &str,
+    ) -> bool {
+        self.sites
+            .contains(&KnownSite::new(contig, position, ref_allele, alt_allele))
+    }
+
+    /// the known sites between two 1-based positions (inclusive) of a contig
+    pub fn sites_within(
+        &self,
+        contig: &str,
+        start: usize,
+        end: usize,
+    ) -> impl Iterator<Item = &KnownSite> + '_ {
+        let first_site = KnownSite::new(contig, start, "", "");
+        let contig = contig.to_string();
+        self.sites
+            .range(first_site..)
+            .take_while(move |site| site.contig == contig && site.position <= end)
+    }
+
+    /// The known sites covered by a vcf record
+    ///
+    /// A variant record covers the sites at its position with the same reference allele and
+    /// one of its alt alleles, or any alt allele if the record has a `<NON_REF>` (or `<*>`)
+    /// allele; a reference record (e.g. a gVCF reference block) covers all sites within its span
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::sites_panel::SitesPanel;
+    /// use noodles_vcf::record::Record;
+    /// let panel = SitesPanel::from_file("data/panel.tsv").unwrap();
+    /// let record: Record = "X\t38145490\t.\tC\t<NON_REF>\t.\t.\tEND=38145500\tGT:AD:DP\t0/0:30,0:30"
+    ///     .parse()
+    ///     .unwrap();
+    /// assert_eq!(panel.record_sites(&record).len(), 3);
+    /// ```
+    pub fn record_sites(&self, record: &Record) -> Vec<&KnownSite> {
+        let contig = record.chromosome().to_string();
+        let position = usize::from(record.position());
+        if is_reference_record(record) {
+            let (start, end) = record_span(record);
+            return self.sites_within(&contig, start, end).collect();
+        }
+        let ref_allele = record.reference_bases().to_string().to_uppercase();
+        let alt_alleles: Vec<String> = record
+            .alternate_bases()
+            .iter()
+            .map(|allele| allele.to_string().to_uppercase())
+            .collect();
+        let any_alt = alt_alleles.iter().any(|allele| is_non_ref_allele(allele));
+        self.sites_within(&contig, position, position)
+            .filter(|site| {
+                site.ref_allele == ref_allele && (any_alt || alt_alleles.contains(&site.alt_allele))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case("data/panel.tsv", "X", 38145494, "T", "G", true)]
+    #[case("data/panel.tsv", "X", 38145494, "T", "C", false)] // allele mismatch
+    #[case("data/panel.tsv", "X", 38145493, "T", "G", false)]
+    #[case("data/panel.tsv", "chrX", 38145911, "C", "T", true)]
+    #[case("data/panel.vcf", "X", 38145494, "T", "C", true)] // multi-allelic record
+    #[case("data/panel.vcf", "X", 38145494, "T", "G", true)]
+    #[case("data/panel.vcf", "X", 38145497, "c", "a", true)] // case insensitive
+    #[case("data/panel.vcf", "1", 12346, "A", "G", false)]
+    fn test_contains(
+        #[case] panel_file: &str,
+        #[case] contig: &str,
+        #[case] position: usize,
+        #[case] ref_allele: &str,
+        #[case] alt_allele: &str,
+        #[case] expected: bool,
+    ) {
+        let panel = SitesPanel::from_file(panel_file).unwrap();
+        assert_eq!(
+            panel.contains(contig, position, ref_allele, alt_allele),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_rename_contigs() {
+        let panel = SitesPanel::from_file("data/panel.tsv")
+            .unwrap()
+            .rename_contigs(|contig| match contig {
+                "1" => None,
+                _ => Some(contig.trim_start_matches("chr").to_string()),
+            });
+        assert_eq!(panel.len(), 5);
+        assert!(panel.contains("X", 38145911, "C", "T"));
+    }
+
+    #[test]
+    #[should_panic(expected = "data/test.bed, line 1: Missing columns in panel line")]
+    fn test_from_file_exception() {
+        SitesPanel::from_file("data/test.bed").unwrap();
+    }
+}
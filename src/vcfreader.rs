@@ -1,252 +1,1504 @@
-use crate::model::{VariantPosition, VariantType, Zygosity};
-use log::info;
+use crate::bcfreader::BcfReader;
+use crate::contig_alias::ContigAliases;
+use crate::error::Error;
+use crate::expression::Expression;
+use crate::format_tags::FormatTags;
+use crate::interval_set::{Interval, IntervalSet};
+use crate::model::{
+    FilterCount, PanelCount, Phase, RejectionCounts, SampleVariants, StrandCounts, VariantPosition,
+    VariantType, Zygosity,
+};
+use crate::sites_panel::{KnownSite, SitesPanel};
+use log::{info, warn};
 use noodles_bgzf as bgzf;
+use noodles_csi::{self as csi, BinningIndex};
 use noodles_tabix as tabix;
 use noodles_vcf as vcf;
 use noodles_vcf::header::format::Key;
 use noodles_vcf::record::filters::Filters;
-use noodles_vcf::record::genotypes::genotype::field::Value::{Integer, IntegerArray};
+use noodles_vcf::record::genotypes::genotype::field::value::genotype::allele::Phasing;
+use noodles_vcf::record::genotypes::genotype::field::Value;
+use noodles_vcf::record::genotypes::Genotype;
 use noodles_vcf::record::Record;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::{metadata, File};
-use std::io::BufReader;
+use std::io::{stdin, BufRead, BufReader, Read};
 use std::string::String;
 use std::vec::Vec;
 
-/// Evaluate a vcf record and determine whether it should be collected
-/// for estimating contamination
+/// Options for selecting the vcf records and variants to be collected
+#[derive(Debug, Clone)]
+pub struct VariantFilter {
+    /// boolean flag indicating whether we should only look at SNV instead of both SNV and indel
+    pub snv_only_flag: bool,
+    /// the variant types to be collected, see [DEFAULT_VARIANT_TYPES]
+    pub variant_types: Vec<VariantType>,
+    /// removing all variants with read depth below this threshold
+    pub depth_threshold: usize,
+    /// the FORMAT tags for the total depth and allele counts
+    pub format_tags: FormatTags,
+    /// FILTER values (other than PASS) of records to be accepted, `*` accepts any FILTER value
+    pub allowed_filters: Vec<String>,
+    /// FILTER values of records to be rejected, this takes precedence over `allowed_filters`
+    pub denied_filters: Vec<String>,
+    /// only collect variants satisfying this expression (e.g. `QUAL>30 && FMT/GQ>=20`)
+    pub include: Option<Expression>,
+    /// skip variants satisfying this expression
+    pub exclude: Option<Expression>,
+    /// skip records overlapping these regions (e.g. segmental duplications and blacklists)
+    pub exclude_regions: Option<IntervalSet>,
+    /// aliases for matching the contig names of the regions to the vcf header
+    pub contig_aliases: ContigAliases,
+    /// only collect variants at these known sites (with matching alleles)
+    pub sites_panel: Option<SitesPanel>,
+    /// skip malformed or inconsistent records and sample calls (counted by reason, see
+    /// [RejectionCounts]) instead of failing
+    pub lenient: bool,
+    /// removing all variants with a phred-scaled Fisher strand bias above this (see
+    /// [StrandCounts::fisher_strand]), variants without strand counts are kept
+    pub max_fisher_strand: Option<f64>,
+    /// removing all variants with a strand odds ratio above this (see
+    /// [StrandCounts::strand_odds_ratio]), variants without strand counts are kept
+    pub max_strand_odds_ratio: Option<f64>,
+    /// use the known sites within gVCF reference blocks as hom-ref calls without alt reads at
+    /// the minimum depth of the block, the blocks have no per-site allele depths so these sites
+    /// are skipped otherwise
+    pub reference_block_depth: bool,
+}
+
+impl Default for VariantFilter {
+    fn default() -> Self {
+        VariantFilter::new(false, 0)
+    }
+}
+
+impl VariantFilter {
+    /// Create a filter that accepts PASS records only
+    ///
+    /// # Arguments
+    /// * `snv_only_flag`: boolean flag indicating whether we should skip all InDel variants
+    /// * `depth_threshold`: if the variant has a total depth lower than this, it will be rejected
+    pub fn new(snv_only_flag: bool, depth_threshold: usize) -> Self {
+        VariantFilter {
+            snv_only_flag,
+            variant_types: DEFAULT_VARIANT_TYPES.to_vec(),
+            depth_threshold,
+            format_tags: FormatTags::default(),
+            allowed_filters: vec![],
+            denied_filters: vec![],
+            include: None,
+            exclude: None,
+            exclude_regions: None,
+            contig_aliases: ContigAliases::default(),
+            sites_panel: None,
+            lenient: false,
+            max_fisher_strand: None,
+            max_strand_odds_ratio: None,
+            reference_block_depth: false,
+        }
+    }
+
+    /// Whether variants of the given type should be collected
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::VariantType;
+    /// use diploid_contam_estimator::vcfreader::VariantFilter;
+    /// let variant_filter = VariantFilter::new(false, 0);
+    /// assert!(variant_filter.accepts_variant_type(VariantType::MNV));
+    /// assert!(!variant_filter.accepts_variant_type(VariantType::SYMBOLIC));
+    /// assert!(!VariantFilter::new(true, 0).accepts_variant_type(VariantType::MNV));
+    /// ```
+    pub fn accepts_variant_type(&self, variant_type: VariantType) -> bool {
+        self.variant_types.contains(&variant_type)
+            && (!self.snv_only_flag || variant_type == VariantType::SNV)
+    }
+
+    /// Whether a variant with the given strand counts passes the strand bias thresholds
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::model::StrandCounts;
+    /// use diploid_contam_estimator::vcfreader::VariantFilter;
+    /// let mut variant_filter = VariantFilter::new(false, 0);
+    /// variant_filter.max_strand_odds_ratio = Some(3.0);
+    /// let biased = StrandCounts { ref_forward: 20, ref_reverse: 20, alt_forward: 20, alt_reverse: 0 };
+    /// assert!(!variant_filter.accepts_strand_counts(Some(&biased)));
+    /// assert!(variant_filter.accepts_strand_counts(None));
+    /// ```
+    pub fn accepts_strand_counts(&self, strand_counts: Option<&StrandCounts>) -> bool {
+        let strand_counts = match strand_counts {
+            Some(strand_counts) => strand_counts,
+            None => return true,
+        };
+        self.max_fisher_strand
+            .is_none_or(|max_fs| strand_counts.fisher_strand() <= max_fs)
+            && self
+                .max_strand_odds_ratio
+                .is_none_or(|max_sor| strand_counts.strand_odds_ratio() <= max_sor)
+    }
+
+    /// Whether a record with the given FILTER values should be collected
+    ///
+    /// # Arguments
+    /// * `filter_values`: the FILTER values of a record (see [`record_filter_values`])
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::vcfreader::VariantFilter;
+    /// let mut variant_filter = VariantFilter::new(false, 0);
+    /// variant_filter.allowed_filters = vec!["LowQual".to_string()];
+    /// assert!(variant_filter.accepts_filters(&["LowQual".to_string()]));
+    /// assert!(!variant_filter.accepts_filters(&["RefCall".to_string()]));
+    /// ```
+    pub fn accepts_filters(&self, filter_values: &[String]) -> bool {
+        if filter_values
+            .iter()
+            .any(|value| self.denied_filters.contains(value))
+        {
+            return false;
+        }
+        filter_values.iter().all(|value| {
+            value == "PASS"
+                || self
+                    .allowed_filters
+                    .iter()
+                    .any(|allowed| allowed == "*" || allowed == value)
+        })
+    }
+}
+
+/// The FILTER values of a vcf record, no filter means PASS
+pub fn record_filter_values(record: &Record) -> Vec<String> {
+    match record.filters() {
+        None | Some(Filters::Pass) => vec!["PASS".to_string()],
+        Some(Filters::Fail(values)) => values.iter().cloned().collect(),
+    }
+}
+
+/// Evaluate a sample of a vcf record and determine whether it should be collected
+/// for estimating contamination, the FILTER column is evaluated by
+/// [`VariantFilter::accepts_filters`] beforehand
 ///
 /// # Arguments
 ///
 /// - `record`: a vcf record from noodles_vcf
-/// - `variants`: a mutable list containing the accepted variants
-/// - `depth_threshold`: if the variant has DP tag lower than this, it will be rejected
-/// - `snv_only_flag`: boolean flag indicating whether we should skip all InDel variants
+/// - `sample_index`: the index of the sample to be evaluated in the vcf record
+/// - `variant_filter`: the depth threshold, variant type, FORMAT tags and include/exclude expression options
 ///
 /// # Return
 /// - `VariantPosition` object or a `Option::None` if the variant didn't pass the filters
+/// - a [Rejection] if the sample call is a no-call, or is malformed or inconsistent
 fn filter_variants(
     record: &Record,
-    depth_threshold: usize,
-    snv_only_flag: bool,
-) -> Result<Option<VariantPosition>, String> {
-    // no filter means PASS
-    if record
-        .filters()
-        .unwrap_or(&Filters::Pass)
-        .eq(&Filters::Pass)
+    sample_index: usize,
+    variant_filter: &VariantFilter,
+) -> Result<Option<VariantPosition>, Rejection> {
+    let sample_genotype = sample_genotype(record, sample_index)?;
+    if !accepts_expressions(record, sample_genotype, variant_filter) {
+        return Ok(None);
+    }
+    // Genotyping sample
+    let (ref_genotype, alt_genotype, alt_haplotype) = genotype_call(sample_genotype)?;
+    if alt_genotype == 0 {
+        // homozygous reference call of this sample in a multi-sample vcf or a gVCF reference block
+        return Ok(None);
+    }
+    // assume diploid call (2nd genotype is non-ref), hence: [1].index
+    let ref_base = record.reference_bases();
+    let alt_base = &record.alternate_bases()[alt_genotype - 1];
+    if is_non_ref_allele(&alt_base.to_string()) {
+        // the unspecified alt allele of a gVCF record
+        return Ok(None);
+    }
+    let (read_depth, allele_depths) = read_counts(record, sample_genotype, variant_filter)?;
+
+    if read_depth >= variant_filter.depth_threshold {
+        let mut zygosity = Zygosity::HOMOZYGOUS;
+        if ref_genotype != alt_genotype {
+            zygosity = Zygosity::HETEROZYGOUS
+        }
+        let alt_depth = allele_depths
+            .get(alt_genotype)
+            .copied()
+            .flatten()
+            .ok_or_else(|| Rejection::MissingAlleleDepth(Error::missing("alt allele depth")))?;
+
+        let variant_type = VariantType::classify(&ref_base.to_string(), &alt_base.to_string());
+        if variant_filter.accepts_variant_type(variant_type) {
+            // make a new VariantPosition here and put into the list
+            let mut variant = VariantPosition::new(
+                &record.chromosome().to_string(),
+                usize::from(record.position()),
+                read_depth, // only sample in the vcf
+                alt_depth,
+                variant_type,
+                zygosity,
+            )
+            .map_err(|e| Rejection::from_depths(read_depth, alt_depth, e))?;
+            variant.set_alleles(&ref_base.to_string(), &alt_base.to_string());
+            if let Some(quality) = record.quality_score() {
+                variant.set_quality(f32::from(quality) as f64);
+            }
+            let strand_counts = variant_filter
+                .format_tags
+                .strand_counts(record, sample_genotype, alt_genotype)
+                .map_err(Rejection::Malformed)?;
+            if !variant_filter.accepts_strand_counts(strand_counts.as_ref()) {
+                return Ok(None);
+            }
+            if let Some(strand_counts) = strand_counts {
+                variant.set_strand_counts(strand_counts);
+            }
+            if let (0, Some(alt_haplotype)) = (ref_genotype, alt_haplotype) {
+                variant.set_phase(Phase {
+                    phase_set: phase_set(sample_genotype)?,
+                    alt_haplotype,
+                });
+            }
+            return Ok(Some(variant));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether a sample of a vcf record satisfies the include/exclude expressions
+fn accepts_expressions(
+    record: &Record,
+    sample_genotype: &Genotype,
+    variant_filter: &VariantFilter,
+) -> bool {
+    if let Some(include) = &variant_filter.include {
+        if !include.evaluate(record, Some(sample_genotype)) {
+            return false;
+        }
+    }
+    if let Some(exclude) = &variant_filter.exclude {
+        if exclude.evaluate(record, Some(sample_genotype)) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Why a sample call of a record cannot be collected, other than not passing the filter options
+#[derive(Debug)]
+enum Rejection {
+    /// the sample has no genotype call (e.g. `./.`)
+    NoCall,
+    /// the allele read counts (e.g. AD) are missing
+    MissingAlleleDepth(Error),
+    /// the total read depth (DP) is lower than the sum of the allele read counts
+    DepthBelowAlleleDepths(Error),
+    /// the record or sample call cannot be parsed or evaluated
+    Malformed(Error),
+}
+
+impl Rejection {
+    /// the rejection of a variant that cannot be created from its total and alt read depths
+    fn from_depths(read_depth: usize, alt_depth: usize, error: Error) -> Self {
+        match read_depth < alt_depth {
+            true => Rejection::DepthBelowAlleleDepths(Error::parse(format!(
+                "DP {} is lower than the alt allele depth {}",
+                read_depth, alt_depth
+            ))),
+            false => Rejection::Malformed(error),
+        }
+    }
+
+    /// Count the rejection, no-calls are always skipped while other rejections are errors
+    /// unless in lenient mode
+    fn count(self, rejection_counts: &mut RejectionCounts, lenient: bool) -> Result<(), Error> {
+        let (count, error) = match self {
+            Rejection::NoCall => (&mut rejection_counts.no_call, None),
+            Rejection::MissingAlleleDepth(error) => {
+                (&mut rejection_counts.missing_allele_depth, Some(error))
+            }
+            Rejection::DepthBelowAlleleDepths(error) => {
+                (&mut rejection_counts.depth_below_allele_depths, Some(error))
+            }
+            Rejection::Malformed(error) => (&mut rejection_counts.malformed, Some(error)),
+        };
+        match (error, lenient) {
+            (Some(error), false) => Err(error),
+            _ => {
+                *count += 1;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// the genotype fields of a sample in a record
+fn sample_genotype(record: &Record, sample_index: usize) -> Result<&Genotype, Rejection> {
+    record.genotypes().get(sample_index).ok_or_else(|| {
+        Rejection::Malformed(Error::parse(format!(
+            "Sample index {} not found in record",
+            sample_index
+        )))
+    })
+}
+
+/// the first two allele indices of a (diploid) genotype, the ref allele first (e.g. 0 and 1
+/// for `1|0`), and the haplotype carrying the second allele (0 for the first allele of GT,
+/// 1 for the second) if the genotype is phased
+fn genotype_call(sample_genotype: &Genotype) -> Result<(usize, usize, Option<usize>), Rejection> {
+    let gt_field = sample_genotype
+        .genotype()
+        .ok_or_else(|| Rejection::Malformed(Error::missing("FORMAT/GT")))?;
+    let gt = gt_field.map_err(|e| Rejection::Malformed(Error::parse(e.to_string())))?;
+    match (gt.first(), gt.get(1)) {
+        (Some(ref_allele), Some(alt_allele)) => {
+            let phased = alt_allele.phasing() == Some(Phasing::Phased);
+            match (ref_allele.position(), alt_allele.position()) {
+                (Some(alt_genotype), Some(0)) => Ok((0, alt_genotype, phased.then_some(0))),
+                (Some(ref_genotype), Some(alt_genotype)) => {
+                    Ok((ref_genotype, alt_genotype, phased.then_some(1)))
+                }
+                // no-call of this sample in a multi-sample vcf
+                _ => Err(Rejection::NoCall),
+            }
+        }
+        _ => Err(Rejection::Malformed(Error::parse(
+            "Genotype is not diploid",
+        ))),
+    }
+}
+
+/// the first two allele indices of a (diploid) genotype, the ref allele first
+fn genotype_alleles(sample_genotype: &Genotype) -> Result<(usize, usize), Rejection> {
+    genotype_call(sample_genotype)
+        .map(|(ref_genotype, alt_genotype, _)| (ref_genotype, alt_genotype))
+}
+
+/// the phase set (FORMAT/PS) of a phased call, None if absent
+fn phase_set(sample_genotype: &Genotype) -> Result<Option<i32>, Rejection> {
+    match sample_genotype
+        .get(&Key::PhaseSet)
+        .and_then(|field| field.value())
     {
-        // only look at pass filter variants
-
-        let sample_genotype = record
-            .genotypes()
-            .get(0)
-            .ok_or_else(|| "Error out Alelle 1".to_string())?;
-        let read_depth = match sample_genotype[&Key::ReadDepth]
-            .value()
-            .ok_or_else(|| "No DP tag?".to_string())?
-        {
-            Integer(n) => *n,
-            _ => 0,
+        Some(Value::Integer(phase_set)) => Ok(Some(*phase_set)),
+        Some(_) => Err(Rejection::Malformed(Error::parse(
+            "Invalid FORMAT/PS, expecting an integer",
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// the total read depth and per-allele read counts of a sample, in lenient mode a total depth
+/// lower than the sum of the allele read counts is rejected
+fn read_counts(
+    record: &Record,
+    sample_genotype: &Genotype,
+    variant_filter: &VariantFilter,
+) -> Result<(usize, Vec<Option<usize>>), Rejection> {
+    let (read_depth, allele_depths) = variant_filter
+        .format_tags
+        .read_counts(record, sample_genotype)
+        .map_err(Rejection::MissingAlleleDepth)?;
+    let allele_depth_sum: usize = allele_depths.iter().flatten().sum();
+    if variant_filter.lenient && read_depth < allele_depth_sum {
+        return Err(Rejection::DepthBelowAlleleDepths(Error::parse(format!(
+            "DP {} is lower than the sum of allele depths {}",
+            read_depth, allele_depth_sum
+        ))));
+    }
+    Ok((read_depth, allele_depths))
+}
+
+/// The variant types collected by default, symbolic alleles carry no allele depths to be modelled
+pub const DEFAULT_VARIANT_TYPES: [VariantType; 4] = [
+    VariantType::SNV,
+    VariantType::INDEL,
+    VariantType::MNV,
+    VariantType::COMPLEX,
+];
+
+/// The unspecified alt alleles of gVCF records (`<NON_REF>` from GATK, `<*>` from bcftools)
+pub const NON_REF_ALLELES: [&str; 2] = ["<NON_REF>", "<*>"];
+
+/// Whether an alt allele is the unspecified alt allele of a gVCF record
+pub fn is_non_ref_allele(allele: &str) -> bool {
+    NON_REF_ALLELES.contains(&allele)
+}
+
+/// Whether a record has no specified alt allele, i.e. a gVCF reference block or a reference
+/// record of an all-sites vcf
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::vcfreader::is_reference_record;
+/// use noodles_vcf::record::Record;
+/// let block: Record = "X\t1\t.\tC\t<NON_REF>\t.\t.\tEND=10\tGT:DP\t0/0:30".parse().unwrap();
+/// assert!(is_reference_record(&block));
+/// let variant: Record = "X\t1\t.\tC\tT,<NON_REF>\t.\t.\t.\tGT:DP\t0/1:30".parse().unwrap();
+/// assert!(!is_reference_record(&variant));
+/// ```
+pub fn is_reference_record(record: &Record) -> bool {
+    record
+        .alternate_bases()
+        .iter()
+        .all(|allele| is_non_ref_allele(&allele.to_string()))
+}
+
+/// Evaluate a homozygous reference call of a sample at the known sites covered by a vcf record
+/// (see [SitesPanel::record_sites]), hom-ref calls at known polymorphic sites are evidence of
+/// contamination if the alt allele of the site is observed
+///
+/// The supported hom-ref calls are:
+/// - single-site records of an all-sites vcf or gVCF (e.g. `GT:AD:DP` with a `<NON_REF>`, `<*>`
+///   or panel alt allele), the alt depth of a site is the read count of its alt allele, or of
+///   the `<NON_REF>` allele, or 0 if the alt allele is not observed
+/// - gVCF reference blocks spanning several sites (e.g. GATK `GT:DP:GQ:MIN_DP` with `END`), which
+///   have only block-level read counts, so their sites are skipped unless
+///   [VariantFilter::reference_block_depth] is set, then each site is a hom-ref call at the
+///   minimum depth of the block without alt reads
+///
+/// # Arguments
+/// - `record`: a vcf record from noodles_vcf
+/// - `sample_index`: the index of the sample to be evaluated in the vcf record
+/// - `variant_filter`: the depth threshold, variant type, FORMAT tags and include/exclude expression options
+/// - `sites`: the known sites covered by the record
+///
+/// # Return
+/// - a `Zygosity::HOM_REF` variant for each site if the sample is homozygous reference
+/// - a [Rejection] if the sample call is a no-call, or is malformed or inconsistent
+fn filter_reference_sites(
+    record: &Record,
+    sample_index: usize,
+    variant_filter: &VariantFilter,
+    sites: &[&KnownSite],
+) -> Result<Vec<VariantPosition>, Rejection> {
+    let (start, end) = record_span(record);
+    let reference_block = start < end;
+    if reference_block && !variant_filter.reference_block_depth {
+        return Ok(vec![]);
+    }
+    let sample_genotype = sample_genotype(record, sample_index)?;
+    if !accepts_expressions(record, sample_genotype, variant_filter) {
+        return Ok(vec![]);
+    }
+    if genotype_alleles(sample_genotype)? != (0, 0) {
+        return Ok(vec![]);
+    }
+    let (read_depth, allele_depths) = match reference_block {
+        true => (
+            variant_filter
+                .format_tags
+                .block_depth(sample_genotype)
+                .map_err(Rejection::MissingAlleleDepth)?,
+            vec![],
+        ),
+        false => read_counts(record, sample_genotype, variant_filter)?,
+    };
+    if read_depth < variant_filter.depth_threshold.max(1) {
+        return Ok(vec![]);
+    }
+    let alt_alleles: Vec<String> = record
+        .alternate_bases()
+        .iter()
+        .map(|allele| allele.to_string().to_uppercase())
+        .collect();
+    let allele_depth = |allele_index: Option<usize>| {
+        allele_index
+            .and_then(|i| allele_depths.get(i + 1).copied().flatten())
+            .unwrap_or(0)
+            .min(read_depth)
+    };
+    let mut variants: Vec<VariantPosition> = vec![];
+    for site in sites {
+        let variant_type = VariantType::classify(&site.ref_allele, &site.alt_allele);
+        if !variant_filter.accepts_variant_type(variant_type) {
+            continue;
+        }
+        let alt_depth = match alt_alleles.iter().position(|a| *a == site.alt_allele) {
+            Some(i) => allele_depth(Some(i)),
+            None => allele_depth(alt_alleles.iter().position(|a| is_non_ref_allele(a))),
         };
+        let mut variant = VariantPosition::new(
+            &site.contig,
+            site.position,
+            read_depth,
+            alt_depth,
+            variant_type,
+            Zygosity::HOM_REF,
+        )
+        .map_err(|e| Rejection::from_depths(read_depth, alt_depth, e))?;
+        variant.set_alleles(&site.ref_allele, &site.alt_allele);
+        if let Some(quality) = record.quality_score() {
+            variant.set_quality(f32::from(quality) as f64);
+        }
+        variants.push(variant);
+    }
+    Ok(variants)
+}
 
-        if read_depth >= depth_threshold as i32 {
-            let bad_vec = &vec![None];
-            let allele_depths = match sample_genotype[&Key::ReadDepths]
-                .value()
-                .ok_or_else(|| "No AD tag".to_string())?
-            {
-                IntegerArray(n) => n,
-                _ => bad_vec,
-            };
-            // Genotyping sample
-            let gt_field = sample_genotype
-                .genotype()
-                .ok_or_else(|| "genotype not found".to_string())?;
-            let gt = gt_field.map_err(|e| e.to_string())?;
-            let ref_genotype = gt[0]
-                .position()
-                .ok_or_else(|| "ref genotype not found".to_string())?;
-            let alt_genotype = gt[1]
-                .position()
-                .ok_or_else(|| "alt genotype not found".to_string())?;
-
-            let mut zygosity = Zygosity::HOMOZYGOUS;
-            if ref_genotype != alt_genotype {
-                zygosity = Zygosity::HETEROZYGOUS
+/// The path that reads the input from stdin
+pub const STDIN: &str = "-";
+
+/// The compression of a variant input, detected from its magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// uncompressed input
+    NONE,
+    /// blocked gzip (bgzip) compressed input
+    BGZF,
+}
+
+/// The format of a (decompressed) variant input, detected from its magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    /// text vcf
+    VCF,
+    /// binary vcf
+    BCF,
+}
+
+/// Open a file for buffered reading, or stdin when the path is `-`
+fn open_input(vcf_file: &str) -> Result<Box<dyn BufRead>, Error> {
+    match vcf_file {
+        STDIN => Ok(Box::new(BufReader::new(stdin()))),
+        _ => {
+            let file = File::open(vcf_file).map_err(|e| Error::io(vcf_file, e))?;
+            Ok(Box::new(BufReader::new(file)))
+        }
+    }
+}
+
+/// Detect the compression of a stream from the gzip magic bytes and the BGZF extra field,
+/// without consuming the stream
+fn peek_compression(reader: &mut dyn BufRead) -> Result<Compression, Error> {
+    let magic = reader.fill_buf()?;
+    match magic {
+        [0x1f, 0x8b, _, flags, _, _, _, _, _, _, _, _, b'B', b'C', ..] if flags & 0x04 != 0 => {
+            Ok(Compression::BGZF)
+        }
+        [0x1f, 0x8b, ..] => Err(Error::parse(
+            "Input is gzip compressed but not bgzipped, please recompress it with bgzip",
+        )),
+        _ => Ok(Compression::NONE),
+    }
+}
+
+/// Detect the format of a decompressed stream from the BCF magic bytes, without consuming the stream
+fn peek_format(reader: &mut dyn BufRead) -> Result<InputFormat, Error> {
+    let magic = reader.fill_buf()?;
+    match magic.starts_with(b"BCF") {
+        true => Ok(InputFormat::BCF),
+        false => Ok(InputFormat::VCF),
+    }
+}
+
+/// Detect the compression and format of a variant file from its content
+///
+/// # Arguments:
+/// - `vcf_file`: file path to a plain vcf, bgzipped vcf or BCF file
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::vcfreader::{detect_format, Compression, InputFormat};
+/// assert_eq!(
+///     detect_format("data/test.bcf").unwrap(),
+///     (Compression::BGZF, InputFormat::BCF)
+/// );
+/// ```
+pub fn detect_format(vcf_file: &str) -> Result<(Compression, InputFormat), Error> {
+    let mut reader = open_input(vcf_file)?;
+    let format = match peek_compression(&mut reader)? {
+        Compression::BGZF => {
+            let mut decompressed = bgzf::Reader::new(reader);
+            (Compression::BGZF, peek_format(&mut decompressed)?)
+        }
+        Compression::NONE => (Compression::NONE, peek_format(&mut reader)?),
+    };
+    Ok(format)
+}
+
+/// the underlying reader of a [VariantReader]
+enum RecordReader {
+    /// the reader, the line buffer and the number of lines read
+    Vcf(vcf::Reader<Box<dyn BufRead>>, String, usize),
+    Bcf(Box<BcfReader<Box<dyn BufRead>>>),
+}
+
+/// A reader for all records of a plain vcf, bgzipped vcf or BCF input
+pub struct VariantReader {
+    header: vcf::Header,
+    records: RecordReader,
+}
+
+impl VariantReader {
+    /// the vcf header of the input
+    pub fn header(&self) -> &vcf::Header {
+        &self.header
+    }
+}
+
+impl Iterator for VariantReader {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.records {
+            RecordReader::Vcf(reader, line, line_number) => {
+                line.clear();
+                *line_number += 1;
+                match reader.read_record(line) {
+                    Ok(0) => None,
+                    Ok(_) => Some(
+                        parse_record(line, &self.header).map_err(|e| e.with_line(*line_number)),
+                    ),
+                    Err(e) => Some(Err(Error::from(e).with_line(*line_number))),
+                }
             }
-            // assume theres only one sample in the vcf file hence:  get(0)
-            // and diploid call (2nd genotype is non-ref), hence: [1].index
-            let ref_base = record.reference_bases();
-            let alt_base = &record.alternate_bases()[alt_genotype - 1];
-            let alt_depth = allele_depths[alt_genotype]
-                .ok_or_else(|| "Alt allele depth is unavaliable (AD tag)".to_string())?
-                as usize;
-
-            let mut variant_type: VariantType = VariantType::INDEL;
-            if ref_base.to_string().len() == alt_base.to_string().len() {
-                // this should be testing the len of Vec<u8> where
-                // each item represents a base
-                // only if they are the same length, it's a SNV
-                variant_type = VariantType::SNV;
+            RecordReader::Bcf(reader) => reader.next(),
+        }
+    }
+}
+
+/// Open a vcf input for reading all records from the start
+///
+/// # Arguments:
+/// - `vcf_file`: file path to a plain vcf, bgzipped vcf or BCF file, or `-` for stdin;
+///   the compression and format are detected from the content
+///
+/// # Returns:
+/// - a record reader holding the parsed vcf header
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::vcfreader::open_vcf_reader;
+/// let reader = open_vcf_reader("data/test.vcf.gz").unwrap();
+/// assert_eq!(reader.count(), 16);
+/// ```
+pub fn open_vcf_reader(vcf_file: &str) -> Result<VariantReader, Error> {
+    let mut reader = open_input(vcf_file)?;
+    let with_file = |e: Error| e.with_file(vcf_file);
+    let mut inner: Box<dyn BufRead> = match peek_compression(&mut reader).map_err(with_file)? {
+        Compression::BGZF => Box::new(bgzf::Reader::new(reader)),
+        Compression::NONE => reader,
+    };
+    match peek_format(&mut inner).map_err(with_file)? {
+        InputFormat::BCF => {
+            let reader = BcfReader::new(inner).map_err(with_file)?;
+            Ok(VariantReader {
+                header: reader.header().clone(),
+                records: RecordReader::Bcf(Box::new(reader)),
+            })
+        }
+        InputFormat::VCF => {
+            let mut reader = vcf::Reader::new(inner);
+            let (header, header_lines) = read_vcf_header(&mut reader).map_err(with_file)?;
+            Ok(VariantReader {
+                header,
+                records: RecordReader::Vcf(reader, String::new(), header_lines),
+            })
+        }
+    }
+}
+
+/// Read and parse the header of a vcf file
+///
+/// # Returns:
+/// - the parsed header and its number of lines
+fn read_vcf_header<R: BufRead>(reader: &mut vcf::Reader<R>) -> Result<(vcf::Header, usize), Error> {
+    let raw_header = reader.read_header()?;
+    let header = raw_header
+        .parse()
+        .map_err(|_| Error::parse("Cannot parse header properly"))?;
+    Ok((header, raw_header.lines().count()))
+}
+
+/// Parse a vcf record line
+fn parse_record(line: &str, header: &vcf::Header) -> Result<Record, Error> {
+    Record::try_from_str(line, header)
+        .map_err(|e| Error::parse(format!("Cannot parse vcf record: {}", e)))
+}
+
+/// Resolve the contig names of the regions or known-sites panel to the contig names in the
+/// vcf header (see [ContigAliases::resolve])
+///
+/// Contigs that cannot be matched to the header are resolved to None with a warning; if the
+/// header has no contig lines, the contig names are kept as is
+///
+/// # Arguments
+/// * `header`: the vcf header
+/// * `contig_aliases`: the contig aliases
+/// * `source`: the kind of file the contig names come from, for logging
+fn header_contig_resolver<'a>(
+    header: &vcf::Header,
+    contig_aliases: &'a ContigAliases,
+    source: &'a str,
+) -> impl FnMut(&str) -> Option<String> + 'a {
+    let header_contigs: Vec<String> = header.contigs().keys().map(|c| c.to_string()).collect();
+    if header_contigs.is_empty() {
+        warn!(
+            "No contig lines in the vcf header, {} contig names are not harmonized",
+            source
+        );
+    }
+    move |contig: &str| {
+        if header_contigs.is_empty() {
+            return Some(contig.to_string());
+        }
+        let header_contig = contig_aliases.resolve(contig, &header_contigs);
+        match &header_contig {
+            Some(header_contig) if header_contig != contig => {
+                info!(
+                    "{} contig {} is renamed to {}",
+                    source, contig, header_contig
+                )
             }
+            Some(_) => (),
+            None => warn!(
+                "{} contig {} does not exist in the vcf header, it is ignored",
+                source, contig
+            ),
+        }
+        header_contig
+    }
+}
 
-            if !snv_only_flag || variant_type == VariantType::SNV {
-                // whether we want snv-only or not
-                // make a new VariantPosition here and put into the list
-                return Ok(Some(
-                    VariantPosition::new(
-                        &record.chromosome().to_string(),
-                        usize::try_from(record.position()).map_err(|e| e.to_string())?,
-                        read_depth as usize, // only sample in the vcf
-                        alt_depth,
-                        variant_type,
-                        zygosity,
-                    )
-                    .expect("Variant record cannot be converted to VariantPosition"),
-                ));
+/// The filters from auxiliary files, with contig names matched to the vcf header
+struct HeaderFilters {
+    /// skip records overlapping these regions
+    exclude_regions: Option<IntervalSet>,
+    /// only collect variants at these known sites
+    sites_panel: Option<SitesPanel>,
+}
+
+impl HeaderFilters {
+    fn new(header: &vcf::Header, variant_filter: &VariantFilter) -> Self {
+        let contig_aliases = &variant_filter.contig_aliases;
+        Self {
+            exclude_regions: variant_filter.exclude_regions.as_ref().map(|regions| {
+                regions.rename_contigs(header_contig_resolver(
+                    header,
+                    contig_aliases,
+                    "Excluded region",
+                ))
+            }),
+            sites_panel: variant_filter.sites_panel.as_ref().map(|panel| {
+                panel.rename_contigs(header_contig_resolver(header, contig_aliases, "Panel"))
+            }),
+        }
+    }
+}
+
+/// the locus of a record for error messages, e.g. X:38145492
+fn record_locus(record: &Record) -> String {
+    format!("{}:{}", record.chromosome(), record.position())
+}
+
+/// the 1-based start and (inclusive) end positions of a record, using INFO END if present
+pub fn record_span(record: &Record) -> (usize, usize) {
+    let start = usize::from(record.position());
+    let end = record.end().map(usize::from).unwrap_or(start);
+    (start, end)
+}
+
+/// Find the index of a sample in the vcf header
+///
+/// # Arguments:
+/// - `header`: the vcf header
+/// - `sample`: a sample name, or a 0-based sample index; the first sample is used if not given
+///
+/// # Returns:
+/// - the 0-based sample index and the sample name
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::vcfreader::{open_vcf_reader, resolve_sample};
+/// let reader = open_vcf_reader("data/family.vcf").unwrap();
+/// let (sample_index, sample_name) = resolve_sample(reader.header(), Some("1")).unwrap();
+/// assert_eq!((sample_index, sample_name.as_str()), (1, "mother"));
+/// ```
+pub fn resolve_sample(
+    header: &vcf::Header,
+    sample: Option<&str>,
+) -> Result<(usize, String), Error> {
+    let sample_names = header.sample_names();
+    let sample_index = match sample {
+        None => {
+            if sample_names.len() > 1 {
+                warn!(
+                    "Multiple samples found in the vcf file, using the first sample: {}",
+                    sample_names
+                        .get_index(0)
+                        .ok_or_else(|| Error::missing("sample columns"))?
+                );
             }
+            0
+        }
+        Some(sample) => match sample_names.get_index_of(sample) {
+            Some(sample_index) => sample_index,
+            None => sample.parse::<usize>().map_err(|_| {
+                Error::option(format!(
+                    "Sample {} not found in vcf file, available samples: {}",
+                    sample,
+                    sample_names
+                        .iter()
+                        .map(|s| s.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(",")
+                ))
+            })?,
+        },
+    };
+    let sample_name = sample_names.get_index(sample_index).ok_or_else(|| {
+        Error::option(format!(
+            "Sample index {} is out of range ({} samples in vcf file)",
+            sample_index,
+            sample_names.len()
+        ))
+    })?;
+    Ok((sample_index, sample_name.to_string()))
+}
+
+/// The index of a bgzipped vcf or BCF file
+pub enum VariantIndex {
+    /// a tabix (.tbi) index
+    TABIX(tabix::Index),
+    /// a coordinate-sorted (.csi) index, needed for contigs longer than 512 Mb
+    CSI(csi::Index),
+}
+
+impl VariantIndex {
+    /// the largest position the index can be queried for, ends beyond it are rejected
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use diploid_contam_estimator::vcfreader::find_index;
+    /// let index = find_index("data/test.vcf.gz", None).unwrap().unwrap();
+    /// assert_eq!(index.max_position(), (1 << 29) - 1);
+    /// ```
+    pub fn max_position(&self) -> usize {
+        match self {
+            VariantIndex::TABIX(_) => TABIX_MAX_POSITION,
+            VariantIndex::CSI(index) => csi_max_position(index),
+        }
+    }
+}
+
+/// the largest position of a tabix index, with its fixed minimal shift of 14 and depth of 5
+const TABIX_MAX_POSITION: usize = (1 << (14 + 3 * 5)) - 1;
+
+/// the largest position of a csi index, given by its minimal shift and depth
+fn csi_max_position(index: &csi::Index) -> usize {
+    (1 << (usize::from(index.min_shift()) + 3 * usize::from(index.depth()))) - 1
+}
+
+/// Read an index file, the index type is detected from its magic bytes
+fn read_index(index_file: &str) -> Result<VariantIndex, Error> {
+    let mut magic = [0; 4];
+    File::open(index_file)
+        .map(bgzf::Reader::new)
+        .and_then(|mut reader| reader.read_exact(&mut magic))
+        .map_err(|e| Error::io(index_file, e))?;
+    let index = match &magic {
+        b"CSI\x01" => csi::read(index_file).map(VariantIndex::CSI),
+        b"TBI\x01" => tabix::read(index_file).map(VariantIndex::TABIX),
+        _ => return Err(Error::index("Not a tabix or csi index file").with_file(index_file)),
+    };
+    index.map_err(|e| Error::index(e.to_string()).with_file(index_file))
+}
+
+/// Find the index of a bgzipped vcf or BCF file
+///
+/// # Arguments:
+/// - `vcf_file`: file path to the bgzipped vcf or BCF file
+/// - `index_file`: an explicit index file path, otherwise `<vcf_file>.csi` and
+///   `<vcf_file>.tbi` are looked up in that order
+///
+/// # Returns:
+/// - the index, or None if no index file exists
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::vcfreader::{find_index, VariantIndex};
+/// let index = find_index("data/test.vcf.gz", None).unwrap();
+/// assert!(matches!(index, Some(VariantIndex::TABIX(_))));
+/// let index = find_index("data/test.vcf.gz", Some("data/test_csi.vcf.gz.csi")).unwrap();
+/// assert!(matches!(index, Some(VariantIndex::CSI(_))));
+/// ```
+pub fn find_index(vcf_file: &str, index_file: Option<&str>) -> Result<Option<VariantIndex>, Error> {
+    if let Some(index_file) = index_file {
+        return read_index(index_file).map(Some);
+    }
+    for extension in ["csi", "tbi"] {
+        let index_file = format!("{}.{}", vcf_file, extension);
+        if metadata(&index_file).is_ok() {
+            return read_index(&index_file).map(Some);
         }
     }
     Ok(None)
 }
 
+/// the contig names of the reference sequence ids of a csi index, stored in the tabix-style
+/// auxiliary data of a vcf index, or in the order of the contigs in the vcf header otherwise
+fn csi_contig_names(index: &csi::Index, header: &vcf::Header) -> Vec<String> {
+    let aux = index.aux();
+    match aux.len() > 28 {
+        true => aux[28..]
+            .split(|&b| b == 0)
+            .filter(|name| !name.is_empty())
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .collect(),
+        false => header
+            .contigs()
+            .keys()
+            .map(|name| name.to_string())
+            .collect(),
+    }
+}
+
+/// Fetch the records of a bgzipped vcf file overlapping an interval using a csi index
+fn query_csi(
+    reader: &mut vcf::Reader<bgzf::Reader<File>>,
+    header: &vcf::Header,
+    index: &csi::Index,
+    interval: &Interval,
+) -> Result<Vec<Result<Record, Error>>, Error> {
+    let reference_sequence_id = match csi_contig_names(index, header)
+        .iter()
+        .position(|name| *name == interval.contig)
+    {
+        Some(id) => id,
+        None => return Ok(vec![]),
+    };
+    let chunks = index
+        .query(reference_sequence_id, interval.to_region()?.interval())
+        .map_err(|e| Error::index(format!("Cannot query {}: {}", interval, e)))?;
+    let mut records: Vec<Result<Record, Error>> = vec![];
+    let mut line = String::new();
+    for chunk in chunks {
+        reader.seek(chunk.start())?;
+        while reader.virtual_position() < chunk.end() {
+            line.clear();
+            if reader.read_record(&mut line)? == 0 {
+                break;
+            }
+            match parse_record(&line, header) {
+                Ok(record) => {
+                    let (start, end) = record_span(&record);
+                    if interval.overlaps(&record.chromosome().to_string(), start, end) {
+                        records.push(Ok(record));
+                    }
+                }
+                Err(e) => records.push(Err(e)),
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// the underlying reader and index of an [IndexedReader]
+enum IndexedRecords {
+    Bcf(Box<BcfReader<bgzf::Reader<File>>>, csi::Index),
+    Vcf(vcf::Reader<bgzf::Reader<File>>, VariantIndex),
+}
+
+/// A reader fetching the records overlapping an interval from an indexed bgzipped vcf or BCF file
+struct IndexedReader {
+    header: vcf::Header,
+    records: IndexedRecords,
+}
+
+impl IndexedReader {
+    fn open(vcf_file: &str, input_format: InputFormat, index: VariantIndex) -> Result<Self, Error> {
+        match (input_format, index) {
+            (InputFormat::BCF, VariantIndex::CSI(index)) => {
+                let reader = BcfReader::open(vcf_file)?;
+                Ok(Self {
+                    header: reader.header().clone(),
+                    records: IndexedRecords::Bcf(Box::new(reader), index),
+                })
+            }
+            (InputFormat::BCF, VariantIndex::TABIX(_)) => Err(Error::index(
+                "BCF files can only be fetched through a csi index",
+            )),
+            (InputFormat::VCF, index) => {
+                let mut reader = File::open(vcf_file)
+                    .map(bgzf::Reader::new)
+                    .map(vcf::Reader::new)
+                    .map_err(|e| Error::io(vcf_file, e))?;
+                let (header, _) = read_vcf_header(&mut reader)?;
+                Ok(Self {
+                    header,
+                    records: IndexedRecords::Vcf(reader, index),
+                })
+            }
+        }
+    }
+
+    /// Clamp the end of an interval to the length of its contig in the vcf header and to the
+    /// largest position of the index, as a whole-contig region (e.g. `X`) has an open end
+    ///
+    /// # Returns:
+    /// - the clamped interval, or None if it starts beyond the contig
+    fn clamp_interval(&self, interval: &Interval) -> Option<Interval> {
+        let max_position = match &self.records {
+            IndexedRecords::Bcf(_, index) => csi_max_position(index),
+            IndexedRecords::Vcf(_, index) => index.max_position(),
+        };
+        let contig_length = self
+            .header
+            .contigs()
+            .get(interval.contig.as_str())
+            .and_then(|contig| contig.len())
+            .and_then(|length| usize::try_from(length).ok())
+            .unwrap_or(max_position);
+        let end = interval.end.min(contig_length).min(max_position);
+        Interval::new(&interval.contig, interval.start, end).ok()
+    }
+
+    /// fetch the records overlapping an interval, records that cannot be parsed are errors
+    fn fetch(&mut self, interval: &Interval) -> Result<Vec<Result<Record, Error>>, Error> {
+        let interval = match self.clamp_interval(interval) {
+            Some(interval) => interval,
+            None => {
+                info!("Skipping {} beyond the end of the contig", interval);
+                return Ok(vec![]);
+            }
+        };
+        match &mut self.records {
+            IndexedRecords::Bcf(reader, index) => Ok(reader
+                .query(index, &interval)?
+                .into_iter()
+                .map(Ok)
+                .collect()),
+            IndexedRecords::Vcf(reader, VariantIndex::CSI(index)) => {
+                query_csi(reader, &self.header, index, &interval)
+            }
+            IndexedRecords::Vcf(reader, VariantIndex::TABIX(index)) => {
+                // contigs without records are not in the index
+                if !index
+                    .header()
+                    .reference_sequence_names()
+                    .contains(&interval.contig)
+                {
+                    info!("Skipping {} with no vcf records", interval);
+                    return Ok(vec![]);
+                }
+                let query = reader
+                    .query(&self.header, index, &interval.to_region()?)
+                    .map_err(|e| Error::index(format!("Cannot query {}: {}", interval, e)))?;
+                Ok(query
+                    .map(|result| {
+                        result.map_err(|e| Error::parse(format!("Cannot parse vcf record: {}", e)))
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
 /// Colelcting variants from a vcf file
 ///
 /// # Arguments:
-/// - `vcf_file`: file path to the vcf file we want to parse
-/// - `snv_only_flag`: boolean flag indicating whether we shopuld only look at SNV instead of both SNV and indel
-/// - `depth_threshold`: we will skip any variants with DP tag lower than this threshold
+/// - `vcf_file`: file path to the vcf file we want to parse, or `-` for stdin
+/// - `variant_filter`: the options for selecting records and variants (e.g. SNV only, minimum depth)
+/// - `regions`: the intervals to fetch variants from, all variants are used if not given
+/// - `index_file`: an explicit tabix or csi index file, otherwise `<vcf_file>.csi` or `<vcf_file>.tbi` is used if it exists
+/// - `sample`: a sample name, or a 0-based sample index; the first sample is used if not given
 ///
 /// # Returns:
-/// - a list of variants that passed the given filters
+/// - the variants that passed the given filters, and the record counts of each FILTER value
 ///
 /// # Examples
 ///
 /// ```
-/// use diploid_contam_estimator::vcfreader::build_variant_list;
-/// let variant_list = build_variant_list("data/test.vcf", true, 100, vec![]).unwrap();
-/// assert_eq!(variant_list.len(), 7);
+/// use diploid_contam_estimator::vcfreader::{build_variant_list, VariantFilter};
+/// let sample_variants =
+///     build_variant_list("data/test.vcf", &VariantFilter::new(true, 100), None, None, None).unwrap();
+/// assert_eq!(sample_variants.variants.len(), 7);
 /// ```
 pub fn build_variant_list(
     vcf_file: &str,
-    snv_only_flag: bool,
-    depth_threshold: usize,
-    regions: Vec<String>,
-) -> Result<Vec<VariantPosition>, String> {
-    let mut variant_list: Vec<VariantPosition> = Vec::new();
-    let is_gz_input = vcf_file.ends_with(".gz");
-    let is_fetch: bool = !regions.is_empty();
-    let exit_code: Result<i8, String> = match (is_gz_input, is_fetch) {
-        (true, true) => {
-            let vcf_file_idx_fn = format!("{}.tbi", vcf_file);
-            if metadata(&vcf_file_idx_fn).is_ok() {
-                let index = tabix::read(vcf_file_idx_fn).map_err(|e| e.to_string())?;
-                let mut reader = File::open(vcf_file)
-                    .map(bgzf::Reader::new)
-                    .map(vcf::Reader::new)
-                    .map_err(|e| e.to_string())?;
+    variant_filter: &VariantFilter,
+    regions: Option<&IntervalSet>,
+    index_file: Option<&str>,
+    sample: Option<&str>,
+) -> Result<SampleVariants, Error> {
+    let mut sample_variants =
+        collect_variants(vcf_file, variant_filter, regions, index_file, |header| {
+            Ok(vec![resolve_sample(header, sample)?])
+        })
+        .map_err(|e| e.with_file(vcf_file))?;
+    sample_variants
+        .pop()
+        .ok_or_else(|| Error::missing("sample columns").with_file(vcf_file))
+}
 
-                let raw_header = reader.read_header().map_err(|e| e.to_string())?;
-                let header = raw_header
-                    .parse()
-                    .map_err(|_| "Cannot parse header properly".to_string())?;
+/// Colelcting variants of every sample from a multi-sample vcf file in a single pass
+///
+/// # Arguments:
+/// - `vcf_file`: file path to the vcf file we want to parse, or `-` for stdin
+/// - `variant_filter`: the options for selecting records and variants (e.g. SNV only, minimum depth)
+/// - `regions`: the intervals to fetch variants from, all variants are used if not given
+/// - `index_file`: an explicit tabix or csi index file, otherwise `<vcf_file>.csi` or `<vcf_file>.tbi` is used if it exists
+///
+/// # Returns:
+/// - the variants that passed the given filters for each sample, in the order of the vcf header
+///
+/// # Examples
+///
+/// ```
+/// use diploid_contam_estimator::vcfreader::{build_variant_lists, VariantFilter};
+/// let sample_variants =
+///     build_variant_lists("data/family.vcf", &VariantFilter::default(), None, None).unwrap();
+/// assert_eq!(sample_variants[0].sample_name, "proband");
+/// assert_eq!(sample_variants[1].variants.len(), 9);
+/// ```
+pub fn build_variant_lists(
+    vcf_file: &str,
+    variant_filter: &VariantFilter,
+    regions: Option<&IntervalSet>,
+    index_file: Option<&str>,
+) -> Result<Vec<SampleVariants>, Error> {
+    collect_variants(vcf_file, variant_filter, regions, index_file, |header| {
+        let sample_names = header.sample_names();
+        if sample_names.is_empty() {
+            return Err(Error::missing("sample columns"));
+        }
+        Ok(sample_names
+            .iter()
+            .enumerate()
+            .map(|(sample_index, sample_name)| (sample_index, sample_name.to_string()))
+            .collect())
+    })
+    .map_err(|e| e.with_file(vcf_file))
+}
+
+/// Read the vcf records once and collect the variants of each of the selected samples
+///
+/// # Arguments:
+/// - `vcf_file`: file path to the vcf file we want to parse, or `-` for stdin
+/// - `variant_filter`: the options for selecting records and variants
+/// - `regions`: the intervals to fetch variants from, all variants are used if not given
+/// - `index_file`: an explicit tabix or csi index file, otherwise `<vcf_file>.csi` or `<vcf_file>.tbi` is used if it exists
+/// - `select_samples`: picks the (index, name) of the samples to be collected from the vcf header
+fn collect_variants<F>(
+    vcf_file: &str,
+    variant_filter: &VariantFilter,
+    regions: Option<&IntervalSet>,
+    index_file: Option<&str>,
+    select_samples: F,
+) -> Result<Vec<SampleVariants>, Error>
+where
+    F: Fn(&vcf::Header) -> Result<Vec<(usize, String)>, Error>,
+{
+    let mut samples: Vec<(usize, String)> = vec![];
+    let mut variant_lists: Vec<Vec<VariantPosition>> = vec![];
+    let mut filter_counts: BTreeMap<String, FilterCount> = BTreeMap::new();
+    let mut excluded_count: usize = 0;
+    let mut panel_found_sites: HashSet<KnownSite> = HashSet::new();
+    // rejections of each sample, and of the records that cannot be read at all
+    let mut rejection_counts: Vec<RejectionCounts> = vec![];
+    let mut malformed_record_count: usize = 0;
+    let mut skip_malformed_record = |error: Error| -> Result<(), Error> {
+        match variant_filter.lenient {
+            true => {
+                malformed_record_count += 1;
+                Ok(())
+            }
+            false => Err(error),
+        }
+    };
+    let mut collect_record = |record: &Record,
+                              samples: &[(usize, String)],
+                              header_filters: &HeaderFilters,
+                              variant_lists: &mut Vec<Vec<VariantPosition>>,
+                              rejection_counts: &mut Vec<RejectionCounts>|
+     -> Result<(), Error> {
+        // the FILTER values are tallied before the known-sites panel and excluded regions,
+        // so the counts do not depend on those options
+        let filter_values = record_filter_values(record);
+        let accepted = variant_filter.accepts_filters(&filter_values);
+        for filter_value in filter_values {
+            let filter_count = filter_counts.entry(filter_value).or_default();
+            match accepted {
+                true => filter_count.accepted += 1,
+                false => filter_count.rejected += 1,
+            }
+        }
+        let panel_sites: Vec<&KnownSite> = match &header_filters.sites_panel {
+            Some(sites_panel) => {
+                let panel_sites = sites_panel.record_sites(record);
+                if panel_sites.is_empty() {
+                    return Ok(());
+                }
+                panel_found_sites.extend(panel_sites.iter().map(|&site| site.clone()));
+                panel_sites
+            }
+            None => vec![],
+        };
+        if let Some(exclude_regions) = &header_filters.exclude_regions {
+            let (start, end) = record_span(record);
+            if exclude_regions.overlaps(&record.chromosome().to_string(), start, end) {
+                excluded_count += 1;
+                return Ok(());
+            }
+        }
+        if !accepted {
+            return Ok(());
+        }
+        for (((sample_index, _), variant_list), sample_rejection_counts) in samples
+            .iter()
+            .zip(variant_lists.iter_mut())
+            .zip(rejection_counts.iter_mut())
+        {
+            let variant = match filter_variants(record, *sample_index, variant_filter) {
+                Ok(variant) => variant,
+                Err(rejection) => {
+                    rejection
+                        .count(sample_rejection_counts, variant_filter.lenient)
+                        .map_err(|e| e.with_record(&record_locus(record)))?;
+                    continue;
+                }
+            };
+            if let Some(variant) = variant {
+                // the allele of the sample must match the panel in a multi-allelic record
+                if let Some(sites_panel) = &header_filters.sites_panel {
+                    if !sites_panel.contains(
+                        &variant.contig,
+                        variant.position,
+                        variant.ref_allele.as_deref().unwrap_or_default(),
+                        variant.alt_allele.as_deref().unwrap_or_default(),
+                    ) {
+                        continue;
+                    }
+                }
+                variant_list.push(variant);
+            } else if !panel_sites.is_empty() {
+                // hom-ref calls are only informative at the known polymorphic sites
+                match filter_reference_sites(record, *sample_index, variant_filter, &panel_sites) {
+                    Ok(variants) => variant_list.extend(variants),
+                    // e.g. a hom-ref record without allele depths, which is not an error
+                    Err(Rejection::MissingAlleleDepth(_)) => {
+                        sample_rejection_counts.missing_allele_depth += 1
+                    }
+                    Err(rejection) => rejection
+                        .count(sample_rejection_counts, variant_filter.lenient)
+                        .map_err(|e| e.with_record(&record_locus(record)))?,
+                }
+            }
+        }
+        Ok(())
+    };
+    // regions are fetched through the index of an indexed bgzipped input, otherwise all records
+    // are streamed and filtered against the regions
+    let indexed_input = match regions.is_some() && vcf_file != STDIN {
+        true => match (detect_format(vcf_file)?, find_index(vcf_file, index_file)?) {
+            ((Compression::BGZF, InputFormat::BCF), Some(VariantIndex::CSI(index))) => {
+                Some((InputFormat::BCF, VariantIndex::CSI(index)))
+            }
+            ((Compression::BGZF, InputFormat::VCF), Some(index)) => Some((InputFormat::VCF, index)),
+            _ => {
+                info!(
+                    "No usable index found for {}, streaming all records to filter the regions",
+                    vcf_file
+                );
+                None
+            }
+        },
+        false => None,
+    };
+    // the contig names of the regions, excluded regions and known-sites panel are matched to
+    // the vcf header once it is read
+    let harmonize = |header: &vcf::Header, regions: &IntervalSet| {
+        regions.rename_contigs(header_contig_resolver(
+            header,
+            &variant_filter.contig_aliases,
+            "Region",
+        ))
+    };
+    let exit_code: Result<i8, Error> = match (regions, indexed_input) {
+        (Some(regions), Some((input_format, index))) => {
+            let mut reader = IndexedReader::open(vcf_file, input_format, index)?;
+            samples = select_samples(&reader.header)?;
+            variant_lists = vec![vec![]; samples.len()];
+            rejection_counts = vec![RejectionCounts::default(); samples.len()];
+            let regions = harmonize(&reader.header, regions);
+            let header_filters = HeaderFilters::new(&reader.header, variant_filter);
+            let mut previous_interval: Option<Interval> = None;
+            for interval in regions.intervals() {
                 let mut variant_count: usize = 0;
-                for region in regions.iter() {
-                    let query = reader.query(
-                        &header,
-                        &index,
-                        &region
-                            .parse()
-                            .map_err(|_| "Cannot fetch record properly".to_string())?,
-                    );
-                    if query.is_ok() {
-                        for record in query.map_err(|e| e.to_string())? {
-                            let variant = filter_variants(
-                                &record.map_err(|e| e.to_string())?,
-                                depth_threshold,
-                                snv_only_flag,
-                            )
-                            .unwrap();
-                            if variant.is_some() {
-                                variant_list.push(variant.ok_or("No variant")?);
-                            }
-                            variant_count += 1;
+                for result in reader.fetch(&interval)? {
+                    let record = match result {
+                        Ok(record) => record,
+                        Err(error) => {
+                            skip_malformed_record(error)?;
+                            continue;
+                        }
+                    };
+                    // a record spanning several intervals was fetched with the previous interval
+                    if let Some(previous_interval) = &previous_interval {
+                        if previous_interval.contig == interval.contig
+                            && usize::from(record.position()) <= previous_interval.end
+                        {
+                            continue;
                         }
-                        info!("Fetched {} variants from {}", variant_count, region);
-                    } else {
-                        info!("Skipping {} with no vcf records", region);
                     }
+                    collect_record(
+                        &record,
+                        &samples,
+                        &header_filters,
+                        &mut variant_lists,
+                        &mut rejection_counts,
+                    )?;
+                    variant_count += 1;
                 }
-                Ok(0)
-            } else {
-                Err(format!("Missing tabix file {}", vcf_file_idx_fn))
+                info!("Fetched {} variants from {}", variant_count, interval);
+                previous_interval = Some(interval);
             }
-        }
-        (true, false) => {
-            // in the case of gzip-ed vcf input, we will read all variants
-            let mut reader = File::open(vcf_file)
-                .map(bgzf::Reader::new)
-                .map(BufReader::new)
-                .map(vcf::Reader::new)
-                .map_err(|_e| "Bad reader".to_string())?;
-
-            let raw_header = reader.read_header().map_err(|e| e.to_string())?;
-            let header = raw_header
-                .parse()
-                .map_err(|_| "Cannot parse header properly".to_string())?;
-            let mut variants = Vec::from_iter(
-                reader
-                    .records(&header)
-                    .map(|result| result.expect("Cannot read vcf record"))
-                    .filter_map(|record| {
-                        filter_variants(&record, depth_threshold, snv_only_flag).unwrap()
-                    }),
-            );
-            variant_list.append(&mut variants);
             Ok(0)
         }
-        _ => {
-            if is_fetch {
-                panic!("Fetching bed loci from non bgzipped vcf file is not supported");
+        (regions, _) => {
+            let mut reader = open_vcf_reader(vcf_file)?;
+            samples = select_samples(reader.header())?;
+            variant_lists = vec![vec![]; samples.len()];
+            rejection_counts = vec![RejectionCounts::default(); samples.len()];
+            let regions = regions.map(|regions| harmonize(reader.header(), regions));
+            let header_filters = HeaderFilters::new(reader.header(), variant_filter);
+            for result in reader.by_ref() {
+                let record = match result {
+                    Ok(record) => record,
+                    Err(error) => {
+                        skip_malformed_record(error)?;
+                        continue;
+                    }
+                };
+                if let Some(regions) = &regions {
+                    let (start, end) = record_span(&record);
+                    if !regions.overlaps(&record.chromosome().to_string(), start, end) {
+                        continue;
+                    }
+                }
+                collect_record(
+                    &record,
+                    &samples,
+                    &header_filters,
+                    &mut variant_lists,
+                    &mut rejection_counts,
+                )?;
             }
-            // in the case of non gz vcf input, we will read all variants
-            let mut reader = File::open(vcf_file)
-                .map(BufReader::new)
-                .map(vcf::Reader::new)
-                .map_err(|e| e.to_string())?;
-
-            let raw_header = reader.read_header().map_err(|e| e.to_string())?;
-            let header = raw_header
-                .parse()
-                .map_err(|_| "Cannot parse header properly".to_string())?;
-            let mut variants = Vec::from_iter(
-                reader
-                    .records(&header)
-                    .map(|result| result.expect("Cannot read vcf record"))
-                    .filter_map(|record| {
-                        filter_variants(&record, depth_threshold, snv_only_flag).unwrap()
-                    }),
-            );
-            variant_list.append(&mut variants);
             Ok(0)
         }
     };
+    exit_code?;
 
-    info!(
-        "Collected {} variants from {}",
-        variant_list.len(),
-        vcf_file
-    );
-    match exit_code {
-        Ok(_) => Ok(variant_list),
-        Err(e) => Err(e),
+    if variant_filter.exclude_regions.is_some() {
+        info!("Skipped {} records in the excluded regions", excluded_count);
     }
+    for (filter_value, filter_count) in filter_counts.iter() {
+        info!(
+            "FILTER {}: {} records accepted, {} records rejected",
+            filter_value, filter_count.accepted, filter_count.rejected
+        );
+    }
+    if let Some(sites_panel) = &variant_filter.sites_panel {
+        info!(
+            "Found {} of {} panel sites in {}",
+            panel_found_sites.len(),
+            sites_panel.len(),
+            vcf_file
+        );
+    }
+    if malformed_record_count > 0 {
+        warn!(
+            "Skipped {} malformed records in {}",
+            malformed_record_count, vcf_file
+        );
+    }
+    Ok(samples
+        .into_iter()
+        .zip(variant_lists)
+        .zip(rejection_counts)
+        .map(|(((_, sample_name), variants), mut rejection_counts)| {
+            info!(
+                "Collected {} variants for sample {} from {}",
+                variants.len(),
+                sample_name,
+                vcf_file
+            );
+            rejection_counts.malformed += malformed_record_count;
+            info!(
+                "Skipped {} no-calls, {} calls without allele depths, {} calls with DP below the sum of allele depths and {} malformed records or calls for sample {}",
+                rejection_counts.no_call,
+                rejection_counts.missing_allele_depth,
+                rejection_counts.depth_below_allele_depths,
+                rejection_counts.malformed,
+                sample_name
+            );
+            let panel_count = variant_filter
+                .sites_panel
+                .as_ref()
+                .map(|sites_panel| PanelCount {
+                    panel_sites: sites_panel.len(),
+                    found: panel_found_sites.len(),
+                    used: variants.len(),
+                });
+            SampleVariants {
+                sample_name,
+                variants,
+                filter_counts: filter_counts.clone(),
+                panel_count,
+                rejection_counts,
+            }
+        })
+        .collect())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use rstest::*;
+    use std::io::Read;
+
+    /// the interval set of the region strings of a test case, or None if there is no region
+    fn intervals(regions: &[String]) -> Option<IntervalSet> {
+        let regions: Vec<&str> = regions.iter().map(|region| region.as_str()).collect();
+        match regions.is_empty() {
+            true => None,
+            false => Some(IntervalSet::from_regions(&regions).unwrap()),
+        }
+    }
+
+    #[rstest]
+    #[case(false, 0, vec![("proband", 14), ("mother", 9)])]
+    #[case(true, 0, vec![("proband", 7), ("mother", 4)])]
+    fn test_build_variant_lists(
+        #[case] snv_only_flag: bool,
+        #[case] depth_threshold: usize,
+        #[case] expected: Vec<(&str, usize)>,
+    ) {
+        let sample_variants = build_variant_lists(
+            "data/family.vcf",
+            &VariantFilter::new(snv_only_flag, depth_threshold),
+            None,
+            None,
+        )
+        .unwrap();
+        let counts: Vec<(&str, usize)> = sample_variants
+            .iter()
+            .map(|sample_variants| {
+                (
+                    sample_variants.sample_name.as_str(),
+                    sample_variants.variants.len(),
+                )
+            })
+            .collect();
+        assert_eq!(counts, expected);
+    }
 
     #[rstest]
     #[case(false, 0, 14, vec![])] // all variants
@@ -261,11 +1513,96 @@ mod tests {
         #[case] regions: Vec<String>,
     ) {
         let vcf_file = "data/test.vcf";
-        let variant_list =
-            build_variant_list(&vcf_file, snv_only_flag, depth_threshold, regions).unwrap();
+        let variant_list = build_variant_list(
+            vcf_file,
+            &VariantFilter::new(snv_only_flag, depth_threshold),
+            intervals(&regions).as_ref(),
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
         assert_eq!(variant_list.len(), expected_number_variants);
     }
 
+    #[rstest]
+    #[case(100, Some((Some(100), 1)))] // 0|1
+    #[case(200, Some((Some(100), 0)))] // 1|0
+    #[case(3000, None)] // unphased
+    #[case(3100, Some((None, 1)))] // phased without PS
+    #[case(3200, None)] // homozygous
+    #[case(3300, None)] // unphased 1/0
+    fn test_build_variant_list_phase(
+        #[case] position: usize,
+        #[case] expected: Option<(Option<i32>, usize)>,
+    ) {
+        let variants = build_variant_list(
+            "data/phased.vcf",
+            &VariantFilter::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
+        let variant = variants
+            .iter()
+            .find(|variant| variant.position == position)
+            .unwrap();
+        let phase = variant
+            .phase
+            .map(|phase| (phase.phase_set, phase.alt_haplotype));
+        assert_eq!(phase, expected);
+    }
+
+    #[rstest]
+    #[case(None, None, vec![100, 200, 300, 400, 500, 600])]
+    #[case(Some(20.0), None, vec![100, 300, 400, 500, 600])]
+    #[case(Some(5.0), None, vec![100, 300, 400, 600])]
+    #[case(None, Some(3.0), vec![100, 300, 400, 600])]
+    fn test_build_variant_list_strand_bias(
+        #[case] max_fisher_strand: Option<f64>,
+        #[case] max_strand_odds_ratio: Option<f64>,
+        #[case] expected: Vec<usize>,
+    ) {
+        let mut variant_filter = VariantFilter::new(false, 0);
+        variant_filter.max_fisher_strand = max_fisher_strand;
+        variant_filter.max_strand_odds_ratio = max_strand_odds_ratio;
+        let variants = build_variant_list("data/strand.vcf", &variant_filter, None, None, None)
+            .unwrap()
+            .variants;
+        let positions: Vec<usize> = variants.iter().map(|variant| variant.position).collect();
+        assert_eq!(positions, expected);
+        // the record at 600 has no ADF/ADR
+        let with_strand_counts = variants
+            .iter()
+            .filter(|variant| variant.strand_counts.is_some())
+            .count();
+        assert_eq!(with_strand_counts, expected.len() - 1);
+    }
+
+    #[rstest]
+    #[case(DEFAULT_VARIANT_TYPES.to_vec(), false, "SNV,MNV,SNV,indel,indel,complex,MNV")]
+    #[case(VariantType::ALL.to_vec(), false, "SNV,MNV,SNV,indel,indel,complex,symbolic,symbolic,MNV")]
+    #[case(VariantType::ALL.to_vec(), true, "SNV,SNV")] // snv only
+    #[case(vec![VariantType::MNV, VariantType::COMPLEX], false, "MNV,complex,MNV")]
+    fn test_build_variant_list_variant_types(
+        #[case] variant_types: Vec<VariantType>,
+        #[case] snv_only_flag: bool,
+        #[case] expected: &str,
+    ) {
+        let mut variant_filter = VariantFilter::new(snv_only_flag, 0);
+        variant_filter.variant_types = variant_types;
+        let variant_types: Vec<String> =
+            build_variant_list("data/variant_types.vcf", &variant_filter, None, None, None)
+                .unwrap()
+                .variants
+                .iter()
+                .map(|variant| variant.variant_type.to_string())
+                .collect();
+        assert_eq!(variant_types.join(","), expected);
+    }
+
     #[rstest]
     #[case(false, 0, 14, vec![])] // all variants
     #[case(true, 0, 7, vec![])] // all SNV
@@ -275,6 +1612,8 @@ mod tests {
     #[case(false, 10, 1, vec![String::from("X:38144665-38144669")])] // test fetch
     #[case(false, 10, 7, vec![String::from("X:38145491-38145540")])] // test fetch
     #[case(false, 10, 8, vec![String::from("X:38144665-38144669"), String::from("X:38145491-38145540")])] // test fetch
+    #[case(false, 10, 7, vec![String::from("X:38145491-38145540"), String::from("X:38145491-38145540")])] // duplicated regions
+    #[case(false, 10, 7, vec![String::from("X:38145491-38145520"), String::from("X:38145510-38145540")])] // overlapping regions
     fn test_build_variant_list_from_vcf_gz(
         #[case] snv_only_flag: bool,
         #[case] depth_threshold: usize,
@@ -282,23 +1621,486 @@ mod tests {
         #[case] regions: Vec<String>,
     ) {
         let vcf_file = "data/test.vcf.gz";
-        let variant_list =
-            build_variant_list(&vcf_file, snv_only_flag, depth_threshold, regions).unwrap();
+        let variant_list = build_variant_list(
+            vcf_file,
+            &VariantFilter::new(snv_only_flag, depth_threshold),
+            intervals(&regions).as_ref(),
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
         assert_eq!(variant_list.len(), expected_number_variants);
     }
 
+    #[rstest]
+    #[case(false, 0, 14, vec![])] // all variants
+    #[case(true, 0, 7, vec![])] // all SNV
+    #[case(true, 1000, 6, vec![])] // all high depth SNV
+    #[case(false, 10, 1, vec![String::from("X:38144665-38144669")])] // test fetch
+    #[case(false, 10, 7, vec![String::from("X:38145491-38145540")])] // test fetch
+    #[case(false, 10, 8, vec![String::from("X:38144665-38144669"), String::from("X:38145491-38145540")])] // test fetch
+    #[case(false, 10, 7, vec![String::from("X:38145491-38145540"), String::from("X:38145491-38145540")])] // duplicated regions
+    #[case(false, 10, 7, vec![String::from("X:38145491-38145520"), String::from("X:38145510-38145540")])] // overlapping regions
+    fn test_build_variant_list_from_bcf(
+        #[case] snv_only_flag: bool,
+        #[case] depth_threshold: usize,
+        #[case] expected_number_variants: usize,
+        #[case] regions: Vec<String>,
+    ) {
+        let bcf_file = "data/test.bcf";
+        let variant_list = build_variant_list(
+            bcf_file,
+            &VariantFilter::new(snv_only_flag, depth_threshold),
+            intervals(&regions).as_ref(),
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
+        assert_eq!(variant_list.len(), expected_number_variants);
+    }
+
+    #[rstest]
+    #[case("data/test_csi.vcf.gz", None, vec![String::from("X:38144665-38144669")], 1)] // <vcf>.csi
+    #[case("data/test_csi.vcf.gz", None, vec![String::from("X:38145491-38145540")], 7)]
+    #[case("data/test_csi.vcf.gz", None, vec![String::from("1:38145491-38145540")], 0)]
+    #[case("data/test.vcf.gz", Some("data/test_csi.vcf.gz.csi"), vec![String::from("X:38144665-38144669"), String::from("X:38145491-38145540")], 8)]
+    #[case("data/test.vcf.gz", Some("data/test.vcf.gz.tbi"), vec![String::from("X:38145491-38145540")], 7)]
+    #[case("data/test.bcf", Some("data/test.bcf.csi"), vec![String::from("X:38145491-38145540")], 7)]
+    #[case("data/test.vcf.gz", Some("data/test.vcf.gz.tbi"), vec![String::from("X")], 14)] // whole contig
+    #[case("data/test_csi.vcf.gz", None, vec![String::from("X")], 14)]
+    #[case("data/test.bcf", Some("data/test.bcf.csi"), vec![String::from("X")], 14)]
+    #[case("data/test.vcf.gz", Some("data/test.vcf.gz.tbi"), vec![String::from("X:38145491")], 11)] // open end
+    #[case("data/test.vcf.gz", Some("data/test.vcf.gz.tbi"), vec![String::from("MT")], 0)] // contig without records
+    #[case("data/test.bcf", Some("data/test.bcf.csi"), vec![String::from("X:200000000-200000010")], 0)] // beyond the contig
+    fn test_build_variant_list_with_index(
+        #[case] vcf_file: &str,
+        #[case] index_file: Option<&str>,
+        #[case] regions: Vec<String>,
+        #[case] expected_number_variants: usize,
+    ) {
+        let variant_list = build_variant_list(
+            vcf_file,
+            &VariantFilter::new(false, 10),
+            intervals(&regions).as_ref(),
+            index_file,
+            None,
+        )
+        .unwrap()
+        .variants;
+        assert_eq!(variant_list.len(), expected_number_variants);
+    }
+
+    #[test]
+    fn test_build_variant_list_without_index() {
+        // a bgzipped vcf file without index falls back to streaming
+        let vcf_file = std::env::temp_dir().join("unindexed.vcf.gz");
+        std::fs::copy("data/test.vcf.gz", &vcf_file).unwrap();
+        let vcf_file = vcf_file.to_str().unwrap();
+        assert!(find_index(vcf_file, None).unwrap().is_none());
+        let variant_list = build_variant_list(
+            vcf_file,
+            &VariantFilter::new(false, 10),
+            intervals(&[String::from("X:38145491-38145540")]).as_ref(),
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
+        std::fs::remove_file(vcf_file).unwrap();
+        assert_eq!(variant_list.len(), 7);
+    }
+
     #[test]
-    #[should_panic(expected = "Fetching bed loci from non bgzipped")]
-    fn test_build_variant_list_exception() {
+    #[should_panic(expected = "data/test.vcf.gz: Not a tabix or csi index file")]
+    fn test_find_index_exception() {
+        find_index("data/test.vcf.gz", Some("data/test.vcf.gz")).unwrap();
+    }
+
+    #[rstest]
+    #[case("data/test.vcf", vec![], vec![String::from("X:38145491-38145540")], 7)] // 14 - 7
+    #[case("data/test.vcf", vec![String::from("X:38145491-38145540")], vec![String::from("X:38145491-38145520")], 3)]
+    #[case("data/test.vcf.gz", vec![String::from("X:38145491-38145540")], vec![String::from("X:38145491-38145520")], 3)] // tabix query
+    #[case("data/test.bcf", vec![], vec![String::from("X:1-38145000"), String::from("X:38145491-38145540")], 6)] // 14 - 1 - 7
+    fn test_build_variant_list_exclude_regions(
+        #[case] vcf_file: &str,
+        #[case] regions: Vec<String>,
+        #[case] exclude_regions: Vec<String>,
+        #[case] expected_number_variants: usize,
+    ) {
+        let variant_filter = VariantFilter {
+            exclude_regions: intervals(&exclude_regions),
+            ..VariantFilter::new(false, 10)
+        };
+        let variant_list = build_variant_list(
+            vcf_file,
+            &variant_filter,
+            intervals(&regions).as_ref(),
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
+        assert_eq!(variant_list.len(), expected_number_variants);
+    }
+
+    #[rstest]
+    #[case("data/test.vcf", vec![String::from("chrX:38145491-38145540")], vec![], 7)] // chr prefix
+    #[case("data/test.vcf.gz", vec![String::from("chrX:38145491-38145540")], vec![], 7)] // tabix query
+    #[case("data/test.bcf", vec![String::from("NC_000023.11:38145491-38145540")], vec![], 7)] // alias table
+    #[case("data/test.vcf.gz", vec![String::from("chrX:38145491-38145540"), String::from("chrUn:1-100")], vec![], 7)] // missing contig
+    #[case("data/test.vcf", vec![], vec![String::from("chrX:38145491-38145540")], 7)] // 14 - 7
+    fn test_build_variant_list_contig_aliases(
+        #[case] vcf_file: &str,
+        #[case] regions: Vec<String>,
+        #[case] exclude_regions: Vec<String>,
+        #[case] expected_number_variants: usize,
+    ) {
+        let variant_filter = VariantFilter {
+            exclude_regions: intervals(&exclude_regions),
+            contig_aliases: ContigAliases::from_file("data/contig_aliases.tsv").unwrap(),
+            ..VariantFilter::new(false, 10)
+        };
+        let variant_list = build_variant_list(
+            vcf_file,
+            &variant_filter,
+            intervals(&regions).as_ref(),
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
+        assert_eq!(variant_list.len(), expected_number_variants);
+    }
+
+    #[rstest]
+    #[case("data/test.vcf", "data/panel.tsv", vec![], (6, 4, 4))] // allele mismatch and chrX in the panel
+    #[case("data/test.vcf", "data/panel.vcf", vec![], (7, 5, 5))] // multi-allelic panel record
+    #[case("data/test.bcf", "data/panel.vcf", vec![String::from("X:38145492-38145500")], (7, 3, 3))]
+    fn test_build_variant_list_sites_panel(
+        #[case] vcf_file: &str,
+        #[case] panel_file: &str,
+        #[case] regions: Vec<String>,
+        #[case] expected: (usize, usize, usize),
+    ) {
+        let variant_filter = VariantFilter {
+            sites_panel: Some(SitesPanel::from_file(panel_file).unwrap()),
+            ..VariantFilter::new(false, 10)
+        };
+        let sample_variants = build_variant_list(
+            vcf_file,
+            &variant_filter,
+            intervals(&regions).as_ref(),
+            None,
+            None,
+        )
+        .unwrap();
+        let panel_count = sample_variants.panel_count.unwrap();
+        assert_eq!(
+            (panel_count.panel_sites, panel_count.found, panel_count.used),
+            expected
+        );
+        assert_eq!(sample_variants.variants.len(), expected.2);
+    }
+
+    #[rstest]
+    #[case(None, false, vec![(38145494, Zygosity::HETEROZYGOUS, 38, 18), (38146000, Zygosity::HETEROZYGOUS, 40, 20)], None)]
+    #[case(
+        Some("data/panel.tsv"),
+        false,
+        vec![
+            (38145494, Zygosity::HETEROZYGOUS, 38, 18),
+            (38145516, Zygosity::HOM_REF, 33, 3), // hom-ref record with the panel alt allele
+        ],
+        Some(2)
+    )] // the reference blocks have no per-site allele depths
+    #[case(
+        Some("data/panel.tsv"),
+        true,
+        vec![
+            (38145492, Zygosity::HOM_REF, 40, 0), // reference block, MIN_DP without alt reads
+            (38145494, Zygosity::HETEROZYGOUS, 38, 18),
+            (38145497, Zygosity::HOM_REF, 30, 0), // reference block without AD
+            (38145516, Zygosity::HOM_REF, 33, 3),
+            (38145911, Zygosity::HOM_REF, 48, 0),
+        ],
+        Some(5)
+    )]
+    fn test_build_variant_list_gvcf(
+        #[case] panel_file: Option<&str>,
+        #[case] reference_block_depth: bool,
+        #[case] expected: Vec<(usize, Zygosity, usize, usize)>,
+        #[case] expected_used: Option<usize>,
+    ) {
+        let variant_filter = VariantFilter {
+            sites_panel: panel_file.map(|panel_file| SitesPanel::from_file(panel_file).unwrap()),
+            reference_block_depth,
+            ..VariantFilter::new(false, 10)
+        };
+        let sample_variants =
+            build_variant_list("data/test.g.vcf", &variant_filter, None, None, None).unwrap();
+        let variants: Vec<(usize, Zygosity, usize, usize)> = sample_variants
+            .variants
+            .iter()
+            .map(|variant| {
+                (
+                    variant.position,
+                    variant.zygosity.clone(),
+                    variant.total_read_depth,
+                    variant.alt_depth,
+                )
+            })
+            .collect();
+        assert_eq!(variants, expected);
+        assert_eq!(
+            sample_variants
+                .panel_count
+                .map(|panel_count| (panel_count.found, panel_count.used)),
+            expected_used.map(|used| (5, used))
+        );
+    }
+
+    #[test]
+    fn test_build_variant_list_lenient() {
+        let variant_filter = VariantFilter {
+            lenient: true,
+            ..VariantFilter::default()
+        };
+        let sample_variants =
+            build_variant_list("data/malformed.vcf", &variant_filter, None, None, None).unwrap();
+        assert_eq!(sample_variants.variants.len(), 2);
+        assert_eq!(
+            sample_variants.rejection_counts,
+            RejectionCounts {
+                no_call: 1,
+                missing_allele_depth: 1,
+                depth_below_allele_depths: 1,
+                malformed: 2, // an unparsable position and a haploid genotype
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "data/malformed.vcf, record X:38145497: Missing FORMAT/AD")]
+    fn test_build_variant_list_strict() {
         build_variant_list(
-            "data/test.vcf",
-            true,
-            10,
-            vec![String::from("1:38145491-38145540")],
+            "data/malformed.vcf",
+            &VariantFilter::default(),
+            None,
+            None,
+            None,
         )
         .unwrap();
     }
 
+    #[rstest]
+    #[case("data/test.vcf", Compression::NONE, InputFormat::VCF)]
+    #[case("data/test.vcf.gz", Compression::BGZF, InputFormat::VCF)]
+    #[case("data/test.bcf", Compression::BGZF, InputFormat::BCF)]
+    fn test_detect_format(
+        #[case] vcf_file: &str,
+        #[case] expected_compression: Compression,
+        #[case] expected_format: InputFormat,
+    ) {
+        assert_eq!(
+            detect_format(vcf_file).unwrap(),
+            (expected_compression, expected_format)
+        );
+    }
+
+    #[rstest]
+    #[case("data/test.vcf.gz", "bgzipped.vcf", true)] // bgzipped vcf without a .gz extension
+    #[case("data/test.bcf", "uncompressed.bcf", false)] // uncompressed BCF stream
+    fn test_build_variant_list_detects_content(
+        #[case] source_file: &str,
+        #[case] file_name: &str,
+        #[case] keep_compression: bool,
+    ) {
+        let vcf_file = std::env::temp_dir().join(file_name);
+        let mut content: Vec<u8> = vec![];
+        let mut source = File::open(source_file).unwrap();
+        match keep_compression {
+            true => source.read_to_end(&mut content).unwrap(),
+            false => bgzf::Reader::new(source).read_to_end(&mut content).unwrap(),
+        };
+        std::fs::write(&vcf_file, content).unwrap();
+        let variant_list = build_variant_list(
+            vcf_file.to_str().unwrap(),
+            &VariantFilter::default(),
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
+        std::fs::remove_file(&vcf_file).unwrap();
+        assert_eq!(variant_list.len(), 14);
+    }
+
+    #[rstest]
+    #[case("data/test.vcf", vec![String::from("1:38145491-38145540")], 0)]
+    #[case("data/test.vcf", vec![String::from("X:38144665-38144669")], 1)]
+    #[case("data/test.vcf", vec![String::from("X:38145491-38145540")], 7)]
+    #[case("data/test.vcf", vec![String::from("X:38144665-38144669"), String::from("X:38145491-38145540")], 8)]
+    #[case("data/test.vcf", vec![String::from("X:38145491-38145520"), String::from("X:38145510-38145540")], 7)]
+    #[case("data/family.vcf", vec![String::from("X:38145491-38145540")], 7)]
+    #[case("data/test.vcf", vec![String::from("X")], 14)]
+    fn test_build_variant_list_streaming_regions(
+        #[case] vcf_file: &str,
+        #[case] regions: Vec<String>,
+        #[case] expected_number_variants: usize,
+    ) {
+        // regions of a non-indexed vcf file are filtered while streaming the records
+        let variant_list = build_variant_list(
+            vcf_file,
+            &VariantFilter::new(false, 10),
+            intervals(&regions).as_ref(),
+            None,
+            None,
+        )
+        .unwrap()
+        .variants;
+        assert_eq!(variant_list.len(), expected_number_variants);
+    }
+
+    #[rstest]
+    #[case(vec![], vec![], 14, FilterCount { accepted: 14, rejected: 0 }, FilterCount { accepted: 0, rejected: 2 })]
+    #[case(vec!["RefCall"], vec![], 14, FilterCount { accepted: 14, rejected: 0 }, FilterCount { accepted: 2, rejected: 0 })] // RefCall records are no-calls
+    #[case(vec!["*"], vec!["PASS"], 0, FilterCount { accepted: 0, rejected: 14 }, FilterCount { accepted: 2, rejected: 0 })]
+    fn test_build_variant_list_filter_counts(
+        #[case] allowed_filters: Vec<&str>,
+        #[case] denied_filters: Vec<&str>,
+        #[case] expected_number_variants: usize,
+        #[case] expected_pass: FilterCount,
+        #[case] expected_ref_call: FilterCount,
+    ) {
+        let variant_filter = VariantFilter {
+            allowed_filters: allowed_filters.into_iter().map(String::from).collect(),
+            denied_filters: denied_filters.into_iter().map(String::from).collect(),
+            ..VariantFilter::default()
+        };
+        let sample_variants =
+            build_variant_list("data/test.vcf", &variant_filter, None, None, None).unwrap();
+        assert_eq!(sample_variants.variants.len(), expected_number_variants);
+        assert_eq!(sample_variants.filter_counts["PASS"], expected_pass);
+        assert_eq!(sample_variants.filter_counts["RefCall"], expected_ref_call);
+    }
+
+    #[rstest]
+    #[case(None, vec![])]
+    #[case(Some("data/panel.vcf"), vec![])]
+    #[case(None, vec![String::from("X:38145491-38145540")])]
+    fn test_build_variant_list_filter_counts_unaffected(
+        #[case] panel_file: Option<&str>,
+        #[case] exclude_regions: Vec<String>,
+    ) {
+        // the FILTER values are tallied before the known-sites panel and excluded regions
+        let variant_filter = VariantFilter {
+            sites_panel: panel_file.map(|panel_file| SitesPanel::from_file(panel_file).unwrap()),
+            exclude_regions: intervals(&exclude_regions),
+            ..VariantFilter::default()
+        };
+        let sample_variants =
+            build_variant_list("data/test.vcf", &variant_filter, None, None, None).unwrap();
+        assert_eq!(
+            sample_variants.filter_counts["PASS"],
+            FilterCount {
+                accepted: 14,
+                rejected: 0
+            }
+        );
+        assert_eq!(
+            sample_variants.filter_counts["RefCall"],
+            FilterCount {
+                accepted: 0,
+                rejected: 2
+            }
+        );
+    }
+
+    #[rstest]
+    #[case(None, None, 14)]
+    #[case(Some("FMT/GQ>=20"), None, 4)]
+    #[case(None, Some("FMT/AD[1]<450"), 10)]
+    fn test_build_variant_list_expression(
+        #[case] include: Option<&str>,
+        #[case] exclude: Option<&str>,
+        #[case] expected_number_variants: usize,
+    ) {
+        let variant_filter = VariantFilter {
+            include: include.map(|expression| expression.parse().unwrap()),
+            exclude: exclude.map(|expression| expression.parse().unwrap()),
+            ..VariantFilter::default()
+        };
+        let sample_variants =
+            build_variant_list("data/test.vcf", &variant_filter, None, None, None).unwrap();
+        assert_eq!(sample_variants.variants.len(), expected_number_variants);
+    }
+
+    #[rstest]
+    #[case(vec!["PASS"], vec![], vec![], true)]
+    #[case(vec!["LowQual"], vec![], vec![], false)]
+    #[case(vec!["LowQual"], vec!["LowQual"], vec![], true)]
+    #[case(vec!["LowQual", "RefCall"], vec!["LowQual"], vec![], false)]
+    #[case(vec!["LowQual"], vec!["*"], vec![], true)]
+    #[case(vec!["LowQual"], vec!["*"], vec!["LowQual"], false)]
+    #[case(vec!["PASS"], vec![], vec!["PASS"], false)]
+    fn test_accepts_filters(
+        #[case] filter_values: Vec<&str>,
+        #[case] allowed_filters: Vec<&str>,
+        #[case] denied_filters: Vec<&str>,
+        #[case] expected: bool,
+    ) {
+        let variant_filter = VariantFilter {
+            allowed_filters: allowed_filters.into_iter().map(String::from).collect(),
+            denied_filters: denied_filters.into_iter().map(String::from).collect(),
+            ..VariantFilter::default()
+        };
+        let filter_values: Vec<String> = filter_values.into_iter().map(String::from).collect();
+        assert_eq!(variant_filter.accepts_filters(&filter_values), expected);
+    }
+
+    #[rstest]
+    #[case(None, 14)]
+    #[case(Some("proband"), 14)]
+    #[case(Some("mother"), 9)] // hom-ref calls are skipped
+    #[case(Some("1"), 9)]
+    fn test_build_variant_list_multi_sample(
+        #[case] sample: Option<&str>,
+        #[case] expected_number_variants: usize,
+    ) {
+        let variant_list = build_variant_list(
+            "data/family.vcf",
+            &VariantFilter::new(false, 0),
+            None,
+            None,
+            sample,
+        )
+        .unwrap()
+        .variants;
+        assert_eq!(variant_list.len(), expected_number_variants);
+    }
+
+    #[rstest]
+    #[case(Some("father"), "Sample father not found in vcf file")]
+    #[case(Some("2"), "Sample index 2 is out of range")]
+    fn test_build_variant_list_missing_sample(
+        #[case] sample: Option<&str>,
+        #[case] expected_error: &str,
+    ) {
+        let error = build_variant_list(
+            "data/family.vcf",
+            &VariantFilter::new(false, 0),
+            None,
+            None,
+            sample,
+        )
+        .unwrap_err();
+        assert!(error.to_string().starts_with(expected_error));
+        assert_eq!(error.exit_code(), 2);
+    }
+
     #[rstest]
     #[case(
         0,
@@ -339,7 +2141,10 @@ mod tests {
         #[case] variant_type: VariantType,
     ) {
         let vcf_file = "data/test.vcf";
-        let variant_list = build_variant_list(&vcf_file, false, 0, vec![]).unwrap();
+        let variant_list =
+            build_variant_list(vcf_file, &VariantFilter::new(false, 0), None, None, None)
+                .unwrap()
+                .variants;
         let record = &variant_list[record_idx];
         assert_eq!(record.zygosity, zygosity);
         assert_eq!(record.alt_depth, alt_depth);